@@ -0,0 +1,99 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use log::{error, info};
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+pub const ADMIN_ADDR_ENV: &str = "ADMIN_ADDR";
+/// Path prefix of the endpoint which triggers a run of a registry. The name of the registry is appended
+/// to the prefix (e.g. `/run/registry`)
+const RUN_PATH: &str = "/run/";
+
+/// Request to apply all rules of a registry once. The receiver answers whether the registry is scheduled
+#[derive(Debug)]
+pub struct RunRequest {
+    pub registry: String,
+    pub respond: oneshot::Sender<bool>
+}
+
+/// Start the admin http server on the given address
+pub fn start_admin_server(address: SocketAddr, tx: Sender<RunRequest>) {
+    let service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle_request(req, tx.clone()))) }
+    });
+    match Server::try_bind(&address) {
+        Ok(builder) => {
+            info!("Listening for admin requests on {address}");
+            tokio::spawn(async move {
+                if let Err(err) = builder.serve(service).await {
+                    error!("Admin server stopped. Reason: {err}")
+                }
+            });
+        },
+        Err(err) => error!("Unable to listen for admin requests on {address}. Reason: {err}")
+    }
+}
+
+async fn handle_request(req: Request<Body>, tx: Sender<RunRequest>) -> Result<Response<Body>, Infallible> {
+    let registry = match req.uri().path().strip_prefix(RUN_PATH) {
+        Some(registry) if !registry.is_empty() && req.method() == Method::POST => registry.to_string(),
+        _ => return Ok(response(StatusCode::NOT_FOUND, json!({ "error": "Not found" })))
+    };
+    let (respond, rx) = oneshot::channel();
+    if let Err(err) = tx.send(RunRequest { registry: registry.clone(), respond }).await {
+        error!("Unable to forward run request for registry '{registry}'. Reason: {err}");
+        return Ok(response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": "Unable to trigger run" })))
+    }
+    match rx.await {
+        Ok(true) => Ok(response(StatusCode::ACCEPTED, json!({ "registry": registry, "status": "accepted" }))),
+        Ok(false) => Ok(response(StatusCode::NOT_FOUND, json!({ "error": format!("Registry '{registry}' isn't scheduled") }))),
+        Err(err) => {
+            error!("Unable to trigger run of registry '{registry}'. Reason: {err}");
+            Ok(response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": "Unable to trigger run" })))
+        }
+    }
+}
+
+fn response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("Content type should be valid header value"));
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{Body, Request, StatusCode};
+    use serde_json::json;
+    use crate::admin::{handle_request, RunRequest};
+
+    async fn answer(mut rx: tokio::sync::mpsc::Receiver<RunRequest>, known: &'static str) {
+        while let Some(request) = rx.recv().await {
+            request.respond.send(request.registry == known).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_request() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(answer(rx, "registry"));
+
+        let req = Request::post("/run/registry").body(Body::empty()).unwrap();
+        let resp = handle_request(req, tx.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "registry": "registry", "status": "accepted" }));
+
+        let req = Request::post("/run/other").body(Body::empty()).unwrap();
+        assert_eq!(handle_request(req, tx.clone()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let req = Request::post("/run/").body(Body::empty()).unwrap();
+        assert_eq!(handle_request(req, tx.clone()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let req = Request::get("/run/registry").body(Body::empty()).unwrap();
+        assert_eq!(handle_request(req, tx).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use log::debug;
+use reqwest::header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE};
+use reqwest::{Request, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use crate::api::DistributionConfig;
+use crate::api::error::ApiError;
+
+/// Per default a token is valid for 60 seconds as specified by the token authentication specification
+const DEFAULT_TOKEN_LIFETIME: u64 = 60;
+/// Tokens are renewed this long before they expire to account for the duration of the requests
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(5);
+
+/// Challenge of a registry which requires a bearer token issued by an authorization service
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct BearerChallenge {
+    pub realm: String,
+    pub service: Option<String>,
+    pub scope: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+    expires_in: Option<u64>
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: String,
+    expires: Instant
+}
+
+/// Tokens cached per user and challenge. The challenge contains the scope of the token
+type TokenCache = HashMap<(Option<String>, BearerChallenge), CachedToken>;
+
+fn token_cache() -> &'static Mutex<TokenCache> {
+    static TOKENS: OnceLock<Mutex<TokenCache>> = OnceLock::new();
+    TOKENS.get_or_init(Default::default)
+}
+
+/// Last challenge per user and registry host. Requests to a host whose challenge has a cached token are sent with the
+/// token right away instead of being challenged again
+type ChallengeCache = HashMap<(Option<String>, String), BearerChallenge>;
+
+fn challenge_cache() -> &'static Mutex<ChallengeCache> {
+    static CHALLENGES: OnceLock<Mutex<ChallengeCache>> = OnceLock::new();
+    CHALLENGES.get_or_init(Default::default)
+}
+
+/// Get the unexpired cached token of the last challenge of the host together with the challenge
+fn cached_token(config: &DistributionConfig, host: &str) -> Option<(BearerChallenge, String)> {
+    let challenge = challenge_cache().lock().ok()?.get(&(config.username.clone(), host.to_string())).cloned()?;
+    let cached = token_cache().lock().ok()?.get(&(config.username.clone(), challenge.clone())).cloned()?;
+    (cached.expires > Instant::now()).then_some((challenge, cached.token))
+}
+
+/// Replace the basic auth credentials of the registry url with a bearer token
+fn authorize(request: &mut Request, token: &str) -> Result<(), ApiError> {
+    request.headers_mut().insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {token}"))
+        .map_err(|_| ApiError::InvalidHeaderValue(String::from("Authorization")))?);
+    Ok(())
+}
+
+/// Parse a `WWW-Authenticate` header with a bearer challenge (e.g. `Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull"`).
+/// Returns `None` for other authentication schemes
+pub fn parse_challenge(header: &str) -> Option<BearerChallenge> {
+    let (scheme, parameters) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None
+    }
+    let mut values = HashMap::new();
+    let mut rest = parameters.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let (value, remaining) = match value.strip_prefix('"') {
+            // quoted values can contain commas (e.g. a scope with multiple actions)
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            },
+            None => value.split_once(',').unwrap_or((value, ""))
+        };
+        values.insert(key.trim().to_lowercase(), value.to_string());
+        rest = remaining.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    Some(BearerChallenge {
+        realm: values.remove("realm")?,
+        service: values.remove("service"),
+        scope: values.remove("scope")
+    })
+}
+
+/// Send a request to the registry. Should the registry respond with a bearer challenge a token is requested from the
+/// authorization service using the credentials of the registry and the request is retried with the token. Tokens are
+/// cached per scope until they expire and requests to a host are sent with the cached token of its last challenge
+pub async fn send(config: &DistributionConfig, request: RequestBuilder) -> Result<Response, ApiError> {
+    let retry = request.try_clone();
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let host = format!("{}:{}", request.url().host_str().unwrap_or_default(), request.url().port_or_known_default().unwrap_or_default());
+    let attached = cached_token(config, &host);
+    if let Some((_, token)) = &attached {
+        authorize(&mut request, token)?;
+    }
+    let response = client.execute(request).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response)
+    }
+    let challenge = response.headers().get(WWW_AUTHENTICATE).and_then(|header| header.to_str().ok()).and_then(parse_challenge);
+    let (Some(challenge), Some(retry)) = (challenge, retry) else {
+        return Ok(response)
+    };
+
+    // the attached token was rejected for its own challenge (e.g. it was revoked) and mustn't be reused
+    if attached.is_some_and(|(attached, _)| attached == challenge) {
+        if let Ok(mut tokens) = token_cache().lock() {
+            tokens.remove(&(config.username.clone(), challenge.clone()));
+        }
+    }
+    let token = get_token(config, &challenge).await?;
+    if let Ok(mut challenges) = challenge_cache().lock() {
+        challenges.insert((config.username.clone(), host), challenge);
+    }
+    let (client, request) = retry.build_split();
+    let mut request = request?;
+    authorize(&mut request, &token)?;
+    Ok(client.execute(request).await?)
+}
+
+/// Get a token for the challenge from the cache or request a new one from the authorization service
+async fn get_token(config: &DistributionConfig, challenge: &BearerChallenge) -> Result<String, ApiError> {
+    let key = (config.username.clone(), challenge.clone());
+    if let Some(cached) = token_cache().lock().ok().and_then(|tokens| tokens.get(&key).cloned()) {
+        if cached.expires > Instant::now() {
+            return Ok(cached.token)
+        }
+    }
+
+    debug!("Requesting token for scope '{}' from '{}'", challenge.scope.clone().unwrap_or_default(), challenge.realm);
+    let mut query = vec![];
+    if let Some(service) = &challenge.service {
+        query.push(("service", service.as_str()));
+    }
+    if let Some(scope) = &challenge.scope {
+        query.push(("scope", scope.as_str()));
+    }
+    let mut request = config.client()?.get(&challenge.realm).query(&query);
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        request = request.basic_auth(username, Some(password));
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let body = response.text().await?;
+        return Err(ApiError::AuthenticationFailed(body.trim().to_string()))
+    }
+    let body = response.json::<TokenResponse>().await?;
+    let token = body.token.or(body.access_token).ok_or(ApiError::AuthenticationFailed(String::from("The response didn't contain a token")))?;
+    let lifetime = Duration::from_secs(body.expires_in.unwrap_or(DEFAULT_TOKEN_LIFETIME)).saturating_sub(TOKEN_EXPIRY_MARGIN);
+    if let Ok(mut tokens) = token_cache().lock() {
+        tokens.insert(key, CachedToken { token: token.clone(), expires: Instant::now() + lifetime });
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod test {
+    use mockito::Matcher;
+    use crate::api::auth::{BearerChallenge, parse_challenge};
+    use crate::api::distribution::Distribution;
+    use crate::api::DistributionConfig;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_challenge() {
+        let header = r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:samalba/my-app:pull,push""#;
+        assert_eq!(parse_challenge(header), Some(BearerChallenge {
+            realm: String::from("https://auth.docker.io/token"),
+            service: Some(String::from("registry.docker.io")),
+            scope: Some(String::from("repository:samalba/my-app:pull,push"))
+        }));
+        assert_eq!(parse_challenge(r#"bearer realm="https://ghcr.io/token""#).map(|challenge| challenge.scope), Some(None));
+        assert_eq!(parse_challenge(r#"Basic realm="Registry Realm""#), None);
+        assert_eq!(parse_challenge(r#"Bearer service="registry.docker.io""#), None);
+    }
+
+    #[tokio::test]
+    async fn test_token_authentication() {
+        let mut server = mockito::Server::new_async().await;
+        let challenge = format!(r#"Bearer realm="{}/token",service="registry",scope="registry:catalog:*""#, server.url());
+        let unauthorized = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::Any)
+            .match_header("authorization", Matcher::Regex(String::from("^Basic ")))
+            .with_status(401)
+            .with_header("WWW-Authenticate", &challenge)
+            .expect(1)
+            .create_async()
+            .await;
+        let token = server.mock("GET", "/token")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(String::from("service"), String::from("registry")),
+                Matcher::UrlEncoded(String::from("scope"), String::from("registry:catalog:*"))
+            ]))
+            .match_header("authorization", "Basic dXNlcjpwYXNz")
+            .with_body(r#"{"token":"secret","expires_in":300}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let catalog = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "Bearer secret")
+            .with_body(r#"{"repositories":["app"]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let config = DistributionConfig::new(server.host_with_port(), Some(String::from("user")), Some(String::from("pass")), true);
+        let distribution = Distribution::new(Arc::new(config));
+
+        // the second request is sent with the cached token right away
+        for _ in 0..2 {
+            let repositories = distribution.get_repositories(None).await.unwrap();
+            assert_eq!(repositories.into_iter().map(|repo| repo.name).collect::<Vec<_>>(), vec!["app"]);
+        }
+        unauthorized.assert_async().await;
+        token.assert_async().await;
+        catalog.assert_async().await;
+    }
+}
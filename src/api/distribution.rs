@@ -1,9 +1,15 @@
 use std::sync::Arc;
+use futures::{Stream, stream, TryStreamExt};
+use reqwest::StatusCode;
+use crate::api::auth::send;
 use crate::api::repository::Repository;
-use crate::api::ApiCatalog;
+use crate::api::{ApiCatalog, encode_repository_name};
 use crate::api::DistributionConfig;
 use crate::api::error::ApiError;
-use crate::api::request::{get_follow_path, handle_response};
+use crate::api::request::{get_follow_path, handle_response, send_with_retry};
+
+/// Amount of repositories requested per catalog page
+const CATALOG_PAGE_SIZE: usize = 100;
 
 #[derive(Debug)]
 pub struct Distribution {
@@ -15,27 +21,226 @@ impl Distribution {
         Self { config }
     }
 
-    /// Get all repositories present in the registry
-    pub async fn get_repositories(&self) -> Result<Vec<Repository>, ApiError> {
-        let mut images = Vec::<Repository>::new();
-        let mut link = Some(self.config.url("/v2/_catalog?n=100"));
-
-        while link.is_some() {
-            let mut resp = reqwest::get(link.expect("Link exists")).await?;
-            resp = handle_response(resp).await?;
-            link = get_follow_path(resp.headers())?;
-            if let Some(l) = link {
-                link = Some(self.config.url(l.as_str()))
-            }
-            let body = resp.json::<ApiCatalog>().await?;
-            images.append(
-                &mut body
-                    .repositories
-                    .into_iter()
-                    .map(|repo| Repository::new(repo, self.config.clone()))
-                    .collect::<Vec<_>>(),
-            );
+    /// Check whether the registry is reachable and supports the v2 api by requesting the `/v2/` endpoint. <br>
+    /// The request isn't retried to fail fast when the registry is misconfigured
+    pub async fn ping(&self) -> Result<(), ApiError> {
+        let response = send(&self.config, self.config.client()?.get(self.config.url("/v2/"))).await?;
+        // registries without the v2 api don't know the endpoint
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(ApiError::UnsupportedRegistry),
+            _ => handle_response(response).await.map(|_| ())
+        }
+    }
+
+    /// Get all repositories present in the registry. When a prefix is provided it's sent to the registry as `prefix`
+    /// query parameter and the repositories are additionally filtered by it since most registries ignore the parameter
+    pub async fn get_repositories(&self, prefix: Option<&str>) -> Result<Vec<Repository>, ApiError> {
+        self.get_repository_stream(prefix).try_collect().await
+    }
+
+    /// Like [`Distribution::get_repositories`] but yields the repositories page by page while the `Link` header is
+    /// followed instead of collecting the whole catalog first. The stream ends after the first error
+    pub fn get_repository_stream<'a>(&'a self, prefix: Option<&'a str>) -> impl Stream<Item = Result<Repository, ApiError>> + 'a {
+        let link = self.catalog_url(prefix, None);
+        stream::try_unfold(Some((link, None)), move |page| async move {
+            let Some((link, last)) = page else {
+                return Ok(None)
+            };
+            self.get_catalog_page(link, last, prefix).await.map(Some)
+        })
+            .map_ok(|repositories| stream::iter(repositories.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// Get the url of the catalog page after the repository `last`
+    fn catalog_url(&self, prefix: Option<&str>, last: Option<&str>) -> String {
+        let mut url = format!("/v2/_catalog?n={CATALOG_PAGE_SIZE}");
+        if let Some(last) = last {
+            url.push_str(format!("&last={}", encode_repository_name(last)).as_str());
         }
-        Ok(images)
+        if let Some(prefix) = prefix {
+            url.push_str(format!("&prefix={}", encode_repository_name(prefix)).as_str());
+        }
+        self.config.url(url.as_str())
+    }
+
+    /// Get the repositories of a single catalog page and the link to the next page. When the registry doesn't send a `Link`
+    /// header but returned a full page, the next page is requested using the `last` query parameter instead
+    async fn get_catalog_page(&self, link: String, last: Option<String>, prefix: Option<&str>) -> Result<(Vec<Repository>, Option<(String, Option<String>)>), ApiError> {
+        let mut resp = send_with_retry(&self.config, self.config.client()?.get(link)).await?;
+        resp = handle_response(resp).await?;
+        let follow = get_follow_path(resp.headers())?.map(|link| self.config.follow_url(link.as_str()));
+        let body = resp.json::<ApiCatalog>().await?;
+        let next = match follow {
+            Some(link) => Some((link, None)),
+            // registries which ignore the `last` parameter would return the same page forever
+            None if body.repositories.len() >= CATALOG_PAGE_SIZE => body.repositories.last()
+                .filter(|name| last.as_ref() != Some(*name))
+                .map(|name| (self.catalog_url(prefix, Some(name)), Some(name.clone()))),
+            None => None
+        };
+        let repositories = body
+            .repositories
+            .into_iter()
+            .filter(|repo| prefix.is_none_or(|prefix| repo.starts_with(prefix)))
+            .map(|repo| Repository::new(repo, self.config.clone()))
+            .collect::<Vec<_>>();
+        Ok((repositories, next))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+    use mockito::Matcher;
+    use crate::api::distribution::Distribution;
+    use crate::api::error::ApiError;
+    use crate::test::get_mock_distribution_config;
+
+    async fn test_pagination(absolute: bool) {
+        let mut server = mockito::Server::new_async().await;
+        let next = if absolute {
+            format!("{}/v2/_catalog?last=b&n=100", server.url())
+        } else {
+            String::from("/v2/_catalog?last=b&n=100")
+        };
+        let first = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::UrlEncoded(String::from("n"), String::from("100")))
+            .with_header("Link", format!("<{next}>; rel=\"next\"").as_str())
+            .with_body(r#"{"repositories":["a","b"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::AllOf(vec![Matcher::UrlEncoded(String::from("last"), String::from("b")), Matcher::UrlEncoded(String::from("n"), String::from("100"))]))
+            .with_body(r#"{"repositories":["c"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let repositories = Distribution::new(get_mock_distribution_config(&server)).get_repositories(None).await.unwrap();
+        assert_eq!(repositories.into_iter().map(|repo| repo.name).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_relative_link() {
+        test_pagination(false).await
+    }
+
+    #[tokio::test]
+    async fn test_absolute_link() {
+        test_pagination(true).await
+    }
+
+    #[tokio::test]
+    async fn test_last_pagination() {
+        let mut server = mockito::Server::new_async().await;
+        let names = (0..150).map(|index| format!("repo-{index:03}")).collect::<Vec<_>>();
+        let catalog = |names: &[String]| serde_json::json!({ "repositories": names }).to_string();
+        let first = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::Exact(String::from("n=100")))
+            .with_body(catalog(&names[..100]))
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::AllOf(vec![Matcher::UrlEncoded(String::from("last"), String::from("repo-099")), Matcher::UrlEncoded(String::from("n"), String::from("100"))]))
+            .with_body(catalog(&names[100..]))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let repositories = Distribution::new(get_mock_distribution_config(&server)).get_repositories(None).await.unwrap();
+        assert_eq!(repositories.into_iter().map(|repo| repo.name).collect::<Vec<_>>(), names);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_ignored_last_parameter() {
+        let mut server = mockito::Server::new_async().await;
+        let names = (0..100).map(|index| format!("repo-{index:03}")).collect::<Vec<_>>();
+        // the registry ignores the `last` parameter and always returns the first page
+        let catalog = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::Any)
+            .with_body(serde_json::json!({ "repositories": names }).to_string())
+            .expect(2)
+            .create_async()
+            .await;
+
+        let repositories = Distribution::new(get_mock_distribution_config(&server)).get_repositories(None).await.unwrap();
+        assert_eq!(repositories.len(), 200);
+        catalog.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let mut server = mockito::Server::new_async().await;
+        let distribution = Distribution::new(get_mock_distribution_config(&server));
+        let version = server.mock("GET", "/v2/")
+            .with_header("Docker-Distribution-API-Version", "registry/2.0")
+            .with_body("{}")
+            .create_async()
+            .await;
+        assert!(distribution.ping().await.is_ok());
+        version.remove_async().await;
+
+        let version = server.mock("GET", "/v2/").with_header("Docker-Distribution-API-Version", "registry/1.0").create_async().await;
+        assert!(matches!(distribution.ping().await, Err(ApiError::UnsupportedRegistry)));
+        version.remove_async().await;
+
+        let unauthorized = server.mock("GET", "/v2/").with_status(401).create_async().await;
+        assert!(matches!(distribution.ping().await, Err(ApiError::Unauthorized)));
+        unauthorized.remove_async().await;
+
+        let missing = server.mock("GET", "/v2/").with_status(404).create_async().await;
+        assert!(matches!(distribution.ping().await, Err(ApiError::UnsupportedRegistry)));
+        missing.remove_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_repository_stream() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::UrlEncoded(String::from("n"), String::from("100")))
+            .with_header("Link", "</v2/_catalog?last=b&n=100>; rel=\"next\"")
+            .with_body(r#"{"repositories":["a","b"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::UrlEncoded(String::from("last"), String::from("b")))
+            .with_status(500)
+            .with_body("internal error")
+            .create_async()
+            .await;
+
+        let distribution = Distribution::new(get_mock_distribution_config(&server));
+        let mut stream = Box::pin(distribution.get_repository_stream(None));
+        assert_eq!(stream.next().await.unwrap().unwrap().name, "a");
+        assert_eq!(stream.next().await.unwrap().unwrap().name, "b");
+        first.assert_async().await;
+        // the second page is only requested once the first page is consumed
+        assert!(!second.matched_async().await);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_catalog_prefix() {
+        let mut server = mockito::Server::new_async().await;
+        let catalog = server.mock("GET", "/v2/_catalog")
+            .match_query(Matcher::AllOf(vec![Matcher::UrlEncoded(String::from("n"), String::from("100")), Matcher::UrlEncoded(String::from("prefix"), String::from("team-a/"))]))
+            .with_body(r#"{"repositories":["team-a/api","team-a/app","team-b/app","team-ab"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let repositories = Distribution::new(get_mock_distribution_config(&server)).get_repositories(Some("team-a/")).await.unwrap();
+        assert_eq!(repositories.into_iter().map(|repo| repo.name).collect::<Vec<_>>(), vec!["team-a/api", "team-a/app"]);
+        catalog.assert_async().await;
     }
 }
@@ -9,11 +9,17 @@ pub enum ApiError {
     InvalidHeaderValue(String),
 
     #[error("There was an error during the request: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error),
+
+    #[error("The request to the registry timed out: {0}")]
+    Timeout(String),
 
     #[error("Received error from api: '{0}'")]
     RegistryError(String),
 
+    #[error("The requested resource doesn't exist: '{0}'")]
+    NotFound(String),
+
     #[error("The given blob can't be converted to the provided struct type")]
     InvalidBlobType,
 
@@ -25,4 +31,28 @@ pub enum ApiError {
 
     #[error("The manifest list didn't contain any manifests")]
     EmptyManifestList,
-}
\ No newline at end of file
+
+    #[error("The artifact has to be a single manifest with at least one layer")]
+    InvalidArtifact,
+
+    #[error("The registry refused to delete an immutable manifest: '{0}'")]
+    ImmutableManifest(String),
+
+    #[error("Unable to get a token from the authorization service: '{0}'")]
+    AuthenticationFailed(String),
+
+    #[error("Unable to load the ca certificate '{0}'")]
+    InvalidCertificate(String),
+
+    #[error("The registry requires authentication but the credentials are missing or invalid")]
+    Unauthorized,
+}
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ApiError::Timeout(err.to_string())
+        } else {
+            ApiError::RequestError(err)
+        }
+    }
+}
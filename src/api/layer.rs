@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -6,4 +7,39 @@ pub struct Layer {
     pub media_type: String,
     pub digest: String,
     pub size: u64,
+    /// Only present on the entries of a manifest list (aka OCI index)
+    pub platform: Option<Platform>,
+}
+
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// Whether the platform is a placeholder (e.g. for attestation manifests) instead of a real platform
+    pub fn is_unknown(&self) -> bool {
+        self.os == "unknown" || self.architecture == "unknown"
+    }
+
+    /// Whether the platform matches a `<os>/<architecture>[/<variant>]` string. When the string doesn't
+    /// contain a variant any variant of the platform matches
+    pub fn matches(&self, platform: &str) -> bool {
+        let mut parts = platform.split('/');
+        let (os, architecture, variant) = (parts.next(), parts.next(), parts.next());
+        os == Some(self.os.as_str())
+            && architecture == Some(self.architecture.as_str())
+            && variant.is_none_or(|variant| self.variant.as_deref() == Some(variant))
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.variant {
+            Some(variant) => write!(f, "{}/{}/{variant}", self.os, self.architecture),
+            None => write!(f, "{}/{}", self.os, self.architecture)
+        }
+    }
 }
@@ -1,13 +1,18 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use crate::api::layer::Layer;
 use crate::api::repository::Repository;
 use crate::api::ApiManifest;
-use crate::api::{get_request_client, DistributionConfig, MANIFEST_CONTENT_TYPE};
+use crate::api::{accept_header, DistributionConfig, MANIFEST_CONTENT_TYPE, OCI_MANIFEST_MEDIA_TYPE};
 use futures::future::try_join_all;
+use reqwest::header::ACCEPT;
 use serde::Deserialize;
 use crate::api::error::ApiError;
-use crate::api::request::handle_response;
+use crate::api::request::{handle_response, send_with_retry};
+
+/// Annotation of oci manifests and indexes which contains the creation time of the image
+pub const CREATED_ANNOTATION: &str = "org.opencontainers.image.created";
 
 #[derive(Debug, Clone)]
 pub struct Manifest {
@@ -17,6 +22,7 @@ pub struct Manifest {
     pub media_type: String,
     pub layers: Vec<Layer>,
     pub digest: String,
+    pub annotations: HashMap<String, String>,
 }
 
 impl Manifest {
@@ -35,9 +41,15 @@ impl Manifest {
             layers,
             repository,
             manifest_config,
+            annotations: HashMap::new(),
         }
     }
 
+    pub fn with_annotations(mut self, annotations: HashMap<String, String>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
     /// Get the config blob for the manifest
     pub async fn get_config(&self) -> Result<ManifestConfig, ApiError> {
         let blob = self
@@ -49,6 +61,22 @@ impl Manifest {
             .await?;
         serde_json::from_value::<ManifestConfig>(blob).map_err(|_| ApiError::InvalidBlobType)
     }
+
+    /// Get the creation time of the image. The `org.opencontainers.image.created` annotation of the index the manifest
+    /// belongs to takes precedence over the annotation of the manifest since it describes the whole multi-arch image.
+    /// Only without an annotation the config blob is pulled
+    pub async fn get_created(&self, index: Option<&HashMap<String, String>>) -> Result<DateTime<Utc>, ApiError> {
+        match index.and_then(annotated_created).or_else(|| annotated_created(&self.annotations)) {
+            Some(created) => Ok(created),
+            None => Ok(self.get_config().await?.created)
+        }
+    }
+}
+
+/// Read the creation time of the `org.opencontainers.image.created` annotation. Invalid timestamps are ignored
+pub fn annotated_created(annotations: &HashMap<String, String>) -> Option<DateTime<Utc>> {
+    let created = annotations.get(CREATED_ANNOTATION)?;
+    DateTime::parse_from_rfc3339(created.trim()).ok().map(|created| created.with_timezone(&Utc))
 }
 
 #[derive(Debug)]
@@ -59,6 +87,7 @@ pub struct ManifestList {
     pub schema_version: u32,
     pub media_type: String,
     pub manifests: Vec<Layer>,
+    pub annotations: HashMap<String, String>,
 }
 
 impl ManifestList {
@@ -77,9 +106,15 @@ impl ManifestList {
             repository,
             digest,
             config,
+            annotations: HashMap::new(),
         }
     }
 
+    pub fn with_annotations(mut self, annotations: HashMap<String, String>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
     /// Get a specific manifest from the manifest list by it's digest
     pub async fn get_manifest(&self, digest: String) -> Result<Manifest, ApiError> {
         let content_type = self
@@ -88,22 +123,26 @@ impl ManifestList {
             .find(|m| m.digest == digest)
             .map(|l| l.media_type.clone())
             .unwrap_or(String::from(MANIFEST_CONTENT_TYPE));
-        let client = get_request_client(format!("{content_type}").as_str())?;
-        let mut resp = client
-            .get(self.config.url(format!("/v2/{}/manifests/{digest}", self.repository.name).as_str()))
-            .send()
-            .await?;
+        let client = self.config.client()?;
+        let mut resp = send_with_retry(&self.config, client.get(self.repository.url(format!("manifests/{digest}").as_str())).header(ACCEPT, accept_header(content_type.as_str())?)).await?;
         resp = handle_response(resp).await?;
 
         let manifest = resp.json::<ApiManifest>().await?;
+        let media_type = if manifest.media_type.is_empty() { String::from(OCI_MANIFEST_MEDIA_TYPE) } else { manifest.media_type };
         Ok(Manifest::new(
             manifest.schema_version,
-            manifest.media_type,
+            media_type,
             manifest.layers,
             self.repository.clone(),
             manifest.config,
             digest,
-        ))
+        ).with_annotations(manifest.annotations))
+    }
+
+    /// Get the entry of the manifest list which represents the whole list. The entry is selected
+    /// using the platform chain from the [`DistributionConfig`]
+    pub fn get_representative(&self) -> Option<&Layer> {
+        select_platform(&self.manifests, &self.config.platforms)
     }
 
     /// Get all manifests of the manifest list in parallel
@@ -117,6 +156,21 @@ impl ManifestList {
     }
 }
 
+/// Select an entry of a manifest list by an ordered platform chain. Each element of the chain is either a platform
+/// in the `<os>/<architecture>[/<variant>]` format or `*` which stands for the first entry with a non-unknown platform. <br>
+/// Should no element of the chain match any entry the first entry is used
+pub fn select_platform<'a>(manifests: &'a [Layer], chain: &[String]) -> Option<&'a Layer> {
+    chain.iter()
+        .find_map(|preferred| {
+            manifests.iter().find(|manifest| match &manifest.platform {
+                Some(platform) if preferred == "*" => !platform.is_unknown(),
+                Some(platform) => platform.matches(preferred),
+                None => false
+            })
+        })
+        .or(manifests.first())
+}
+
 #[derive(Debug)]
 pub enum ManifestResponse {
     ManifestList(ManifestList),
@@ -133,3 +187,66 @@ pub enum ManifestResponse {
 pub struct ManifestConfig {
     pub created: DateTime<Utc>,
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use crate::api::layer::Layer;
+    use crate::api::manifest::{annotated_created, CREATED_ANNOTATION, select_platform};
+
+    fn get_manifests() -> Vec<Layer> {
+        serde_json::from_str(r#"[
+            { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:attestation", "size": 1, "platform": { "architecture": "unknown", "os": "unknown" } },
+            { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:arm", "size": 1, "platform": { "architecture": "arm64", "os": "linux", "variant": "v8" } },
+            { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:amd", "size": 1, "platform": { "architecture": "amd64", "os": "linux" } }
+        ]"#).expect("Manifests should be valid")
+    }
+
+    fn chain(raw: Vec<&str>) -> Vec<String> {
+        raw.into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_preferred_present() {
+        let manifests = get_manifests();
+        let selected = select_platform(&manifests, &chain(vec!["linux/amd64", "linux/arm64", "*"]));
+        assert_eq!(selected.map(|m| m.digest.as_str()), Some("sha256:amd"));
+    }
+
+    #[test]
+    fn test_fallback_used() {
+        let manifests = get_manifests();
+        let selected = select_platform(&manifests, &chain(vec!["linux/s390x", "linux/arm64", "*"]));
+        assert_eq!(selected.map(|m| m.digest.as_str()), Some("sha256:arm"));
+    }
+
+    #[test]
+    fn test_variant() {
+        let manifests = get_manifests();
+        assert_eq!(select_platform(&manifests, &chain(vec!["linux/arm64/v7", "linux/amd64"])).map(|m| m.digest.as_str()), Some("sha256:amd"));
+        assert_eq!(select_platform(&manifests, &chain(vec!["linux/arm64/v8"])).map(|m| m.digest.as_str()), Some("sha256:arm"));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let manifests = get_manifests();
+        let selected = select_platform(&manifests, &chain(vec!["windows/amd64", "*"]));
+        assert_eq!(selected.map(|m| m.digest.as_str()), Some("sha256:arm"));
+    }
+
+    #[test]
+    fn test_exhausted_chain() {
+        let manifests = get_manifests();
+        let selected = select_platform(&manifests, &chain(vec!["windows/amd64"]));
+        assert_eq!(selected.map(|m| m.digest.as_str()), Some("sha256:attestation"));
+        assert!(select_platform(&[], &chain(vec!["*"])).is_none());
+    }
+
+    #[test]
+    fn test_annotated_created() {
+        let annotations = |created: &str| HashMap::from([(String::from(CREATED_ANNOTATION), String::from(created))]);
+        assert_eq!(annotated_created(&annotations("2024-01-01T12:00:00Z")).map(|created| created.to_rfc3339()), Some(String::from("2024-01-01T12:00:00+00:00")));
+        assert_eq!(annotated_created(&annotations("yesterday")), None);
+        assert_eq!(annotated_created(&HashMap::new()), None);
+    }
+}
@@ -1,34 +1,56 @@
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, ACCEPT};
+use reqwest::StatusCode;
+use tokio::time::sleep;
 use crate::api::manifest::{Manifest, ManifestList, ManifestResponse};
-use crate::api::{get_request_client, DistributionConfig, INDEX_CONTENT_TYPE, MANIFEST_CONTENT_TYPE};
+use crate::api::{accept_header, encode_repository_name, parse_digest, DistributionConfig, DOCKER_MANIFEST_MEDIA_TYPE, INDEX_CONTENT_TYPE, MANIFEST_CONTENT_TYPE, OCI_INDEX_MEDIA_TYPE, OCI_MANIFEST_MEDIA_TYPE};
 use crate::api::{ApiManifest, ApiManifestList, ApiTags};
 use crate::api::error::ApiError;
-use crate::api::request::{get_follow_path, handle_response};
+use crate::api::auth::send;
+use crate::api::request::{get_follow_path, handle_delete_response, handle_response, send_with_retry};
 use serde_json::Value;
 use crate::api::tag::Tag;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Repository {
     pub name: String,
+    /// Amount of tags in the repository. Only present after calling [`Repository::with_tag_count`]
+    pub tag_count: Option<usize>,
     config: Arc<DistributionConfig>,
 }
 
 impl Repository {
     pub fn new(repo: String, config: Arc<DistributionConfig>) -> Self {
-        Self { name: repo, config }
+        Self { name: repo, tag_count: None, config }
+    }
+
+    /// Get the url of a path relative to this repository. The repository name is percent-encoded to
+    /// support names with characters which aren't allowed in a url path
+    pub fn url(&self, rest: &str) -> String {
+        self.config.url(format!("/v2/{}/{rest}", encode_repository_name(&self.name)).as_str())
+    }
+
+    /// Load the amount of tags in this repository. This is needed for repository policies
+    /// which depend on the tags of a repository
+    pub async fn with_tag_count(mut self) -> Result<Self, ApiError> {
+        self.tag_count = Some(self.get_tags().await?.len());
+        Ok(self)
     }
 
     /// Get all tags on this repository
     pub async fn get_tags(&self) -> Result<Vec<String>, ApiError> {
         let mut tags = Vec::<String>::new();
-        let mut link = Some(self.config.url(format!("/v2/{}/tags/list?n=100", self.name).as_str()));
+        let mut link = Some(self.url("tags/list?n=100"));
 
         while link.is_some() {
-            let mut resp = reqwest::get(link.expect("Link exists")).await?;
+            let mut resp = send_with_retry(&self.config, self.config.client()?.get(link.expect("Link exists"))).await?;
             resp = handle_response(resp).await?;
             link = get_follow_path(resp.headers())?;
             if let Some(l) = link {
-                link = Some(self.config.url(l.as_str()))
+                link = Some(self.config.follow_url(l.as_str()))
             }
             let body = resp.json::<ApiTags>().await?;
             tags.append(&mut body.tags.unwrap_or(vec![]));
@@ -36,127 +58,662 @@ impl Repository {
         Ok(tags)
     }
 
+    /// Get the digest of a manifest by its tag using a `HEAD` request. This avoids downloading the manifest
+    /// when only its digest is needed
+    pub async fn get_digest(&self, tag: &str) -> Result<String, ApiError> {
+        let client = self.config.client()?;
+        let resp = send_with_retry(&self.config, client.head(self.url(format!("manifests/{tag}").as_str())).header(ACCEPT, accept_header(format!("{MANIFEST_CONTENT_TYPE},{INDEX_CONTENT_TYPE}").as_str())?)).await?;
+        let resp = handle_response(resp).await?;
+        read_digest(resp.headers())
+    }
+
     /// Get a manifest by its tag or digest <br>
     /// Depending whether the manifest is a multi-arch, docker or oci manifest a Manifest or
     /// ManifestList is returned in form of a ManifestResponse
     pub async fn get_manifest(&self, tag: &str) -> Result<ManifestResponse, ApiError> {
-        let client = get_request_client(format!("{MANIFEST_CONTENT_TYPE},{INDEX_CONTENT_TYPE}").as_str())?;
-        let mut resp = client
-            .get(self.config.url(format!("/v2/{}/manifests/{tag}", self.name).as_str()))
-            .send()
-            .await?;
+        let client = self.config.client()?;
+        let mut resp = send_with_retry(&self.config, client.get(self.url(format!("manifests/{tag}").as_str())).header(ACCEPT, accept_header(format!("{MANIFEST_CONTENT_TYPE},{INDEX_CONTENT_TYPE}").as_str())?)).await?;
         resp = handle_response(resp).await?;
 
-        let digest = resp
-            .headers()
-            .get("Docker-Content-Digest")
-            .ok_or(ApiError::MissingDigest)?
-            .to_str()
-            .map_err(|_| ApiError::InvalidHeaderValue(String::from("Docker-Content-Digest")))?
-            .to_string();
+        let digest = read_digest(resp.headers())?;
 
         let body = resp.json::<Value>().await?;
 
-        if let Some(media_type) = body.get("mediaType") {
-            if media_type == "application/vnd.docker.distribution.manifest.v2+json" {
-                // we have a single-arch manifest
-                let manifest = serde_json::from_value::<ApiManifest>(body)
-                    .map_err(|_| ApiError::InvalidBlobType)?;
-                Ok(ManifestResponse::Manifest(Manifest::new(
-                    manifest.schema_version,
-                    manifest.media_type,
-                    manifest.layers,
-                    Arc::new(self.clone()),
-                    manifest.config,
-                    digest,
-                )))
-            } else {
-                // we have a multi-arch manifest list (aka OCI index)
-                let index = serde_json::from_value::<ApiManifestList>(body)
-                    .map_err(|_| ApiError::InvalidBlobType)?;
-                Ok(ManifestResponse::ManifestList(ManifestList::new(
-                    index.schema_version,
-                    index.media_type,
-                    index.manifests,
-                    Arc::new(self.clone()),
-                    digest,
-                    self.config.clone(),
-                )))
-            }
+        // the media type is optional for oci manifests and indexes which are then detected by their fields
+        let media_type = body.get("mediaType").and_then(Value::as_str).map(str::to_string);
+        let is_manifest = match &media_type {
+            Some(media_type) => media_type == DOCKER_MANIFEST_MEDIA_TYPE || media_type == OCI_MANIFEST_MEDIA_TYPE,
+            None if body.get("manifests").is_some_and(Value::is_array) => false,
+            None if body.get("config").is_some() && body.get("layers").is_some_and(Value::is_array) => true,
+            None => return Err(ApiError::MissingMediaType)
+        };
+
+        if is_manifest {
+            // we have a single-arch manifest
+            let manifest = serde_json::from_value::<ApiManifest>(body)
+                .map_err(|_| ApiError::InvalidBlobType)?;
+            Ok(ManifestResponse::Manifest(Manifest::new(
+                manifest.schema_version,
+                media_type.unwrap_or(String::from(OCI_MANIFEST_MEDIA_TYPE)),
+                manifest.layers,
+                Arc::new(self.clone()),
+                manifest.config,
+                digest,
+            ).with_annotations(manifest.annotations)))
         } else {
-            Err(ApiError::MissingMediaType)
+            // we have a multi-arch manifest list (aka OCI index)
+            let index = serde_json::from_value::<ApiManifestList>(body)
+                .map_err(|_| ApiError::InvalidBlobType)?;
+            Ok(ManifestResponse::ManifestList(ManifestList::new(
+                index.schema_version,
+                media_type.unwrap_or(String::from(OCI_INDEX_MEDIA_TYPE)),
+                index.manifests,
+                Arc::new(self.clone()),
+                digest,
+                self.config.clone(),
+            ).with_annotations(index.annotations)))
         }
     }
 
     /// Pull a schemaless blob by it's digest from the registry
     pub async fn pull_blob(&self, digest: &str, content_type: &str) -> Result<Value, ApiError> {
-        let client = get_request_client(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE},{content_type}").as_str())?;
-        let mut resp = client
-            .get(self.config.url(format!("/v2/{}/blobs/{digest}", self.name).as_str()))
-            .send()
-            .await?;
+        let client = self.config.client()?;
+        let mut resp = send_with_retry(&self.config, client.get(self.url(format!("blobs/{digest}").as_str())).header(ACCEPT, accept_header(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE},{content_type}").as_str())?)).await?;
         resp = handle_response(resp).await?;
 
         let body = resp.json::<Value>().await?;
         Ok(body)
     }
 
+    /// Pull a blob by it's digest from the registry without parsing its content
+    pub async fn pull_raw_blob(&self, digest: &str, content_type: &str) -> Result<String, ApiError> {
+        let client = self.config.client()?;
+        let mut resp = send_with_retry(&self.config, client.get(self.url(format!("blobs/{digest}").as_str())).header(ACCEPT, accept_header(content_type)?)).await?;
+        resp = handle_response(resp).await?;
+
+        Ok(resp.text().await?)
+    }
+
+    /// Pull the content of an artifact by its tag or digest. The content of an artifact
+    /// is expected to be stored in the first layer of the artifact manifest
+    pub async fn pull_artifact(&self, reference: &str) -> Result<String, ApiError> {
+        match self.get_manifest(reference).await? {
+            ManifestResponse::Manifest(manifest) => {
+                let layer = manifest.layers.first().ok_or(ApiError::InvalidArtifact)?;
+                self.pull_raw_blob(&layer.digest, &layer.media_type).await
+            },
+            ManifestResponse::ManifestList(_) => Err(ApiError::InvalidArtifact)
+        }
+    }
+
     /// Delete a specific tag <br>
     /// **Important**: The tag delete endpoint is not implemented in all registries therefore it's safer to
     /// use the `delete_manifest(digest)` method with the digest of the tag manifest
     pub async fn delete_tag(&self, tag: &str) -> Result<(), ApiError> {
-        let client = get_request_client(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?;
-        let resp = client
-            .delete(self.config.url(format!("/v2/{}/manifests/{tag}", self.name).as_str()))
-            .send()
-            .await?;
-        handle_response(resp).await?;
+        let client = self.config.client()?;
+        let resp = send(&self.config, client.delete(self.url(format!("manifests/{tag}").as_str())).header(ACCEPT, accept_header(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?)).await?;
+        handle_delete_response(resp).await?;
         Ok(())
     }
 
     /// Delete a specific manifest by it's digest from the registry
     pub async fn delete_manifest(&self, digest: &str) -> Result<(), ApiError> {
-        let client = get_request_client(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?;
-        let resp = client
-            .delete(self.config.url(format!("/v2/{}/manifests/{digest}", self.name).as_str()))
-            .send()
-            .await?;
-        handle_response(resp).await?;
+        let client = self.config.client()?;
+        let resp = send(&self.config, client.delete(self.url(format!("manifests/{digest}").as_str())).header(ACCEPT, accept_header(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?)).await?;
+        handle_delete_response(resp).await?;
         Ok(())
     }
 
+    /// Check whether a manifest exists by its tag or digest without downloading it
+    pub async fn head_manifest(&self, reference: &str) -> Result<bool, ApiError> {
+        let client = self.config.client()?;
+        let resp = send(&self.config, client.head(self.url(format!("manifests/{reference}").as_str())).header(ACCEPT, accept_header(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?)).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(false)
+        }
+        handle_response(resp).await?;
+        Ok(true)
+    }
+
+    /// Verify a deleted manifest is no longer present in the registry. Since some registries process
+    /// deletions asynchronously the check is retried up to `attempts` times with an exponential backoff
+    /// starting at `backoff` <br>
+    /// Returns whether the manifest is gone
+    pub async fn verify_deleted(&self, digest: &str, attempts: u32, backoff: Duration) -> Result<bool, ApiError> {
+        let mut delay = backoff;
+        for attempt in 0..attempts {
+            if !self.head_manifest(digest).await? {
+                return Ok(true)
+            }
+            if attempt + 1 < attempts {
+                sleep(delay).await;
+                delay *= 2;
+            }
+        }
+        Ok(false)
+    }
+
     /// Delete a specific blob by it's digest from the registry
     pub async fn delete_blob(&self, digest: &str) -> Result<(), ApiError> {
-        let client = get_request_client(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?;
-        let resp = client
-            .delete(self.config.url(format!("/v2/{}/blobs/{digest}", self.name).as_str()))
-            .send()
-            .await?;
+        let client = self.config.client()?;
+        let resp = send(&self.config, client.delete(self.url(format!("blobs/{digest}").as_str())).header(ACCEPT, accept_header(format!("{INDEX_CONTENT_TYPE},{MANIFEST_CONTENT_TYPE}").as_str())?)).await?;
         handle_response(resp).await?;
         Ok(())
     }
 
+    /// Get the digests of all blobs (config and layers) referenced by a manifest or by all manifests of a manifest list
+    pub async fn get_blobs(&self, reference: &str) -> Result<HashSet<String>, ApiError> {
+        let manifests = match self.get_manifest(reference).await? {
+            ManifestResponse::Manifest(manifest) => vec![manifest],
+            ManifestResponse::ManifestList(list) => list.get_all_manifests().await?
+        };
+        Ok(manifests.into_iter()
+            .flat_map(|manifest| std::iter::once(manifest.manifest_config.digest).chain(manifest.layers.into_iter().map(|l| l.digest)))
+            .collect())
+    }
+
     /// Get the tags of the repository with some basic data about the tag useful
     /// for applying the deletion rules
     pub async fn get_tags_with_data(&self) -> Result<Vec<Tag>, ApiError> {
-        let mut tags = Vec::<Tag>::new();
         let raw = self.get_tags().await?;
-        for tag in raw {
-            match self.get_manifest(&tag).await? {
-                ManifestResponse::Manifest(manifest) => {
-                    let size: u64 = manifest.layers.iter().map(|l| l.size).sum();
-                    let config = manifest.get_config().await?;
-                    tags.push(Tag::new(tag, manifest.digest, config.created, size));
-                },
-                ManifestResponse::ManifestList(list) => {
-                    let size: u64 = list.manifests.iter().map(|m| m.size).sum();
-                    let layer = list.manifests.get(0).ok_or(ApiError::EmptyManifestList)?;
-                    let manifest = list.get_manifest(layer.digest.clone()).await?;
-                    let config = manifest.get_config().await?;
-                    tags.push(Tag::new(tag, manifest.digest, config.created, size));
-                }
+        // the data of the tags is fetched concurrently but in order. The first error aborts the remaining requests
+        stream::iter(raw)
+            .map(|tag| self.get_tag_data(tag))
+            .buffered(self.config.concurrency.max(1))
+            .try_collect()
+            .await
+    }
+
+    /// Get the data of a single tag from its manifest and config blob
+    async fn get_tag_data(&self, tag: String) -> Result<Tag, ApiError> {
+        match self.get_manifest(&tag).await? {
+            ManifestResponse::Manifest(manifest) => {
+                // the manifest only contains the compressed sizes of the layers. The config blob lists the digests of
+                // the uncompressed layers (`rootfs.diff_ids`) but not their sizes
+                let size: u64 = manifest.layers.iter().map(|l| l.size).sum();
+                let created = manifest.get_created(None).await?;
+                let layers = manifest.layers.into_iter().map(|l| l.digest).collect();
+                Ok(Tag::new(tag, manifest.digest, created, size).with_layers(layers).with_media_type(manifest.media_type))
+            },
+            ManifestResponse::ManifestList(list) => {
+                let layer = list.get_representative().ok_or(ApiError::EmptyManifestList)?;
+                let manifests = list.get_all_manifests().await?;
+                // the entries of the index only contain the sizes of the manifests themselves. Layers shared between
+                // the platforms are only counted once
+                let mut unique = HashSet::new();
+                let size: u64 = manifests.iter()
+                    .flat_map(|manifest| manifest.layers.iter())
+                    .filter(|layer| unique.insert(layer.digest.as_str()))
+                    .map(|layer| layer.size)
+                    .sum();
+                let manifest = manifests.into_iter().find(|manifest| manifest.digest == layer.digest).ok_or(ApiError::EmptyManifestList)?;
+                let created = manifest.get_created(Some(&list.annotations)).await?;
+                let layers = manifest.layers.into_iter().map(|l| l.digest).collect();
+                // placeholder platforms of attestation manifests aren't platforms of the image
+                let platforms = list.manifests.iter()
+                    .filter_map(|m| m.platform.as_ref())
+                    .filter(|platform| !platform.is_unknown())
+                    .map(|platform| platform.to_string())
+                    .collect();
+                // the tag references the manifest list and not the representative manifest
+                Ok(Tag::new(tag, list.digest, created, size).with_layers(layers).with_media_type(list.media_type).with_platforms(platforms))
             }
         }
-        Ok(tags)
+    }
+}
+
+/// Read and validate the `Docker-Content-Digest` header of a manifest response
+fn read_digest(headers: &HeaderMap) -> Result<String, ApiError> {
+    let digest = headers
+        .get("Docker-Content-Digest")
+        .ok_or(ApiError::MissingDigest)?
+        .to_str()
+        .map_err(|_| ApiError::InvalidHeaderValue(String::from("Docker-Content-Digest")))?
+        .to_string();
+    if parse_digest(&digest).is_none() {
+        return Err(ApiError::InvalidHeaderValue(String::from("Docker-Content-Digest")))
+    }
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::api::error::ApiError;
+    use crate::api::manifest::ManifestResponse;
+    use crate::api::repository::Repository;
+    use chrono::Utc;
+    use mockito::Matcher;
+    use crate::test::{get_mock_distribution_config, mock_delete, mock_manifest, mock_tag_list};
+
+    #[tokio::test]
+    async fn test_verify_deleted() {
+        let mut server = mockito::Server::new_async().await;
+        let delete = server.mock("DELETE", "/v2/app/manifests/sha256:abc").with_status(202).create_async().await;
+        let head = server.mock("HEAD", "/v2/app/manifests/sha256:abc").with_status(404).expect(1).create_async().await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        repository.delete_manifest("sha256:abc").await.unwrap();
+        assert!(repository.verify_deleted("sha256:abc", 3, Duration::from_millis(1)).await.unwrap());
+        delete.assert_async().await;
+        head.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_digest() {
+        let mut server = mockito::Server::new_async().await;
+        let head = server.mock("HEAD", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:abc")
+            .expect(1)
+            .create_async()
+            .await;
+        let get = server.mock("GET", "/v2/app/manifests/v1").expect(0).create_async().await;
+        let missing = server.mock("HEAD", "/v2/app/manifests/v2").with_status(404).create_async().await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        assert_eq!(repository.get_digest("v1").await.unwrap(), "sha256:abc");
+        assert!(repository.get_digest("v2").await.is_err());
+        head.assert_async().await;
+        get.assert_async().await;
+        missing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_accept_per_request() {
+        let mut server = mockito::Server::new_async().await;
+        let yaml = server.mock("GET", "/v2/app/blobs/sha256:yaml")
+            .match_header("accept", "application/yaml")
+            .with_body("yaml")
+            .create_async()
+            .await;
+        let json = server.mock("GET", "/v2/app/blobs/sha256:json")
+            .match_header("accept", "application/json")
+            .with_body("json")
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        // both requests use the same client with a different accept header
+        assert_eq!(repository.pull_raw_blob("sha256:yaml", "application/yaml").await.unwrap(), "yaml");
+        assert_eq!(repository.pull_raw_blob("sha256:json", "application/json").await.unwrap(), "json");
+        yaml.assert_async().await;
+        json.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_pull_artifact() {
+        let mut server = mockito::Server::new_async().await;
+        let manifest = server.mock("GET", "/v2/_abwart/config/manifests/latest")
+            .with_header("Docker-Content-Digest", "sha256:artifact")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "artifactType": "application/vnd.abwart.config.v1",
+                "config": { "mediaType": "application/vnd.oci.empty.v1+json", "digest": "sha256:empty", "size": 2 },
+                "layers": [{ "mediaType": "application/yaml", "digest": "sha256:content", "size": 20 }]
+            }"#)
+            .create_async()
+            .await;
+        let blob = server.mock("GET", "/v2/_abwart/config/blobs/sha256:content")
+            .with_body("default:\n  revisions: 5\n")
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("_abwart/config"), get_mock_distribution_config(&server));
+
+        assert_eq!(repository.pull_artifact("latest").await.unwrap(), "default:\n  revisions: 5\n");
+        manifest.assert_async().await;
+        blob.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_compressed_size() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        let _manifest = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("content-type", "application/vnd.docker.distribution.manifest.v2+json")
+            .with_header("Docker-Content-Digest", "sha256:abc")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": { "mediaType": "application/vnd.docker.container.image.v1+json", "digest": "sha256:config", "size": 1000 },
+                "layers": [
+                    { "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": "sha256:bottom", "size": 300 },
+                    { "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": "sha256:top", "size": 200 }
+                ]
+            }"#)
+            .create_async()
+            .await;
+        let _config = server.mock("GET", "/v2/app/blobs/sha256:config")
+            .with_body(r#"{"created":"2024-01-01T00:00:00Z","rootfs":{"type":"layers","diff_ids":["sha256:bottom-uncompressed","sha256:top-uncompressed"]}}"#)
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        // the size is the sum of the compressed layer sizes without the config blob
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].size, 500);
+    }
+
+    #[tokio::test]
+    async fn test_large_layers() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        let _manifest = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("content-type", "application/vnd.docker.distribution.manifest.v2+json")
+            .with_header("Docker-Content-Digest", "sha256:abc")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": { "mediaType": "application/vnd.docker.container.image.v1+json", "digest": "sha256:config", "size": 1000 },
+                "layers": [
+                    { "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": "sha256:bottom", "size": 5000000000 },
+                    { "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": "sha256:top", "size": 4294967295 }
+                ]
+            }"#)
+            .create_async()
+            .await;
+        let _config = server.mock("GET", "/v2/app/blobs/sha256:config")
+            .with_body(r#"{"created":"2024-01-01T00:00:00Z"}"#)
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        // layers bigger than 4 GiB neither fail to deserialize nor overflow the sum
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].size, 9_294_967_295);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tag_data() {
+        let mut server = mockito::Server::new_async().await;
+        let names = (0..20).map(|index| format!("v{index}")).collect::<Vec<_>>();
+        let _tags = mock_tag_list(&mut server, "app", names.iter().map(String::as_str).collect()).await;
+        let mut mocks = vec![];
+        for (index, name) in names.iter().enumerate() {
+            mocks.push(mock_manifest(&mut server, "app", name, &format!("sha256:{index}"), Utc::now(), index as u64).await);
+        }
+        let mut config = (*get_mock_distribution_config(&server)).clone();
+        config.concurrency = 4;
+        let repository = Repository::new(String::from("app"), Arc::new(config));
+
+        // the tags are returned in the order of the tag list
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>(), names);
+        assert_eq!(tags.iter().map(|tag| tag.size).collect::<Vec<_>>(), (0..20).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_tag_data_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2", "v3"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:1", Utc::now(), 1).await;
+        let _v2 = server.mock("GET", "/v2/app/manifests/v2").with_status(404).create_async().await;
+        let _v3 = mock_manifest(&mut server, "app", "v3", "sha256:3", Utc::now(), 1).await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        assert!(repository.get_tags_with_data().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encoded_repository_name() {
+        let mut server = mockito::Server::new_async().await;
+        let tags = server.mock("GET", "/v2/team/app%20service/tags/list?n=100")
+            .with_body(r#"{"name":"team/app service","tags":["v1"]}"#)
+            .create_async()
+            .await;
+        let delete = mock_delete(&mut server, "team/app%20service", "sha256:abc", 202, "", 1).await;
+        let repository = Repository::new(String::from("team/app service"), get_mock_distribution_config(&server));
+
+        assert_eq!(repository.get_tags().await.unwrap(), vec!["v1"]);
+        assert!(repository.delete_manifest("sha256:abc").await.is_ok());
+        tags.assert_async().await;
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_sha512_digest() {
+        let mut server = mockito::Server::new_async().await;
+        let digest = format!("sha512:{}", "ab".repeat(64));
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        let _manifest = mock_manifest(&mut server, "app", "v1", &digest, Utc::now(), 10).await;
+        let delete = mock_delete(&mut server, "app", &digest, 202, "", 1).await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].digest, digest);
+        assert!(repository.delete_manifest(&tags[0].digest).await.is_ok());
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_digest_header() {
+        let mut server = mockito::Server::new_async().await;
+        let _manifest = mock_manifest(&mut server, "app", "v1", "not-a-digest", Utc::now(), 10).await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        assert!(matches!(repository.get_manifest("v1").await, Err(ApiError::InvalidHeaderValue(_))));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_list_digest() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        let _index = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha512:index")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [
+                    { "mediaType": "application/vnd.docker.distribution.manifest.v2+json", "digest": "sha512:amd", "size": 10, "platform": { "architecture": "amd64", "os": "linux" } },
+                    { "mediaType": "application/vnd.docker.distribution.manifest.v2+json", "digest": "sha512:arm", "size": 10, "platform": { "architecture": "arm64", "os": "linux", "variant": "v8" } },
+                    { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha512:attestation", "size": 1, "platform": { "architecture": "unknown", "os": "unknown" } }
+                ]
+            }"#)
+            .create_async()
+            .await;
+        let _amd = mock_manifest(&mut server, "app", "sha512:amd", "sha512:amd", Utc::now(), 10).await;
+        let _arm = mock_manifest(&mut server, "app", "sha512:arm", "sha512:arm", Utc::now(), 10).await;
+        let _attestation = mock_manifest(&mut server, "app", "sha512:attestation", "sha512:attestation", Utc::now(), 1).await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].digest, "sha512:index");
+        assert_eq!(tags[0].layers, vec!["sha512:amd-layer"]);
+        assert_eq!(tags[0].platforms, vec!["linux/amd64", "linux/arm64/v8"]);
+    }
+
+    /// Mock an image manifest with the given layers (digest and size) whose config blob contains the creation time
+    async fn mock_platform_manifest(server: &mut mockito::ServerGuard, digest: &str, layers: Vec<(&str, u64)>, created: &str) -> (mockito::Mock, mockito::Mock) {
+        let layers = layers.into_iter()
+            .map(|(digest, size)| serde_json::json!({ "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip", "digest": digest, "size": size }))
+            .collect::<Vec<_>>();
+        let manifest = server.mock("GET", format!("/v2/app/manifests/{digest}").as_str())
+            .with_header("Docker-Content-Digest", digest)
+            .with_body(serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "mediaType": "application/vnd.oci.image.config.v1+json", "digest": format!("{digest}-config"), "size": 1 },
+                "layers": layers
+            }).to_string())
+            .create_async()
+            .await;
+        let config = server.mock("GET", format!("/v2/app/blobs/{digest}-config").as_str())
+            .with_body(serde_json::json!({ "created": created }).to_string())
+            .create_async()
+            .await;
+        (manifest, config)
+    }
+
+    #[tokio::test]
+    async fn test_multi_arch_tag() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2"]).await;
+        let manifests = r#"[
+            { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:arm", "size": 400, "platform": { "architecture": "arm64", "os": "linux" } },
+            { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:amd", "size": 400, "platform": { "architecture": "amd64", "os": "linux" } }
+        ]"#;
+        let _v1 = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:v1")
+            .with_body(format!(r#"{{ "schemaVersion": 2, "mediaType": "application/vnd.oci.image.index.v1+json", "manifests": {manifests} }}"#))
+            .create_async()
+            .await;
+        let _v2 = server.mock("GET", "/v2/app/manifests/v2")
+            .with_header("Docker-Content-Digest", "sha256:v2")
+            .with_body(format!(r#"{{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": {manifests},
+                "annotations": {{ "org.opencontainers.image.created": "2024-03-01T00:00:00Z" }}
+            }}"#))
+            .create_async()
+            .await;
+        let _arm = mock_platform_manifest(&mut server, "sha256:arm", vec![("sha256:base", 100), ("sha256:arm-layer", 30)], "2024-02-01T00:00:00Z").await;
+        let _amd = mock_platform_manifest(&mut server, "sha256:amd", vec![("sha256:base", 100), ("sha256:amd-layer", 20)], "2024-01-01T00:00:00Z").await;
+        let mut config = (*get_mock_distribution_config(&server)).clone();
+        config.platforms = vec![String::from("linux/amd64"), String::from("*")];
+        let repository = Repository::new(String::from("app"), Arc::new(config));
+
+        let tags = repository.get_tags_with_data().await.unwrap();
+        // the size contains the layers of all platforms with the shared base layer counted once
+        assert_eq!(tags[0].size, 150);
+        // the creation time is read from the platform selected by the platform chain
+        assert_eq!(tags[0].created.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(tags[0].layers, vec!["sha256:base", "sha256:amd-layer"]);
+        // the annotation of the index takes precedence over the platforms
+        assert_eq!(tags[1].size, 150);
+        assert_eq!(tags[1].created.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn test_index_without_media_type() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        let _index = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:index")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "manifests": [
+                    { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:amd", "size": 10, "platform": { "architecture": "amd64", "os": "linux" } }
+                ]
+            }"#)
+            .create_async()
+            .await;
+        let _manifest = server.mock("GET", "/v2/app/manifests/sha256:amd")
+            .with_header("Docker-Content-Digest", "sha256:amd")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "config": { "mediaType": "application/vnd.oci.image.config.v1+json", "digest": "sha256:config", "size": 100 },
+                "layers": [{ "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip", "digest": "sha256:layer", "size": 10 }]
+            }"#)
+            .create_async()
+            .await;
+        let _config = server.mock("GET", "/v2/app/blobs/sha256:config")
+            .with_body(r#"{"created":"2024-01-01T00:00:00Z"}"#)
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].digest, "sha256:index");
+        assert_eq!(tags[0].media_type, "application/vnd.oci.image.index.v1+json");
+        assert!(tags[0].is_index());
+        assert_eq!(tags[0].layers, vec!["sha256:layer"]);
+        assert_eq!(tags[0].platforms, vec!["linux/amd64"]);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_without_media_type() {
+        let mut server = mockito::Server::new_async().await;
+        let _manifest = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:abc")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "config": { "mediaType": "application/vnd.oci.image.config.v1+json", "digest": "sha256:config", "size": 100 },
+                "layers": [{ "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip", "digest": "sha256:layer", "size": 10 }]
+            }"#)
+            .create_async()
+            .await;
+        let _unknown = server.mock("GET", "/v2/app/manifests/v2")
+            .with_header("Docker-Content-Digest", "sha256:def")
+            .with_body(r#"{"schemaVersion": 2}"#)
+            .create_async()
+            .await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        match repository.get_manifest("v1").await.unwrap() {
+            ManifestResponse::Manifest(manifest) => assert_eq!(manifest.media_type, "application/vnd.oci.image.manifest.v1+json"),
+            ManifestResponse::ManifestList(_) => panic!("Expected single manifest")
+        }
+        assert!(matches!(repository.get_manifest("v2").await, Err(ApiError::MissingMediaType)));
+    }
+
+    #[tokio::test]
+    async fn test_annotated_created() {
+        let mut server = mockito::Server::new_async().await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2"]).await;
+        let _manifest = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:abc")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "mediaType": "application/vnd.oci.empty.v1+json", "digest": "sha256:empty", "size": 2 },
+                "layers": [{ "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip", "digest": "sha256:layer", "size": 10 }],
+                "annotations": { "org.opencontainers.image.created": "2024-01-01T00:00:00Z" }
+            }"#)
+            .create_async()
+            .await;
+        let _index = server.mock("GET", "/v2/app/manifests/v2")
+            .with_header("Docker-Content-Digest", "sha256:index")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [{ "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:amd", "size": 10, "platform": { "architecture": "amd64", "os": "linux" } }],
+                "annotations": { "org.opencontainers.image.created": "2024-02-01T00:00:00+01:00" }
+            }"#)
+            .create_async()
+            .await;
+        let _amd = server.mock("GET", "/v2/app/manifests/sha256:amd")
+            .with_header("Docker-Content-Digest", "sha256:amd")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "mediaType": "application/vnd.oci.image.config.v1+json", "digest": "sha256:config", "size": 2 },
+                "layers": [{ "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip", "digest": "sha256:layer", "size": 10 }]
+            }"#)
+            .create_async()
+            .await;
+        // the config blobs aren't pulled when the creation time is annotated
+        let config = server.mock("GET", Matcher::Regex(String::from("^/v2/app/blobs/"))).expect(0).create_async().await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        let tags = repository.get_tags_with_data().await.unwrap();
+        assert_eq!(tags[0].created.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(tags[1].created.to_rfc3339(), "2024-01-31T23:00:00+00:00");
+        config.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_immutable_delete() {
+        let mut server = mockito::Server::new_async().await;
+        let body = r#"{"errors":[{"code":"PRECONDITION","message":"Failed to delete tag v1 because the tag is immutable"}]}"#;
+        let delete = mock_delete(&mut server, "app", "sha256:abc", 412, body, 1).await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        assert!(matches!(repository.delete_manifest("sha256:abc").await, Err(ApiError::ImmutableManifest(_))));
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_verify_lingering_delete() {
+        let mut server = mockito::Server::new_async().await;
+        let delete = server.mock("DELETE", "/v2/app/manifests/sha256:abc").with_status(202).create_async().await;
+        let head = server.mock("HEAD", "/v2/app/manifests/sha256:abc").with_status(200).expect(3).create_async().await;
+        let repository = Repository::new(String::from("app"), get_mock_distribution_config(&server));
+
+        repository.delete_manifest("sha256:abc").await.unwrap();
+        assert!(!repository.verify_deleted("sha256:abc", 3, Duration::from_millis(1)).await.unwrap());
+        delete.assert_async().await;
+        head.assert_async().await;
     }
 }
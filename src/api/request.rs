@@ -1,20 +1,110 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
+use crate::api::auth::send;
+use crate::api::DistributionConfig;
 use crate::api::error::ApiError;
-use reqwest::header::HeaderMap;
-use reqwest::Response;
+use log::warn;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use tokio::time::sleep;
 
-/// For an reqwest response check the registry version as well as map errors to `ApiError`s
+/// Statuses of transient registry errors after which a request is retried
+const RETRYABLE_STATUSES: [StatusCode; 4] = [StatusCode::TOO_MANY_REQUESTS, StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE, StatusCode::GATEWAY_TIMEOUT];
+
+/// How requests which failed due to transient registry errors are retried
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RetryConfig {
+    /// How many times a failed request is retried
+    pub retries: u32,
+    /// Backoff before the first retry which is doubled after every failed attempt
+    pub backoff: Duration,
+    /// Upper bound of a single backoff. Also caps the delay requested by a `Retry-After` header
+    pub max_backoff: Duration
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self { retries: 3, backoff: Duration::from_millis(500), max_backoff: Duration::from_secs(30) }
+    }
+}
+
+impl RetryConfig {
+    /// Backoff before the given retry (starting at `0`) with up to 50% jitter to spread out the retries
+    /// of concurrent runs
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or_default();
+        let jitter = backoff.mul_f64(f64::from(nanos % 1000) / 2000.0);
+        backoff + jitter
+    }
+}
+
+/// Send an idempotent request (e.g. listing the catalog or getting a manifest) and retry it with an exponential backoff
+/// when the registry responds with a transient error (`429`, `502`, `503`, `504`) or can't be reached. A `Retry-After`
+/// header of the response is honored. All other responses are returned immediately
+pub async fn send_with_retry(config: &DistributionConfig, request: RequestBuilder) -> Result<Response, ApiError> {
+    let retry_config = config.retry;
+    let mut retry = 0;
+    loop {
+        let Some(attempt) = request.try_clone() else {
+            return send(config, request).await
+        };
+        let (delay, reason) = match send(config, attempt).await {
+            Ok(response) if retry < retry_config.retries && RETRYABLE_STATUSES.contains(&response.status()) => {
+                let delay = get_retry_after(response.headers()).map(|delay| delay.min(retry_config.max_backoff));
+                (delay.unwrap_or_else(|| retry_config.backoff(retry)), response.status().to_string())
+            },
+            Err(ApiError::RequestError(err)) if retry < retry_config.retries && err.is_connect() => {
+                (retry_config.backoff(retry), err.to_string())
+            },
+            Err(ApiError::Timeout(err)) if retry < retry_config.retries => (retry_config.backoff(retry), err),
+            other => return other
+        };
+        retry += 1;
+        warn!("Request to registry '{}' failed ({reason}). Retrying in {delay:?} (retry {retry}/{})", config.host, retry_config.retries);
+        sleep(delay).await
+    }
+}
+
+/// Read the `Retry-After` header which is either a delay in seconds or a http date
+pub fn get_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds))
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// For an reqwest response check the registry version as well as map errors to `ApiError`s. Missing resources
+/// (`404`) and missing authorization (`401`) are mapped to their own errors
 pub async fn handle_response(response: Response) -> Result<Response, ApiError> {
     validate_registry_version(&response)?;
 
     let status = response.status();
-    if !status.is_success() {
-        let body = response.text().await?;
-        Err(ApiError::RegistryError(body.trim().to_string()))
+    if status == StatusCode::UNAUTHORIZED {
+        Err(ApiError::Unauthorized)
+    } else if !status.is_success() {
+        let body = response.text().await?.trim().to_string();
+        if status == StatusCode::NOT_FOUND {
+            Err(ApiError::NotFound(body))
+        } else {
+            Err(ApiError::RegistryError(body))
+        }
     } else {
         Ok(response)
     }
 }
 
+/// Like [`handle_response`] but maps registry errors caused by tag immutability to [`ApiError::ImmutableManifest`]. <br>
+/// Registries with immutable tags (e.g. Harbor) don't share an error code for this case therefore the error message is checked
+pub async fn handle_delete_response(response: Response) -> Result<Response, ApiError> {
+    match handle_response(response).await {
+        Err(ApiError::RegistryError(body)) if body.to_lowercase().contains("immutable") => Err(ApiError::ImmutableManifest(body)),
+        other => other
+    }
+}
+
 /// Validate the `Docker-Distribution-API-Version` header was present in the response and that it's value
 /// is set to use registry v2
 pub fn validate_registry_version(response: &Response) -> Result<(), ApiError> {
@@ -55,3 +145,97 @@ pub fn get_follow_path(headers: &HeaderMap) -> Result<Option<String>, ApiError>
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use crate::api::distribution::Distribution;
+    use crate::api::error::ApiError;
+    use crate::api::request::{get_retry_after, RetryConfig};
+    use crate::api::repository::Repository;
+    use crate::api::DistributionConfig;
+
+    fn get_config(server: &mockito::ServerGuard) -> Arc<DistributionConfig> {
+        let mut config = DistributionConfig::new(server.host_with_port(), None, None, true);
+        config.retry = RetryConfig { retries: 2, backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(10) };
+        Arc::new(config)
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(get_retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(get_retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(get_retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = RetryConfig { retries: 5, backoff: Duration::from_millis(100), max_backoff: Duration::from_millis(300) };
+        assert!(config.backoff(0) >= Duration::from_millis(100) && config.backoff(0) < Duration::from_millis(150));
+        assert!(config.backoff(1) >= Duration::from_millis(200) && config.backoff(1) < Duration::from_millis(300));
+        assert!(config.backoff(4) >= Duration::from_millis(300) && config.backoff(4) < Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server.mock("GET", "/v2/_catalog").match_query(mockito::Matcher::Any).with_status(503).expect(1).create_async().await;
+        let limited = server.mock("GET", "/v2/_catalog").match_query(mockito::Matcher::Any).with_status(429).with_header("Retry-After", "0").expect(1).create_async().await;
+        let catalog = server.mock("GET", "/v2/_catalog").match_query(mockito::Matcher::Any).with_body(r#"{"repositories":["app"]}"#).expect(1).create_async().await;
+
+        let repositories = Distribution::new(get_config(&server)).get_repositories(None).await.unwrap();
+        assert_eq!(repositories.len(), 1);
+        unavailable.assert_async().await;
+        limited.assert_async().await;
+        catalog.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        let body = r#"{"errors":[{"code":"MANIFEST_UNKNOWN","message":"manifest unknown"}]}"#;
+        let _missing = server.mock("GET", "/v2/app/tags/list").match_query(mockito::Matcher::Any).with_status(404).with_body(body).create_async().await;
+        let _unauthorized = server.mock("GET", "/v2/api/tags/list").match_query(mockito::Matcher::Any).with_status(401).create_async().await;
+        let _failed = server.mock("GET", "/v2/web/tags/list").match_query(mockito::Matcher::Any).with_status(500).create_async().await;
+
+        assert!(matches!(Repository::new(String::from("app"), get_config(&server)).get_tags().await, Err(ApiError::NotFound(err)) if err == body));
+        assert!(matches!(Repository::new(String::from("api"), get_config(&server)).get_tags().await, Err(ApiError::Unauthorized)));
+        assert!(matches!(Repository::new(String::from("web"), get_config(&server)).get_tags().await, Err(ApiError::RegistryError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server.mock("GET", "/v2/app/tags/list").match_query(mockito::Matcher::Any).with_status(503).expect(3).create_async().await;
+
+        let result = Repository::new(String::from("app"), get_config(&server)).get_tags().await;
+        assert!(matches!(result, Err(ApiError::RegistryError(_))));
+        unavailable.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // the listener accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = DistributionConfig::new(listener.local_addr().unwrap().to_string(), None, None, true);
+        config.retry.retries = 0;
+        config.timeout = Duration::from_millis(100);
+
+        let result = Repository::new(String::from("app"), Arc::new(config)).get_tags().await;
+        assert!(matches!(result, Err(ApiError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast() {
+        let mut server = mockito::Server::new_async().await;
+        let missing = server.mock("GET", "/v2/app/tags/list").match_query(mockito::Matcher::Any).with_status(404).expect(1).create_async().await;
+
+        assert!(Repository::new(String::from("app"), get_config(&server)).get_tags().await.is_err());
+        missing.assert_async().await;
+    }
+}
@@ -1,15 +1,51 @@
 use chrono::{DateTime, Utc};
+use crate::api::INDEX_CONTENT_TYPE;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Tag {
     pub name: String,
     pub digest: String,
     pub created: DateTime<Utc>,
-    pub size: u64
+    pub size: u64,
+    /// Digests of the layers of the tag ordered from the bottom-most to the top-most layer. For
+    /// multi-arch images the layers of the representative manifest are used
+    pub layers: Vec<String>,
+    /// Media type of the manifest referenced by the tag
+    pub media_type: String,
+    /// Platforms (`<os>/<architecture>[/<variant>]`) of the manifests of a multi-arch image. Empty for
+    /// single-arch images
+    pub platforms: Vec<String>,
+    /// Time at which the tag was last pulled. `None` when no pull data is available for the tag
+    pub last_pulled: Option<DateTime<Utc>>
 }
 
 impl Tag {
     pub fn new(name: String, digest: String, created: DateTime<Utc>, size: u64) -> Self {
-        Self { name, digest, created, size }
+        Self { name, digest, created, size, layers: vec![], media_type: String::new(), platforms: vec![], last_pulled: None }
     }
-}
\ No newline at end of file
+
+    pub fn with_media_type(mut self, media_type: String) -> Self {
+        self.media_type = media_type;
+        self
+    }
+
+    /// Whether the tag references a manifest list (aka OCI index) of a multi-arch image
+    pub fn is_index(&self) -> bool {
+        INDEX_CONTENT_TYPE.split(',').any(|media_type| media_type == self.media_type)
+    }
+
+    pub fn with_layers(mut self, layers: Vec<String>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_platforms(mut self, platforms: Vec<String>) -> Self {
+        self.platforms = platforms;
+        self
+    }
+
+    pub fn with_last_pulled(mut self, last_pulled: Option<DateTime<Utc>>) -> Self {
+        self.last_pulled = last_pulled;
+        self
+    }
+}
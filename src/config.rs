@@ -1,11 +1,20 @@
 use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
-use log::{error, info, warn};
+use cron::Schedule;
+use log::{debug, error, info, warn};
 use notify::{RecommendedWatcher, RecursiveMode};
-use notify_debouncer_mini::{DebouncedEventKind, Debouncer, new_debouncer_opt};
+use notify_debouncer_mini::{DebouncedEvent, DebouncedEventKind, Debouncer, new_debouncer_opt};
+use globset::Glob;
+use regex::Regex;
 use serde::Deserialize;
+use serde_json::Value;
+use crate::error::Error;
+use crate::policies::group::GROUP_LABEL;
+use crate::policies::not::NOT_SUFFIX;
+use crate::policies::tag_allowed::TAG_ALLOWED_LABEL;
 use crate::NAME;
 
 #[derive(Deserialize, Clone, Debug, Default, Eq, PartialEq)]
@@ -13,16 +22,94 @@ pub struct Config {
     registries: HashMap<String, InstanceConfig>
 }
 
+/// Format of the static configuration file which is detected by its file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json
+}
+
+impl ConfigFormat {
+    /// Detect the format by the extension of the path. Paths with an unknown extension are expected to be yaml
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml
+        }
+    }
+}
+
 impl Config {
-    pub fn parse() -> serde_yaml::Result<Self> {
-        let path = Path::new("config.yml");
-        if let Ok(content) = read_to_string(path) {
-            serde_yaml::from_str(&content)
+    pub fn parse() -> Result<Self, Error> {
+        let path = Config::path();
+        if let Ok(content) = read_to_string(&path) {
+            Config::from_str(&content, ConfigFormat::from_path(&path))
         } else {
             Ok(Self { registries: HashMap::new() })
         }
     }
 
+    /// Parse the content of a static configuration file in the given format
+    pub fn from_str(content: &str, format: ConfigFormat) -> Result<Self, Error> {
+        let mut config = Config::parse_format(content, format)?;
+        config.expand_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn parse_format(content: &str, format: ConfigFormat) -> Result<Self, Error> {
+        match format {
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(|err| Error::InvalidConfig(format!("Invalid yaml: {err}"))),
+            ConfigFormat::Toml => {
+                let value = toml::from_str::<Value>(content).map_err(|err| Error::InvalidConfig(format!("Invalid toml: {err}")))?;
+                serde_json::from_value(stringify_scalars(value)).map_err(|err| Error::InvalidConfig(format!("Invalid toml: {err}")))
+            },
+            ConfigFormat::Json => {
+                let value = serde_json::from_str::<Value>(content).map_err(|err| Error::InvalidConfig(format!("Invalid json: {err}")))?;
+                serde_json::from_value(stringify_scalars(value)).map_err(|err| Error::InvalidConfig(format!("Invalid json: {err}")))
+            }
+        }
+    }
+
+    /// Replace `${VAR}` references in all values with the value of the environment variable
+    fn expand_env(&mut self) -> Result<(), Error> {
+        for (registry, config) in self.registries.iter_mut() {
+            let values = config.network.iter_mut()
+                .chain(config.username.iter_mut())
+                .chain(config.password.iter_mut())
+                .chain(config.username_file.iter_mut())
+                .chain(config.password_file.iter_mut())
+                .chain(config.webhook.iter_mut())
+                .chain(config.host.iter_mut())
+                .chain(config.default.iter_mut().flat_map(|default| default.values_mut()))
+                .chain(config.rules.iter_mut().flat_map(|rules| rules.values_mut()).flat_map(|rule| rule.values_mut()));
+            for value in values {
+                *value = expand_env(value).map_err(|err| Error::InvalidConfig(format!("{err} in registry '{registry}'")))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the schedules and patterns of all rules. Invalid values would otherwise be ignored and the rule would never
+    /// run or never match anything
+    fn validate(&self) -> Result<(), Error> {
+        let mut registries = self.registries.iter().collect::<Vec<_>>();
+        registries.sort_by_key(|(name, _)| *name);
+        for (registry, config) in registries {
+            if let Some(default) = &config.default {
+                validate_policies(default, &format!("the default rule in registry '{registry}'"))?;
+            }
+            let mut rules = config.rules.iter().flatten().collect::<Vec<_>>();
+            rules.sort_by_key(|(name, _)| *name);
+            for (rule, policies) in rules {
+                validate_policies(policies, &format!("rule '{rule}' in registry '{registry}'"))?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn path() -> String {
         std::env::var("CONFIG_PATH").unwrap_or(String::from("config.yml"))
     }
@@ -34,23 +121,19 @@ impl Config {
     pub fn get_registries(&self) -> HashMap<String, HashMap<String, String>> {
         let mut registries = HashMap::new();
         self.registries.iter().for_each(|(name, config)| {
-            let mut labels = HashMap::new();
-            if let Some(network) = &config.network {
-                labels.insert(String::from("network"), network.clone());
-            }
-            if let Some(default) = &config.default {
-                default.iter().for_each(|(key, value)| { labels.insert(format!("{NAME}.default.{key}"), value.clone()); });
-            }
-            if let Some(rules) = &config.rules {
-                rules.iter().for_each(|(rule, value)| {
-                    value.iter().for_each(|(key, value)| { labels.insert(format!("{NAME}.rule.{rule}.{key}"), value.clone()); });
-                });
-            }
-            registries.insert(name.clone(), labels);
+            registries.insert(name.clone(), config.get_labels());
         });
         registries
     }
 
+    /// Get the registries which aren't running in a container and are reached using their `host` instead
+    pub fn get_standalone_registries(&self) -> HashMap<String, HashMap<String, String>> {
+        self.registries.iter()
+            .filter(|(_, config)| config.host.is_some())
+            .map(|(name, config)| (name.clone(), config.get_labels()))
+            .collect()
+    }
+
     pub fn get_registry(&self, name: &str) -> Option<HashMap<String, String>> {
         self.get_registries().get(name).cloned()
     }
@@ -62,6 +145,164 @@ pub struct InstanceConfig {
     #[serde(rename = "rule")]
     rules: Option<HashMap<String, HashMap<String, String>>>,
     network: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    username_file: Option<String>,
+    password_file: Option<String>,
+    webhook: Option<String>,
+    /// Host (and port) of a registry which isn't running in a container
+    host: Option<String>,
+    insecure: Option<String>,
+}
+
+impl InstanceConfig {
+    /// Get the configuration in the same format as if it was defined using labels
+    pub fn get_labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        if let Some(network) = &self.network {
+            labels.insert(format!("{NAME}.network"), network.clone());
+        }
+        if let Some(username) = &self.username {
+            labels.insert(format!("{NAME}.username"), username.clone());
+        }
+        if let Some(password) = &self.password {
+            labels.insert(format!("{NAME}.password"), password.clone());
+        }
+        if let Some(username_file) = &self.username_file {
+            labels.insert(format!("{NAME}.username_file"), username_file.clone());
+        }
+        if let Some(password_file) = &self.password_file {
+            labels.insert(format!("{NAME}.password_file"), password_file.clone());
+        }
+        if let Some(webhook) = &self.webhook {
+            labels.insert(format!("{NAME}.webhook"), webhook.clone());
+        }
+        if let Some(host) = &self.host {
+            labels.insert(format!("{NAME}.host"), host.clone());
+        }
+        if let Some(insecure) = &self.insecure {
+            labels.insert(format!("{NAME}.insecure"), insecure.clone());
+        }
+        if let Some(default) = &self.default {
+            default.iter().for_each(|(key, value)| { labels.insert(format!("{NAME}.default.{key}"), value.clone()); });
+        }
+        if let Some(rules) = &self.rules {
+            rules.iter().for_each(|(rule, value)| {
+                value.iter().for_each(|(key, value)| { labels.insert(format!("{NAME}.rule.{rule}.{key}"), value.clone()); });
+            });
+        }
+        labels
+    }
+}
+
+/// Expand `${VAR}` references with the value of the environment variable `VAR`. A literal dollar sign is written as `$$`.
+/// A `$` which isn't followed by `{` or `$` is kept as it is
+pub fn expand_env(value: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        if let Some(remaining) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = remaining;
+        } else if let Some(remaining) = rest.strip_prefix('{') {
+            let end = remaining.find('}').ok_or(format!("Unterminated environment variable reference '${{{remaining}'"))?;
+            let name = &remaining[..end];
+            let variable = std::env::var(name).map_err(|_| format!("Referenced environment variable '{name}' isn't set"))?;
+            expanded.push_str(&variable);
+            rest = &remaining[end + 1..];
+        } else {
+            expanded.push('$');
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn validate_schedule(schedule: &str) -> Result<(), cron::error::Error> {
+    Schedule::from_str(schedule).map(|_| ())
+}
+
+/// Get the regular expression of a regex-valued policy. The value of `keep.group` is the regex and the amount of tags
+/// to keep separated by the last `;`
+fn policy_pattern<'a>(policy: &str, value: &'a str) -> Option<&'a str> {
+    match policy.strip_suffix(NOT_SUFFIX).unwrap_or(policy) {
+        GROUP_LABEL => Some(value.rsplit_once(';').map_or(value, |(pattern, _)| pattern)),
+        TAG_ALLOWED_LABEL => Some(value),
+        policy if policy.ends_with(".pattern") => Some(value),
+        _ => None
+    }
+}
+
+/// Validate the schedule, the regular expressions of all regex-valued policies (`*.pattern`, `tag.allowed` and `keep.group`)
+/// and the globs of all `*.glob` policies (including negated ones) of a rule.
+/// The `rule` describes the rule in the error
+fn validate_policies(policies: &HashMap<String, String>, rule: &str) -> Result<(), Error> {
+    if let Some(schedule) = policies.get("schedule") {
+        validate_schedule(schedule).map_err(|err| Error::InvalidConfig(format!("Invalid schedule '{schedule}' of {rule}: {err}")))?;
+    }
+    let mut patterns = policies.iter()
+        .filter_map(|(policy, value)| policy_pattern(policy, value).map(|pattern| (policy, pattern)))
+        .collect::<Vec<_>>();
+    patterns.sort();
+    for (policy, pattern) in patterns {
+        Regex::new(pattern).map_err(|err| Error::InvalidConfig(format!("Invalid pattern '{pattern}' of policy '{policy}' in {rule}: {err}")))?;
+    }
+    let mut globs = policies.iter()
+        .filter(|(policy, _)| policy.strip_suffix(NOT_SUFFIX).unwrap_or(policy).ends_with(".glob"))
+        .collect::<Vec<_>>();
+    globs.sort();
+    for (policy, glob) in globs {
+        Glob::new(glob).map_err(|err| Error::InvalidConfig(format!("Invalid glob '{glob}' of policy '{policy}' in {rule}: {err}")))?;
+    }
+    Ok(())
+}
+
+/// Convert numbers and booleans to strings since all configuration values are strings like labels. Unlike yaml
+/// toml and json don't allow unquoted scalars to be read as strings (e.g. `revisions = 5`)
+fn stringify_scalars(value: Value) -> Value {
+    match value {
+        Value::Number(number) => Value::String(number.to_string()),
+        Value::Bool(bool) => Value::String(bool.to_string()),
+        Value::Array(values) => Value::Array(values.into_iter().map(stringify_scalars).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(key, value)| (key, stringify_scalars(value))).collect()),
+        value => value
+    }
+}
+
+/// Coalesces the events of the static configuration file watcher into config updates. A single save often
+/// results in multiple events which would otherwise reload the same configuration multiple times
+struct ConfigReloader {
+    last: Option<Config>
+}
+
+impl ConfigReloader {
+    fn new(current: Option<Config>) -> Self {
+        Self { last: current }
+    }
+
+    /// Handle a batch of debounced events. The configuration is parsed at most once per batch and only returned
+    /// when it differs from the last returned configuration
+    fn reload(&mut self, events: &[DebouncedEvent], parse: impl FnOnce() -> Result<Config, Error>) -> Option<Config> {
+        if !events.iter().any(|event| event.kind == DebouncedEventKind::Any) {
+            return None
+        }
+        match parse() {
+            Ok(config) if self.last.as_ref() == Some(&config) => {
+                debug!("Static configuration file changed without changing the configuration. Skipping update");
+                None
+            },
+            Ok(config) => {
+                self.last = Some(config.clone());
+                Some(config)
+            },
+            Err(err) => {
+                error!("Error whilst parsing updated config. Reason: {err}");
+                None
+            }
+        }
+    }
 }
 
 /// Watch the static configuration file at [`Config::path()`]. Any successful changes to the config file
@@ -85,18 +326,14 @@ pub fn watch_config(sender: tokio::sync::mpsc::Sender<Config>) -> Result<(), not
         // this is necessary to move the watcher and therefore prevent tx from leaving the scope
         // and closing the channel
         let _file_watcher = debouncer;
+        let mut reloader = ConfigReloader::new(Config::parse().ok());
         for res in &rx {
             match res {
                 Ok(events) => {
-                    if events.iter().any(|event| event.kind == DebouncedEventKind::Any) {
-                        match Config::parse() {
-                            Ok(config) => {
-                                futures::executor::block_on(async {
-                                    sender.send(config).await.expect("Channel should be open");
-                                })
-                            },
-                            Err(err) => error!("Error whilst parsing updated config. Reason: {err}")
-                        }
+                    if let Some(config) = reloader.reload(&events, Config::parse) {
+                        futures::executor::block_on(async {
+                            sender.send(config).await.expect("Channel should be open");
+                        })
                     }
                 },
                 Err(err) => warn!("Received error whilst watching static configuration file. Reason: {err}")
@@ -105,4 +342,131 @@ pub fn watch_config(sender: tokio::sync::mpsc::Sender<Config>) -> Result<(), not
     });
     info!("Set up static configuration file listener at '{}'", Config::path());
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::path::PathBuf;
+    use notify_debouncer_mini::{DebouncedEvent, DebouncedEventKind};
+    use crate::config::{Config, ConfigFormat, ConfigReloader, expand_env};
+
+    fn get_rule_labels(config: &Config) -> Vec<(String, String)> {
+        let mut labels = config.get_registry("registry").unwrap().into_iter().collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ConfigFormat::from_path("config.yml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("/app/config.yaml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("/app/config.TOML"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("config.json"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("config"), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn test_equal_formats() {
+        let yaml = Config::from_str("registries:\n  registry:\n    network: abwart\n    rule:\n      weekly:\n        revisions: 5\n        tidy: true\n", ConfigFormat::Yaml).unwrap();
+        let toml = Config::from_str("[registries.registry]\nnetwork = \"abwart\"\n\n[registries.registry.rule.weekly]\nrevisions = 5\ntidy = true\n", ConfigFormat::Toml).unwrap();
+        let json = Config::from_str(r#"{"registries":{"registry":{"network":"abwart","rule":{"weekly":{"revisions":5,"tidy":true}}}}}"#, ConfigFormat::Json).unwrap();
+        assert_eq!(get_rule_labels(&yaml), vec![
+            (String::from("abwart.network"), String::from("abwart")),
+            (String::from("abwart.rule.weekly.revisions"), String::from("5")),
+            (String::from("abwart.rule.weekly.tidy"), String::from("true"))
+        ]);
+        assert_eq!(toml, yaml);
+        assert_eq!(json, yaml);
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let err = Config::from_str("registries:\n  registry:\n    rule:\n      weekly:\n        schedule: every monday\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid schedule 'every monday' of rule 'weekly' in registry 'registry'"));
+        let err = Config::from_str("registries:\n  registry:\n    default:\n      schedule: 0 0\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid schedule '0 0' of the default rule in registry 'registry'"));
+        assert!(Config::from_str("registries:\n  registry:\n    rule:\n      weekly:\n        schedule: 0 0 0 * * MON *\n", ConfigFormat::Yaml).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = Config::from_str("registries:\n  registry:\n    rule:\n      nightly:\n        tag.pattern: nightly-(.+\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid pattern 'nightly-(.+' of policy 'tag.pattern' in rule 'nightly' in registry 'registry'"));
+        let err = Config::from_str("registries:\n  registry:\n    default:\n      image.pattern.not: '[a-z'\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid pattern '[a-z' of policy 'image.pattern.not' in the default rule in registry 'registry'"));
+        let err = Config::from_str("registries:\n  registry:\n    rule:\n      rc:\n        tag.glob: '*-rc[0-9'\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid glob '*-rc[0-9' of policy 'tag.glob' in rule 'rc' in registry 'registry'"));
+        let err = Config::from_str("registries:\n  registry:\n    rule:\n      naming:\n        tag.allowed: '^(v\\d+|latest$'\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid pattern '^(v\\d+|latest$' of policy 'tag.allowed' in rule 'naming' in registry 'registry'"));
+        let err = Config::from_str("registries:\n  registry:\n    rule:\n      features:\n        keep.group: 'feature-(?<group>\\d+;2'\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Invalid pattern 'feature-(?<group>\\d+' of policy 'keep.group' in rule 'features' in registry 'registry'"));
+        assert!(Config::from_str("registries:\n  registry:\n    rule:\n      nightly:\n        tag.pattern: ^nightly-.+$\n        build-number.pattern: '(\\d+)$'\n", ConfigFormat::Yaml).is_ok());
+        assert!(Config::from_str("registries:\n  registry:\n    rule:\n      features:\n        keep.group: 'feature-(?<group>\\d+)-;2'\n        tag.allowed: ^feature-\n", ConfigFormat::Yaml).is_ok());
+    }
+
+    #[test]
+    fn test_expand_env() {
+        std::env::set_var("ABWART_TEST_PASSWORD", "secret");
+        assert_eq!(expand_env("${ABWART_TEST_PASSWORD}"), Ok(String::from("secret")));
+        assert_eq!(expand_env("pre-${ABWART_TEST_PASSWORD}-post"), Ok(String::from("pre-secret-post")));
+        assert_eq!(expand_env("$$${ABWART_TEST_PASSWORD}$$"), Ok(String::from("$secret$")));
+        assert_eq!(expand_env("$${ABWART_TEST_PASSWORD}"), Ok(String::from("${ABWART_TEST_PASSWORD}")));
+        assert_eq!(expand_env("^v[0-9]+$"), Ok(String::from("^v[0-9]+$")));
+        assert!(expand_env("${ABWART_TEST_UNSET}").unwrap_err().contains("ABWART_TEST_UNSET"));
+        assert!(expand_env("${ABWART_TEST_PASSWORD").is_err());
+    }
+
+    #[test]
+    fn test_standalone_registries() {
+        let config = Config::from_str("registries:\n  registry:\n    network: bridge\n  hosted:\n    host: registry.example.com\n    insecure: true\n", ConfigFormat::Yaml).unwrap();
+        let standalone = config.get_standalone_registries();
+        assert_eq!(standalone.len(), 1);
+        assert_eq!(standalone["hosted"]["abwart.host"], "registry.example.com");
+        assert_eq!(standalone["hosted"]["abwart.insecure"], "true");
+    }
+
+    #[test]
+    fn test_config_env() {
+        std::env::set_var("ABWART_TEST_USER", "admin");
+        let config = Config::from_str("registries:\n  registry:\n    username: ${ABWART_TEST_USER}\n    password: p$$ss\n    webhook: https://hooks.example.com/${ABWART_TEST_USER}\n", ConfigFormat::Yaml).unwrap();
+        let labels = config.get_registry("registry").unwrap();
+        assert_eq!(labels["abwart.username"], "admin");
+        assert_eq!(labels["abwart.password"], "p$ss");
+        assert_eq!(labels["abwart.webhook"], "https://hooks.example.com/admin");
+        let err = Config::from_str("registries:\n  registry:\n    password: ${ABWART_TEST_MISSING}\n", ConfigFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Referenced environment variable 'ABWART_TEST_MISSING' isn't set in registry 'registry'"));
+    }
+
+    #[test]
+    fn test_coalesce_reloads() {
+        let event = |kind| DebouncedEvent { path: PathBuf::from("config.yml"), kind };
+        let parses = Cell::new(0);
+        let parse = |content: &str| {
+            parses.set(parses.get() + 1);
+            Config::from_str(content, ConfigFormat::Yaml)
+        };
+        let first = "registries:\n  registry:\n    network: first\n";
+        let mut reloader = ConfigReloader::new(parse(first).ok());
+
+        // a burst of writes of the same content results in no update
+        let burst = vec![event(DebouncedEventKind::Any), event(DebouncedEventKind::AnyContinuous), event(DebouncedEventKind::Any)];
+        assert_eq!(reloader.reload(&burst, || parse(first)), None);
+        // a burst of writes changing the content results in exactly one update
+        let second = "registries:\n  registry:\n    network: second\n";
+        let updates = (0..3).filter_map(|_| reloader.reload(&burst, || parse(second))).collect::<Vec<_>>();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].get_registry("registry").unwrap()["abwart.network"], "second");
+        assert_eq!(parses.get(), 5);
+
+        assert_eq!(reloader.reload(&[event(DebouncedEventKind::AnyContinuous)], || parse(first)), None);
+        assert_eq!(parses.get(), 5);
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        let err = Config::from_str("[registries", ConfigFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("Invalid toml"));
+        let err = Config::from_str("{", ConfigFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("Invalid json"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use bollard::{API_DEFAULT_VERSION, Docker};
+
+pub const DOCKER_HOST_ENV: &str = "DOCKER_HOST";
+pub const DOCKER_TLS_VERIFY_ENV: &str = "DOCKER_TLS_VERIFY";
+pub const DOCKER_CERT_PATH_ENV: &str = "DOCKER_CERT_PATH";
+/// Socket which is used when the `DOCKER_HOST` environment variable isn't set
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+/// Read/write timeout of the connections to the docker daemon in seconds
+const DOCKER_TIMEOUT: u64 = 30;
+
+/// Endpoint of the docker daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DockerEndpoint {
+    /// Unix socket at the given path
+    Unix(String),
+    /// Plain http connection to the given address
+    Http(String),
+    /// Https connection to the given address using the `key.pem`, `cert.pem` and `ca.pem` in the certificate directory
+    Tls { address: String, cert_path: PathBuf }
+}
+
+impl Default for DockerEndpoint {
+    fn default() -> Self {
+        Self::Unix(String::from(DEFAULT_SOCKET))
+    }
+}
+
+impl Display for DockerEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DockerEndpoint::Unix(path) => write!(f, "unix://{path}"),
+            DockerEndpoint::Http(address) => write!(f, "{address}"),
+            DockerEndpoint::Tls { address, .. } => write!(f, "{address} (tls)")
+        }
+    }
+}
+
+impl DockerEndpoint {
+    /// Get the endpoint from the `DOCKER_HOST`, `DOCKER_TLS_VERIFY` and `DOCKER_CERT_PATH` environment variables
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok();
+        Self::parse(var(DOCKER_HOST_ENV).as_deref(), var(DOCKER_TLS_VERIFY_ENV).as_deref(), var(DOCKER_CERT_PATH_ENV).as_deref())
+    }
+
+    /// Get the endpoint from the values of the docker environment variables. As with the docker cli any non-empty
+    /// `tls_verify` value enables tls for tcp hosts
+    pub fn parse(host: Option<&str>, tls_verify: Option<&str>, cert_path: Option<&str>) -> Result<Self, String> {
+        let host = match host.map(str::trim) {
+            Some(host) if !host.is_empty() => host,
+            _ => return Ok(Self::default())
+        };
+        let tls = tls_verify.is_some_and(|value| !value.trim().is_empty());
+        let cert_path = || match cert_path.map(str::trim) {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => Path::new(&std::env::var("HOME").unwrap_or_default()).join(".docker")
+        };
+
+        if let Some(path) = host.strip_prefix("unix://") {
+            if path.is_empty() {
+                return Err(format!("The host '{host}' is missing the socket path"))
+            }
+            Ok(Self::Unix(path.to_string()))
+        } else if host.starts_with("https://") || (tls && (host.starts_with("tcp://") || host.starts_with("http://"))) {
+            Ok(Self::Tls { address: host.to_string(), cert_path: cert_path() })
+        } else if host.starts_with("tcp://") || host.starts_with("http://") {
+            Ok(Self::Http(host.to_string()))
+        } else {
+            Err(format!("The host '{host}' has an unsupported scheme. Expected 'unix://', 'tcp://', 'http://' or 'https://'"))
+        }
+    }
+
+    /// Create a docker client connected to the endpoint
+    pub fn connect(&self) -> Result<Docker, String> {
+        let result = match self {
+            DockerEndpoint::Unix(path) => Docker::connect_with_unix(path, DOCKER_TIMEOUT, API_DEFAULT_VERSION),
+            DockerEndpoint::Http(address) => Docker::connect_with_http(address, DOCKER_TIMEOUT, API_DEFAULT_VERSION),
+            DockerEndpoint::Tls { address, cert_path } => {
+                // Bollard only strips the tcp and https schemes of tls addresses
+                let address = address.replacen("http://", "tcp://", 1);
+                Docker::connect_with_ssl(&address, &cert_path.join("key.pem"), &cert_path.join("cert.pem"),
+                                         &cert_path.join("ca.pem"), DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+            }
+        };
+        result.map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use crate::docker::DockerEndpoint;
+
+    #[test]
+    fn test_docker_endpoint() {
+        assert_eq!(DockerEndpoint::parse(None, None, None), Ok(DockerEndpoint::Unix(String::from("/var/run/docker.sock"))));
+        assert_eq!(DockerEndpoint::parse(Some(""), Some("1"), None), Ok(DockerEndpoint::default()));
+        assert_eq!(DockerEndpoint::parse(Some("unix:///run/user/1000/docker.sock"), None, None), Ok(DockerEndpoint::Unix(String::from("/run/user/1000/docker.sock"))));
+        assert_eq!(DockerEndpoint::parse(Some("tcp://docker:2375"), None, Some("/certs")), Ok(DockerEndpoint::Http(String::from("tcp://docker:2375"))));
+        assert_eq!(DockerEndpoint::parse(Some("tcp://docker:2376"), Some("1"), Some("/certs")), Ok(DockerEndpoint::Tls {
+            address: String::from("tcp://docker:2376"),
+            cert_path: PathBuf::from("/certs")
+        }));
+        assert_eq!(DockerEndpoint::parse(Some("https://docker:2376"), None, Some("/certs")), Ok(DockerEndpoint::Tls {
+            address: String::from("https://docker:2376"),
+            cert_path: PathBuf::from("/certs")
+        }));
+        assert_eq!(DockerEndpoint::parse(Some("tcp://docker:2375"), Some(""), None), Ok(DockerEndpoint::Http(String::from("tcp://docker:2375"))));
+        assert!(DockerEndpoint::parse(Some("unix://"), None, None).is_err());
+        assert!(DockerEndpoint::parse(Some("ssh://user@docker"), None, None).is_err());
+    }
+}
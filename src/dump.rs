@@ -0,0 +1,107 @@
+use std::io::Write;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::api::distribution::Distribution;
+use crate::api::DistributionConfig;
+use crate::api::tag::Tag;
+use crate::error::Error;
+
+/// Metadata of a single tag in the inventory of a registry
+#[derive(Serialize, Debug)]
+struct TagRecord<'a> {
+    repository: &'a str,
+    name: &'a str,
+    digest: &'a str,
+    created: DateTime<Utc>,
+    /// The registry api doesn't expose when a tag was pushed
+    pushed: Option<DateTime<Utc>>,
+    size: u64,
+    is_index: bool,
+    media_type: &'a str
+}
+
+impl<'a> TagRecord<'a> {
+    fn new(repository: &'a str, tag: &'a Tag) -> Self {
+        Self {
+            repository,
+            name: &tag.name,
+            digest: &tag.digest,
+            created: tag.created,
+            pushed: None,
+            size: tag.size,
+            is_index: tag.is_index(),
+            media_type: &tag.media_type
+        }
+    }
+}
+
+/// Write all tags of all repositories in the registry as json array to the writer. The tags are
+/// written per repository to prevent buffering the whole inventory. Returns the amount of written tags
+pub async fn dump_registry(config: &DistributionConfig, out: &mut impl Write) -> Result<usize, Error> {
+    let repositories = Distribution::new(Arc::new(config.clone())).get_repositories(None).await?;
+    let mut count = 0;
+    write(out, "[")?;
+    for repository in repositories {
+        for tag in repository.get_tags_with_data().await? {
+            let record = serde_json::to_string(&TagRecord::new(&repository.name, &tag)).map_err(|err| Error::DumpFailed(err.to_string()))?;
+            write(out, if count == 0 { "\n  " } else { ",\n  " })?;
+            write(out, &record)?;
+            count += 1;
+        }
+        out.flush().map_err(|err| Error::DumpFailed(err.to_string()))?;
+    }
+    write(out, if count == 0 { "]\n" } else { "\n]\n" })?;
+    out.flush().map_err(|err| Error::DumpFailed(err.to_string()))?;
+    Ok(count)
+}
+
+fn write(out: &mut impl Write, content: &str) -> Result<(), Error> {
+    out.write_all(content.as_bytes()).map_err(|err| Error::DumpFailed(err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+    use serde_json::{json, Value};
+    use crate::dump::dump_registry;
+    use crate::test::{get_mock_distribution_config, mock_catalog, mock_manifest, mock_tag_list};
+
+    #[tokio::test]
+    async fn test_dump_registry() {
+        let mut server = mockito::Server::new_async().await;
+        let created = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let _catalog = mock_catalog(&mut server, vec!["app", "api"]).await;
+        let _app = mock_tag_list(&mut server, "app", vec!["v1", "v2"]).await;
+        let _api = mock_tag_list(&mut server, "api", vec!["latest"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", created, 20).await;
+        let _latest = mock_manifest(&mut server, "api", "latest", "sha256:latest", created, 30).await;
+
+        let mut out = Vec::new();
+        let config = get_mock_distribution_config(&server);
+        assert_eq!(dump_registry(&config, &mut out).await.unwrap(), 3);
+        let inventory = serde_json::from_slice::<Value>(&out).unwrap();
+        let record = |repository: &str, name: &str, size: u64| json!({
+            "repository": repository,
+            "name": name,
+            "digest": format!("sha256:{name}"),
+            "created": "2024-01-01T00:00:00Z",
+            "pushed": null,
+            "size": size,
+            "is_index": false,
+            "media_type": "application/vnd.docker.distribution.manifest.v2+json"
+        });
+        assert_eq!(inventory, json!([record("app", "v1", 10), record("app", "v2", 20), record("api", "latest", 30)]));
+    }
+
+    #[tokio::test]
+    async fn test_dump_empty_registry() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec![]).await;
+
+        let mut out = Vec::new();
+        assert_eq!(dump_registry(&get_mock_distribution_config(&server), &mut out).await.unwrap(), 0);
+        assert_eq!(serde_json::from_slice::<Value>(&out).unwrap(), json!([]));
+    }
+}
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use crate::api::error::ApiError;
 
 #[derive(thiserror::Error, Debug)]
@@ -11,6 +12,9 @@ pub enum Error {
     #[error("The registry container '{0}' doesn't have a network")]
     NoNetwork(String),
 
+    #[error("The standalone registry '{0}' is missing a host")]
+    MissingHost(String),
+
     #[error("The registry container '{0}' doesn't exist")]
     InexistentContainer(String),
 
@@ -27,5 +31,36 @@ pub enum Error {
     ApiError(#[from] ApiError),
 
     #[error("The shared config cannot be locked")]
-    ConfigLockError()
+    ConfigLocked(),
+
+    #[error("The garbage collector failed. Reason: {0}")]
+    GarbageCollectorFailed(String),
+
+    #[error("The garbage collector didn't finish within {0:?}")]
+    GarbageCollectorTimeout(Duration),
+
+    #[error("The cleanup plan is invalid. Reason: {0}")]
+    InvalidPlan(String),
+
+    #[error("The registry '{0}' is locked by another abwart process ({1})")]
+    RegistryLocked(String, String),
+
+    #[error("Unable to lock the registry '{0}'. {1}")]
+    LockUnavailable(String, String),
+
+    #[error("Unable to write the inventory. Reason: {0}")]
+    DumpFailed(String),
+
+    #[error("The static configuration file is invalid. Reason: {0}")]
+    InvalidConfig(String),
+
+    #[error("The scan report is invalid. Reason: {0}")]
+    InvalidScanReport(String),
+
+    #[error("The pull log is invalid. Reason: {0}")]
+    InvalidPullLog(String),
+
+    #[cfg(feature = "kube")]
+    #[error("Unable to query the kubernetes api. Reason: {0}")]
+    KubernetesApi(String)
 }
@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use bollard::Docker;
+use bollard::exec::{CreateExecOptions, StartExecOptions};
+use log::{debug, warn};
+use tokio::time::sleep;
+use crate::api::error::ApiError;
+use crate::api::repository::Repository;
+use crate::error::Error;
+use crate::label;
+use crate::policies::parse_std_duration;
+
+/// Command which is executed inside the registry container to run the garbage collector
+const GC_COMMAND: [&str; 2] = ["/bin/registry", "garbage-collect"];
+/// Flag of the garbage collector which additionally deletes all untagged manifests
+const DELETE_UNTAGGED_FLAG: &str = "--delete-untagged";
+/// Per default the configuration of the registry is expected at the default path of the registry image
+const DEFAULT_CONFIG_PATH: &str = "/etc/docker/registry/config.yml";
+/// Interval in which a running garbage collector exec is checked for completion
+const EXEC_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GarbageCollectorConfig {
+    /// How many times a failed garbage collector run is retried
+    pub retries: u32,
+    /// Maximum duration of a single garbage collector run
+    pub timeout: Duration,
+    /// Initial backoff between two attempts which is doubled after every failed attempt
+    pub backoff: Duration,
+    /// Pause between the deletion of the tags and the garbage collector run which gives registries with
+    /// eventually consistent storage backends time to reconcile the deletions
+    pub delay: Duration,
+    pub mode: GarbageCollectorMode,
+    /// Command which replaces the whole default garbage collector command
+    pub command: Option<Vec<String>>,
+    /// Path of the registry configuration inside the registry container
+    pub config_path: String,
+    /// Whether untagged manifests are deleted by the garbage collector
+    pub delete_untagged: bool,
+    /// Whether the garbage collector is run after the rules were applied
+    pub enabled: bool
+}
+
+/// How the garbage collector of a registry is run
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum GarbageCollectorMode {
+    /// Exec the garbage collector of the registry binary inside the registry container
+    #[default]
+    Exec,
+    /// Delete the blobs of deleted manifests which are no longer referenced using the registry api. This is meant
+    /// for externally managed registries abwart can't exec into
+    Api
+}
+
+impl GarbageCollectorMode {
+    fn from_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "exec" => Some(Self::Exec),
+            "api" => Some(Self::Api),
+            _ => None
+        }
+    }
+}
+
+impl Default for GarbageCollectorConfig {
+    fn default() -> Self {
+        Self { retries: 2, timeout: Duration::from_secs(600), backoff: Duration::from_secs(5), delay: Duration::ZERO, mode: GarbageCollectorMode::default(),
+            command: None, config_path: String::from(DEFAULT_CONFIG_PATH), delete_untagged: true, enabled: true }
+    }
+}
+
+impl GarbageCollectorConfig {
+    /// Parse the `gc.*` labels of an instance. Invalid values fall back to the default value
+    pub fn from_labels(labels: &HashMap<String, String>) -> Self {
+        let mut config = Self::default();
+        if let Some(retries) = labels.get(&label("gc.retries")) {
+            if let Ok(retries) = retries.parse::<u32>() {
+                config.retries = retries
+            } else {
+                warn!("Received invalid garbage collector retries value '{retries}'. Expected positive integer. Using default ({}) instead", config.retries)
+            }
+        }
+        if let Some(timeout) = labels.get(&label("gc.timeout")) {
+            match parse_std_duration(timeout) {
+                Some(timeout) if !timeout.is_zero() => config.timeout = timeout,
+                _ => warn!("Received invalid garbage collector timeout '{timeout}'. Using default ({:?}) instead", config.timeout)
+            }
+        }
+        if let Some(backoff) = labels.get(&label("gc.backoff")) {
+            match parse_std_duration(backoff) {
+                Some(backoff) => config.backoff = backoff,
+                None => warn!("Received invalid garbage collector backoff '{backoff}'. Using default ({:?}) instead", config.backoff)
+            }
+        }
+        if let Some(delay) = labels.get(&label("gc.delay")) {
+            match parse_std_duration(delay) {
+                Some(delay) => config.delay = delay,
+                None => warn!("Received invalid garbage collector delay '{delay}'. Using default ({:?}) instead", config.delay)
+            }
+        }
+        if let Some(mode) = labels.get(&label("gc.mode")) {
+            match GarbageCollectorMode::from_str(mode) {
+                Some(mode) => config.mode = mode,
+                None => warn!("Received invalid garbage collector mode '{mode}'. Expected 'exec' or 'api'. Using default ({:?}) instead", config.mode)
+            }
+        }
+        if let Some(enabled) = labels.get(&label("gc.enable")) {
+            match enabled.parse::<bool>() {
+                Ok(enabled) => config.enabled = enabled,
+                Err(_) => warn!("Received invalid garbage collector enable value '{enabled}'. Expected boolean. Using default ({}) instead", config.enabled)
+            }
+        }
+        if let Some(command) = labels.get(&label("gc.command")) {
+            let command = command.split_whitespace().map(String::from).collect::<Vec<_>>();
+            if command.is_empty() {
+                warn!("Received empty garbage collector command. Using default ({}) instead", config.command().join(" "))
+            } else {
+                config.command = Some(command)
+            }
+        }
+        if let Some(path) = labels.get(&label("gc.config")) {
+            if path.trim().is_empty() {
+                warn!("Received empty garbage collector config path. Using default ({}) instead", config.config_path)
+            } else {
+                config.config_path = path.trim().to_string()
+            }
+        }
+        if let Some(delete_untagged) = labels.get(&label("gc.delete-untagged")) {
+            match delete_untagged.parse::<bool>() {
+                Ok(delete_untagged) => config.delete_untagged = delete_untagged,
+                Err(_) => warn!("Received invalid garbage collector delete untagged value '{delete_untagged}'. Expected boolean. Using default ({}) instead", config.delete_untagged)
+            }
+        }
+        config
+    }
+
+    /// Get the command which runs the garbage collector inside the registry container. A custom command
+    /// is used as it is without adding the config path or any flags
+    pub fn command(&self) -> Vec<String> {
+        if let Some(command) = &self.command {
+            return command.clone()
+        }
+        let mut command = GC_COMMAND.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        if self.delete_untagged {
+            command.push(String::from(DELETE_UNTAGGED_FLAG));
+        }
+        command.push(self.config_path.clone());
+        command
+    }
+}
+
+/// Delete the blobs of deleted manifests which aren't referenced by any remaining tag of the repository anymore using
+/// the registry api. Blobs which fail to delete are skipped <br>
+/// Returns the amount of deleted blobs
+pub async fn reclaim_blobs(repository: &Repository, candidates: &HashSet<String>) -> Result<usize, ApiError> {
+    let mut referenced = HashSet::new();
+    for tag in repository.get_tags().await? {
+        referenced.extend(repository.get_blobs(&tag).await?);
+    }
+    let mut unreferenced = candidates.difference(&referenced).collect::<Vec<_>>();
+    unreferenced.sort();
+
+    let mut reclaimed = 0;
+    for digest in unreferenced {
+        match repository.delete_blob(digest).await {
+            Ok(_) => {
+                debug!("Deleted unreferenced blob '{digest}' from repository '{}'", repository.name);
+                reclaimed += 1
+            },
+            Err(err) => warn!("Unable to delete unreferenced blob '{digest}' from repository '{}'. Reason: {err}", repository.name)
+        }
+    }
+    Ok(reclaimed)
+}
+
+/// Exec the `registry garbage-collect` utility in the registry container and wait until it exited
+pub async fn exec_garbage_collector(client: &Docker, container: &str, command: &[String]) -> Result<(), Error> {
+    let exec = client.create_exec(container, CreateExecOptions::<&str> {
+        cmd: Some(command.iter().map(String::as_str).collect()),
+        user: Some("root"),
+        ..CreateExecOptions::default()
+    }).await.map_err(|err| Error::GarbageCollectorFailed(err.to_string()))?;
+
+    client.start_exec(exec.id.as_str(), Some(StartExecOptions { detach: true, ..StartExecOptions::default() }))
+        .await
+        .map_err(|err| Error::GarbageCollectorFailed(err.to_string()))?;
+
+    loop {
+        let inspect = client.inspect_exec(exec.id.as_str()).await.map_err(|err| Error::GarbageCollectorFailed(err.to_string()))?;
+        if inspect.running.unwrap_or(false) {
+            debug!("Garbage collector exec '{}' is still running", exec.id);
+            sleep(EXEC_POLL_INTERVAL).await;
+            continue
+        }
+        return match inspect.exit_code {
+            Some(0) | None => Ok(()),
+            Some(code) => Err(Error::GarbageCollectorFailed(format!("Process exited with code {code}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use crate::garbage_collector::GarbageCollectorConfig;
+    use crate::label;
+
+    fn get_config(labels: Vec<(&str, &str)>) -> GarbageCollectorConfig {
+        GarbageCollectorConfig::from_labels(&labels.into_iter().map(|(key, value)| (label(key), value.to_string())).collect::<HashMap<_, _>>())
+    }
+
+    #[test]
+    fn test_default_command() {
+        assert_eq!(get_config(vec![]).command(), vec!["/bin/registry", "garbage-collect", "--delete-untagged", "/etc/docker/registry/config.yml"]);
+    }
+
+    #[test]
+    fn test_custom_config() {
+        let config = get_config(vec![("gc.config", "/etc/registry.yml"), ("gc.delete-untagged", "false")]);
+        assert_eq!(config.command(), vec!["/bin/registry", "garbage-collect", "/etc/registry.yml"]);
+    }
+
+    #[test]
+    fn test_custom_command() {
+        let config = get_config(vec![("gc.command", "/usr/bin/zot  gc /etc/zot/config.json"), ("gc.config", "/etc/registry.yml")]);
+        assert_eq!(config.command(), vec!["/usr/bin/zot", "gc", "/etc/zot/config.json"]);
+        assert_eq!(get_config(vec![("gc.command", " ")]).command, None);
+    }
+}
@@ -1,16 +1,35 @@
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::net::Ipv6Addr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use futures::{stream, StreamExt};
 use bollard::Docker;
-use bollard::exec::{CreateExecOptions, StartExecOptions};
 use bollard::models::{ContainerSummary, EventActor};
 use bollard::secret::EndpointSettings;
 use log::{debug, error, info, warn};
 use regex::Regex;
+use tokio::time::{sleep, timeout};
 use crate::api::distribution::Distribution;
+use crate::api::repository::Repository;
 use crate::api::DistributionConfig;
+use crate::api::error::ApiError;
+use crate::api::tag::Tag;
 use crate::error::Error;
 use crate::{label, NAME};
-use crate::config::Config;
+use crate::config::{Config, InstanceConfig};
+use crate::garbage_collector::{exec_garbage_collector, reclaim_blobs, GarbageCollectorConfig, GarbageCollectorMode};
+use crate::plan::{CleanupPlan, DeleteBudget, DryRunOutput, PlannedTag, RepositoryPlan};
+use crate::report::{group_aliases, Representative, RunReport};
+use crate::kube::{deployed_images, DeployedImages};
+use crate::pulls::{PullLog, PullSource};
+use crate::scan::{parse_scan_report, ScanReference};
+use crate::state::{LockError, StateStore};
+use crate::notify::{RunSummary, send_summary};
+use crate::policies::{format_size, parse_duration, parse_size, parse_std_duration};
 use crate::policies::age_max::{AGE_MAX_LABEL, AgeMaxPolicy};
 use crate::policies::age_min::{AGE_MIN_LABEL, AgeMinPolicy};
 use crate::policies::image_pattern::{IMAGE_PATTERN_LABEL, ImagePatternPolicy};
@@ -26,26 +45,124 @@ pub struct Instance {
     pub distribution: DistributionConfig,
     pub default_rule: Rule,
     pub rules: HashMap<String, Rule>,
-    pub port: u16,
     pub cleanup_schedule: Option<String>,
+    pub verify_deletions: bool,
+    /// Apply all rules once when the registry is scheduled instead of waiting for the first tick of their schedules
+    pub run_on_start: bool,
+    /// Apply the rules which missed a tick of their schedule whilst abwart wasn't running once the registry is scheduled
+    pub catch_up: bool,
+    /// Window within which the scheduled runs of the registry are delayed by a stable offset to stagger registries sharing a schedule
+    pub jitter: Option<Duration>,
+    /// Url to which a summary of every run is posted
+    pub webhook: Option<String>,
+    pub immutable_pattern: Option<Regex>,
+    /// Repository and reference of the artifact in the registry which contains additional configuration
+    pub config_artifact: Option<(String, String)>,
+    pub garbage_collector: GarbageCollectorConfig,
+    /// Delay between the last push to a repository and its cleanup when registry notifications are received
+    pub notify_delay: Duration,
+    pub on_error: ErrorStrategy,
+    /// Tags created within this duration before a run are never deleted independent of the rules
+    pub min_age_absolute: Option<chrono::Duration>,
+    /// Maximum amount of bytes which are selected for deletion in a single run
+    pub max_delete_bytes: Option<u64>,
+    /// Maximum amount of tags which are deleted in a single run
+    pub max_deletions: Option<usize>,
+    /// Maximum fraction of the tags of a repository which is deleted in a single run. Repositories exceeding it are left unmodified
+    pub guard_max_fraction: Option<f64>,
+    /// Names of the registries whose co-scheduled runs have to finish before the rules of this registry are applied
+    pub after: Vec<String>,
+    /// Path to a vulnerability scan report whose flagged images are deleted on every run
+    pub scan_report: Option<String>,
+    /// Path to a json file with the times at which the tags of the registry were last pulled
+    pub pull_log: Option<String>,
+    /// Only the repositories starting with the prefix are considered when listing the catalog
+    pub catalog_prefix: Option<String>,
+    /// Only the repositories matching at least one of the patterns are considered. Considers all repositories when empty
+    pub repositories_include: Vec<Regex>,
+    /// Repositories matching any of the patterns are never considered, not even their tags are listed
+    pub repositories_exclude: Vec<Regex>,
+    /// Which tag name represents the tags sharing a digest in logs
+    pub representative: Representative,
+    /// Log the deletions and garbage collector runs instead of performing them
+    pub dry_run: bool,
+    /// Path to which the deletions of a dry run are written as json. `-` writes them to stdout
+    pub dry_run_output: Option<String>,
+    /// Whether the registry is declared in the static configuration file without a container
+    pub standalone: bool,
+    /// Digest and content of the last loaded config artifact
+    artifact_cache: Mutex<Option<(String, String)>>,
+    labels: HashMap<String, String>,
     client: Arc<Docker>
 }
 
+/// How errors whilst applying the rules of a registry are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorStrategy {
+    /// Log the error and continue with the remaining repositories and tags
+    #[default]
+    Continue,
+    /// Abort the whole run on the first error
+    Abort
+}
+
+impl ErrorStrategy {
+    fn from_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "continue" => Some(Self::Continue),
+            "abort" => Some(Self::Abort),
+            _ => None
+        }
+    }
+}
+
 const RULE_REGEX: &str = "rule\\.(?<name>[a-z]+)";
 const DEFAULT_RULE_REGEX: &str = "default";
-const POLICY_NAME_REGEX: &str = "(?<policy>[a-z\\.]+)";
+const POLICY_NAME_REGEX: &str = "(?<policy>[a-z\\.\\-]+)";
+/// Values of a default policy which remove the policy from the default rule
+const DISABLED_POLICY_VALUES: [&str; 2] = ["off", "disabled"];
 /// Per default the schedule is set to daily at midnight
 const DEFAULT_SCHEDULE: &str = "0 0 0 * * * *";
+/// How many times the registry is asked whether a deleted manifest is gone when `delete.verify` is enabled
+const DELETE_VERIFY_ATTEMPTS: u32 = 4;
+/// Initial backoff between two deletion verifications which is doubled after every attempt
+const DELETE_VERIFY_BACKOFF: Duration = Duration::from_millis(500);
+/// Per default a repository is cleaned up 30 seconds after the last push to it
+const DEFAULT_NOTIFY_DELAY: Duration = Duration::from_secs(30);
 
 impl Instance {
     pub fn new(id: String, mut name: String, labels: HashMap<String, String>, networks: HashMap<String, EndpointSettings>, client: Arc<Docker>) -> Result<Self, Error> {
         let mut network = None;
         let mut port = 5000u16;
+        let host = labels.get(&label("host")).map(|host| host.trim().to_string()).filter(|host| !host.is_empty());
+        // containers are reached directly via http whereas registries without a container are usually only reachable via https
         // TODO: Check whether for actors outside scope "LOCAL" secure would make sense
-        let mut distribution = DistributionConfig::new(String::new(), None, None, true);
+        let mut distribution = DistributionConfig::new(String::new(), None, None, !networks.is_empty());
         let mut cleanup_schedule = None;
+        let mut verify_deletions = false;
+        let mut run_on_start = false;
+        let mut catch_up = false;
+        let mut jitter = None;
+        let mut webhook = None;
+        let mut immutable_pattern = None;
+        let mut config_artifact = None;
+        let mut notify_delay = DEFAULT_NOTIFY_DELAY;
+        let mut on_error = ErrorStrategy::default();
+        let mut min_age_absolute = None;
+        let mut max_delete_bytes = None;
+        let mut max_deletions = None;
+        let mut guard_max_fraction = None;
+        let mut dry_run_output = None;
+        let mut after = vec![];
+        let mut scan_report = None;
+        let mut pull_log = None;
+        let mut catalog_prefix = None;
+        let mut repositories_include = vec![];
+        let mut repositories_exclude = vec![];
+        let mut representative = Representative::default();
+        let garbage_collector = GarbageCollectorConfig::from_labels(&labels);
 
-        if networks.is_empty() {
+        if networks.is_empty() && host.is_none() {
             return Err(Error::NoNetwork(name))
         }
 
@@ -73,31 +190,249 @@ impl Instance {
                     warn!("Received invalid cleanup schedule '{custom_cleanup_schedule}'. Using none instead")
                 }
             }
-            distribution.username = labels.get(&label("username")).cloned();
-            distribution.password = labels.get(&label("password")).cloned();
+            if let Some(verify) = labels.get(&label("delete.verify")) {
+                if let Ok(verify) = verify.parse::<bool>() {
+                    verify_deletions = verify
+                } else {
+                    warn!("Received invalid delete verification value '{verify}'. Expected boolean. Using default ({verify_deletions}) instead")
+                }
+            }
+            if let Some(value) = labels.get(&label("run_on_start")) {
+                if let Ok(value) = value.parse::<bool>() {
+                    run_on_start = value
+                } else {
+                    warn!("Received invalid run on start value '{value}'. Expected boolean. Using default ({run_on_start}) instead")
+                }
+            }
+            if let Some(value) = labels.get(&label("catch_up")) {
+                if let Ok(value) = value.parse::<bool>() {
+                    catch_up = value
+                } else {
+                    warn!("Received invalid catch up value '{value}'. Expected boolean. Using default ({catch_up}) instead")
+                }
+            }
+            if let Some(value) = labels.get(&label("jitter")) {
+                match parse_std_duration(value) {
+                    Some(value) => jitter = Some(value).filter(|value| !value.is_zero()),
+                    None => warn!("Received invalid jitter '{value}'. Using none instead")
+                }
+            }
+            if let Some(url) = labels.get(&label("webhook")) {
+                match reqwest::Url::parse(url) {
+                    Ok(_) => webhook = Some(url.clone()),
+                    Err(err) => warn!("Received invalid webhook '{url}'. Using none instead. Reason: {err}")
+                }
+            }
+            if let Some(pattern) = labels.get(&label("immutable-pattern")) {
+                match Regex::new(pattern) {
+                    Ok(pattern) => immutable_pattern = Some(pattern),
+                    Err(err) => warn!("Received invalid immutable pattern '{pattern}'. Using none instead. Reason: {err}")
+                }
+            }
+            if let Some(artifact) = labels.get(&label("config-artifact")) {
+                config_artifact = parse_artifact_reference(artifact);
+                if config_artifact.is_none() {
+                    warn!("Received invalid config artifact reference '{artifact}'. Using none instead")
+                }
+            }
+            if let Some(platforms) = labels.get(&label("platform")) {
+                let platforms = platforms.split(',').map(|platform| platform.trim().to_string()).filter(|platform| !platform.is_empty()).collect::<Vec<_>>();
+                if platforms.is_empty() {
+                    warn!("Received empty platform chain. Using default ({}) instead", distribution.platforms.join(","))
+                } else {
+                    distribution.platforms = platforms
+                }
+            }
+            if let Some(timeout) = labels.get(&label("timeout")) {
+                match parse_std_duration(timeout) {
+                    Some(timeout) if !timeout.is_zero() => distribution.timeout = timeout,
+                    _ => warn!("Received invalid request timeout '{timeout}'. Using default ({:?}) instead", distribution.timeout)
+                }
+            }
+            if let Some(ca) = labels.get(&label("ca")) {
+                if std::path::Path::new(ca).is_file() {
+                    distribution.ca = Some(ca.clone());
+                    distribution.insecure = false
+                } else {
+                    warn!("Received invalid ca certificate path '{ca}'. Expected path to a PEM file. Using none instead")
+                }
+            }
+            if let Some(accept) = labels.get(&label("tls.accept-invalid-certs")) {
+                match accept.parse::<bool>() {
+                    Ok(accept) => {
+                        distribution.accept_invalid_certs = accept;
+                        distribution.insecure &= !accept
+                    },
+                    Err(_) => warn!("Received invalid value '{accept}' for accepting invalid certificates. Expected boolean. Using default ({}) instead", distribution.accept_invalid_certs)
+                }
+            }
+            if let Some(insecure) = labels.get(&label("insecure")) {
+                match insecure.parse::<bool>() {
+                    Ok(insecure) => distribution.insecure = insecure,
+                    Err(_) => warn!("Received invalid insecure value '{insecure}'. Expected boolean. Using default ({}) instead", distribution.insecure)
+                }
+            }
+            if let Some(proxy) = labels.get(&label("proxy")) {
+                match reqwest::Proxy::all(proxy.as_str()) {
+                    Ok(_) => distribution.proxy = Some(proxy.clone()),
+                    Err(err) => warn!("Received invalid proxy '{proxy}'. Using proxies from the environment instead. Reason: {err}")
+                }
+            }
+            if let Some(concurrency) = labels.get(&label("request.concurrency")) {
+                match concurrency.parse::<usize>() {
+                    Ok(concurrency) if concurrency > 0 => distribution.concurrency = concurrency,
+                    _ => warn!("Received invalid request concurrency '{concurrency}'. Expected non-zero positive integer. Using default ({}) instead", distribution.concurrency)
+                }
+            }
+            if let Some(retries) = labels.get(&label("request.retries")) {
+                match retries.parse::<u32>() {
+                    Ok(retries) => distribution.retry.retries = retries,
+                    Err(_) => warn!("Received invalid request retry count '{retries}'. Expected positive integer. Using default ({}) instead", distribution.retry.retries)
+                }
+            }
+            if let Some(backoff) = labels.get(&label("request.backoff")) {
+                match parse_std_duration(backoff) {
+                    Some(backoff) => distribution.retry.backoff = backoff,
+                    None => warn!("Received invalid request backoff '{backoff}'. Using default ({:?}) instead", distribution.retry.backoff)
+                }
+            }
+            if let Some(delay) = labels.get(&label("notify.delay")) {
+                match parse_std_duration(delay) {
+                    Some(delay) => notify_delay = delay,
+                    None => warn!("Received invalid notification delay '{delay}'. Using default ({notify_delay:?}) instead")
+                }
+            }
+            if let Some(strategy) = labels.get(&label("on-error")) {
+                match ErrorStrategy::from_str(strategy) {
+                    Some(strategy) => on_error = strategy,
+                    None => warn!("Received invalid error strategy '{strategy}'. Expected 'continue' or 'abort'. Using default ({on_error:?}) instead")
+                }
+            }
+            if let Some(value) = labels.get(&label("report.representative")) {
+                match Representative::from_str(value) {
+                    Some(value) => representative = value,
+                    None => warn!("Received invalid representative '{value}'. Expected 'semver' or 'lexical'. Using default ({representative:?}) instead")
+                }
+            }
+            if let Some(age) = labels.get(&label("min-age-absolute")) {
+                match parse_duration(age.clone()) {
+                    Some(age) => min_age_absolute = Some(age),
+                    None => warn!("Received invalid absolute minimum age '{age}'. Using none instead")
+                }
+            }
+            if let Some(max) = labels.get(&label("max-delete-bytes")) {
+                match parse_size(max) {
+                    Some(max) => max_delete_bytes = Some(max),
+                    None => warn!("Received invalid deletion budget '{max}'. Expected size. Using none instead")
+                }
+            }
+            if let Some(max) = labels.get(&label("max_deletions")) {
+                match max.trim().parse::<usize>() {
+                    Ok(max) => max_deletions = Some(max).filter(|max| *max > 0),
+                    Err(_) => warn!("Received invalid deletion limit '{max}'. Expected positive integer. Using none instead")
+                }
+            }
+            if let Some(fraction) = labels.get(&label("guard.max_fraction")) {
+                match fraction.trim().parse::<f64>() {
+                    Ok(value) if value > 0.0 && value <= 1.0 => guard_max_fraction = Some(value),
+                    _ => warn!("Received invalid maximum deletion fraction '{fraction}'. Expected number between 0 (exclusive) and 1. Using none instead")
+                }
+            }
+            if let Some(basis) = labels.get(&label("size.basis")) {
+                match basis.trim() {
+                    "compressed" => {},
+                    "uncompressed" => warn!("Uncompressed sizes aren't available since the registry api only exposes the compressed sizes of the layers. Using compressed sizes instead"),
+                    _ => warn!("Received invalid size basis '{basis}'. Expected 'compressed'. Using default (compressed) instead")
+                }
+            }
+            if let Some(registries) = labels.get(&label("after")) {
+                after = registries.split(',').map(|registry| registry.trim().to_string()).filter(|registry| !registry.is_empty()).collect();
+            }
+            if let Some(path) = labels.get(&label("scan-report")) {
+                scan_report = Some(path.clone()).filter(|path| !path.trim().is_empty());
+            }
+            if let Some(path) = labels.get(&label("pull-log")) {
+                pull_log = Some(path.clone()).filter(|path| !path.trim().is_empty());
+            }
+            if let Some(path) = labels.get(&label("dryrun.output")) {
+                dry_run_output = Some(path.trim().to_string()).filter(|path| !path.is_empty());
+            }
+            if let Some(prefix) = labels.get(&label("catalog-prefix")) {
+                catalog_prefix = Some(prefix.trim().to_string()).filter(|prefix| !prefix.is_empty());
+            }
+            if let Some(patterns) = labels.get(&label("repositories.include")) {
+                repositories_include = parse_patterns(patterns, "repository include");
+            }
+            if let Some(patterns) = labels.get(&label("repositories.exclude")) {
+                repositories_exclude = parse_patterns(patterns, "repository exclude");
+            }
+            distribution.username = labels.get(&label("username")).cloned().or_else(|| read_secret_label(&labels, "username_file"));
+            distribution.password = labels.get(&label("password")).cloned().or_else(|| read_secret_label(&labels, "password_file"));
         } else {
             info!("Using default instance attributes");
         }
 
-        let mut address = match &network {
-            Some(network) => networks.get(network.as_str()).expect("Network should exist").ip_address.clone(),
-            None => networks.values().next().expect("There should be at least one network").ip_address.clone()
-        }.unwrap_or(String::from("127.0.0.1"));
-        if address.is_empty() {
-            address = String::from("127.0.0.1")
+        if network.is_none() && host.is_none() {
+            network = select_network(&networks);
+            if networks.len() > 1 {
+                info!("Registry '{name}' is attached to multiple networks. Using network '{}'", network.clone().unwrap_or_default())
+            }
         }
 
-        distribution.host = format!("{address}:{port}");
+        distribution.host = match host {
+            Some(host) => host,
+            None => {
+                let address = network.as_ref()
+                    .and_then(|network| networks.get(network.as_str()))
+                    .and_then(endpoint_address)
+                    .unwrap_or(String::from("127.0.0.1"));
+                format_host(&address, port)
+            }
+        };
 
         if name.starts_with('/') {
             // the `/` in the container name can be removed for aesthetic reasons
             name = name[1..name.len()].to_string()
         }
 
-        debug!("Registered new registry '{name}' with: {address}:{port} ({network:?}) {rules:?} {default_rule:?}");
+        debug!("Registered new registry '{name}' with: {} ({network:?}) {rules:?} {default_rule:?}", distribution.host);
 
-        let mut instance = Self { id, port, name, rules, default_rule, distribution, cleanup_schedule, client };
-        instance.apply_defaults();
+        let mut instance = Self {
+            id,
+            name,
+            rules,
+            default_rule,
+            distribution,
+            cleanup_schedule,
+            verify_deletions,
+            run_on_start,
+            catch_up,
+            jitter,
+            webhook,
+            immutable_pattern,
+            config_artifact,
+            garbage_collector,
+            notify_delay,
+            on_error,
+            min_age_absolute,
+            max_delete_bytes,
+            max_deletions,
+            guard_max_fraction,
+            after,
+            scan_report,
+            pull_log,
+            catalog_prefix,
+            repositories_include,
+            repositories_exclude,
+            representative,
+            dry_run: false,
+            dry_run_output,
+            standalone: false,
+            artifact_cache: Mutex::new(None),
+            labels,
+            client
+        };
+        Instance::apply_defaults(&instance.default_rule, &mut instance.rules);
         Ok(instance)
     }
 
@@ -105,7 +440,7 @@ impl Instance {
         let id = actor.id.ok_or(Error::MissingId)?;
         let container = client.inspect_container(id.as_str(), None).await.map_err(|_| Error::InexistentContainer(id.clone()))?;
         let name = container.name.unwrap_or(id.clone())[1..].to_string();
-        let registry_config = config.lock().map_err(|_| Error::ConfigLockError())?.get_registry(&name).unwrap_or_default();
+        let registry_config = config.lock().map_err(|_| Error::ConfigLocked())?.get_registry(&name).unwrap_or_default();
         let mut labels = actor.attributes.unwrap_or_default();
         labels.extend(registry_config);
         Self::new(id, name, labels, container.network_settings.ok_or(Error::MissingNetworks)?.networks.unwrap_or_default(), client)
@@ -113,35 +448,97 @@ impl Instance {
 
     pub fn from_container(container: ContainerSummary, client: Arc<Docker>, config: Arc<Mutex<Config>>) -> Result<Instance, Error> {
         let id = container.id.ok_or(Error::MissingId)?;
-        let name = container.names.unwrap_or(Vec::new()).get(0).unwrap_or(&id).clone()[1..].to_string();
-        let registry_config = config.lock().map_err(|_| Error::ConfigLockError())?.get_registry(&name).unwrap_or_default();
+        let name = container.names.unwrap_or_default().first().unwrap_or(&id).clone()[1..].to_string();
+        let registry_config = config.lock().map_err(|_| Error::ConfigLocked())?.get_registry(&name).unwrap_or_default();
         let mut labels = container.labels.unwrap_or_default();
         labels.extend(registry_config);
         Self::new(id, name, labels, container.network_settings.ok_or(Error::MissingNetworks)?.networks.unwrap_or_default(), client)
     }
 
-    /// Apply the `default_tag_policies`, `default_repository_policies` and `default_schedule` to the rules in the current instance
-    fn apply_defaults(&mut self) {
-        self.rules.iter_mut().for_each(|(_, rule)| {
-            self.default_rule.tag_policies.iter().for_each(|(name, policy)| {
+    /// Create an instance of a registry which isn't running in a container managed by abwart (e.g. a hosted registry). The
+    /// registry is identified by its name and reached using the `host` of its configuration
+    pub fn standalone(name: String, labels: HashMap<String, String>, client: Arc<Docker>) -> Result<Instance, Error> {
+        if !labels.contains_key(&label("host")) {
+            return Err(Error::MissingHost(name))
+        }
+        let mut instance = Self::new(name.clone(), name, labels, HashMap::new(), client)?;
+        instance.standalone = true;
+        Ok(instance)
+    }
+
+    /// Apply the `default_tag_policies`, `default_repository_policies` and `default_schedule` of the default rule to the given rules
+    fn apply_defaults(default_rule: &Rule, rules: &mut HashMap<String, Rule>) {
+        rules.iter_mut().for_each(|(_, rule)| {
+            default_rule.tag_policies.iter().for_each(|(name, policy)| {
                 if !rule.tag_policies.contains_key(name) {
                     rule.tag_policies.insert(name, policy.clone());
                 }
             });
-            self.default_rule.repository_policies.iter().for_each(|(name, policy)| {
+            default_rule.repository_policies.iter().for_each(|(name, policy)| {
                 if !rule.repository_policies.contains_key(name) {
                     rule.repository_policies.insert(name, policy.clone());
                 }
             });
             if rule.schedule.is_empty() {
-                rule.schedule = self.default_rule.schedule.clone()
+                rule.schedule = default_rule.schedule.clone()
             }
             if rule.tidy.is_none() {
-                rule.tidy = Some(self.default_rule.tidy.unwrap_or(false))
+                rule.tidy = Some(default_rule.tidy.unwrap_or(false))
             }
         });
     }
 
+    /// Replace the rules of the instance with the rules resulting from the configuration in the config artifact. <br>
+    /// This is done before scheduling the instance to respect the schedules defined in the config artifact
+    pub async fn load_config_artifact(&mut self) {
+        if let Some((default_rule, rules)) = self.get_artifact_rules().await {
+            self.default_rule = default_rule;
+            self.rules = rules;
+        }
+    }
+
+    /// Get the default rule and the rules of the instance with the configuration from the config artifact applied. The
+    /// config artifact is preferred over the labels of the instance <br>
+    /// Returns `None` should there be no config artifact configured or should it be unavailable
+    async fn get_artifact_rules(&self) -> Option<(Rule, HashMap<String, Rule>)> {
+        let (repository, reference) = self.config_artifact.as_ref()?;
+        let artifact = Repository::new(repository.clone(), Arc::new(self.distribution.clone()));
+        // the artifact is only downloaded again when its digest changed since it was last loaded
+        let digest = artifact.get_digest(reference).await.ok();
+        let cached = self.artifact_cache.lock().ok().and_then(|cache| cache.clone())
+            .filter(|(cached, _)| digest.as_ref() == Some(cached))
+            .map(|(_, content)| content);
+        let content = match cached {
+            Some(content) => content,
+            None => match artifact.pull_artifact(reference).await {
+                Ok(content) => {
+                    if let (Some(digest), Ok(mut cache)) = (digest, self.artifact_cache.lock()) {
+                        *cache = Some((digest, content.clone()));
+                    }
+                    content
+                },
+                Err(err) => {
+                    info!("Unable to load config artifact '{repository}:{reference}' from registry '{}'. Using labels instead. Reason: {err}", self.name);
+                    return None
+                }
+            }
+        };
+        let artifact_labels = match serde_yaml::from_str::<InstanceConfig>(&content) {
+            Ok(config) => config.get_labels(),
+            Err(err) => {
+                warn!("Received invalid config artifact '{repository}:{reference}' in registry '{}'. Using labels instead. Reason: {err}", self.name);
+                return None
+            }
+        };
+        debug!("Loaded config artifact '{repository}:{reference}' from registry '{}'", self.name);
+
+        let mut labels = self.labels.clone();
+        labels.extend(artifact_labels);
+        let (default_rule, mut rules) = Instance::parse_rules(&self.id, &labels);
+        Instance::apply_defaults(&default_rule, &mut rules);
+        Some((default_rule, rules))
+    }
+
     fn get_default_rule_pattern() -> Regex {
         Regex::new(format!("{NAME}\\.{DEFAULT_RULE_REGEX}\\.{POLICY_NAME_REGEX}").as_str()).expect("Default rule pattern should be valid")
     }
@@ -179,6 +576,12 @@ impl Instance {
         labels.iter()
             .filter_map(|(key, value)| default_rule_pattern.captures(key).map(|captures| (captures["policy"].to_string(), value)))
             .for_each(|(key, value)| {
+                if DISABLED_POLICY_VALUES.contains(&value.trim().to_lowercase().as_str()) {
+                    debug!("Disabled default policy '{key}'");
+                    default_rule.tag_policies.remove(key.as_str());
+                    default_rule.repository_policies.remove(key.as_str());
+                    return
+                }
                 let entry = rule_labels.entry(default_rule_name.clone()).or_insert(vec![]);
                 entry.push((key, value.as_str()))
             });
@@ -209,11 +612,35 @@ impl Instance {
         (default_rule, rules)
     }
 
+    /// The default rule is run like any other rule when the instance only has default policies and no named rules.
+    /// Otherwise, it only holds the defaults of the named rules
+    fn scheduled_default_rule(&self) -> Option<&Rule> {
+        let default_prefix = label("default.");
+        let has_defaults = self.labels.keys().any(|key| key.starts_with(&default_prefix));
+        (self.rules.is_empty() && has_defaults).then_some(&self.default_rule)
+    }
+
+    /// Offset by which the scheduled runs of the registry are delayed. The offset lies within the jitter window and
+    /// is derived from the id of the registry which keeps it stable for the lifetime of the process
+    pub fn jitter_offset(&self) -> Duration {
+        let Some(window) = self.jitter else {
+            return Duration::ZERO
+        };
+        let mut hasher = DefaultHasher::new();
+        self.id.hash(&mut hasher);
+        Duration::from_millis(hasher.finish() % window.as_millis().max(1) as u64)
+    }
+
+    /// Get the names of all rules which are run on the registry including the default rule when it's run
+    pub fn rule_names(&self) -> Vec<String> {
+        self.rules.keys().cloned().chain(self.scheduled_default_rule().map(|rule| rule.name.clone())).collect()
+    }
+
     /// Get all rules of the instance in a bundled format where the keys are the cron schedules and the values
     /// are the associated rules which should run in the given schedule
     pub fn get_bundled_rules(&self) -> HashMap<String, Vec<String>> {
         let mut bundles = HashMap::<String, Vec<String>>::new();
-        self.rules.iter().for_each(|(_, rule)| {
+        self.rules.values().chain(self.scheduled_default_rule()).filter(|rule| rule.enabled).for_each(|rule| {
             if let Some(rules) = bundles.get_mut(&rule.schedule) {
                 rules.push(rule.name.clone())
             } else {
@@ -227,84 +654,713 @@ impl Instance {
     /// Apply a given set of rules defined on the instance onto the associated registry. The
     /// rules are referenced by their name <br>
     /// All tags (on repositories) which match at least one of the rules will be deleted and
-    /// additionally the garbage collector inside the registry will be run automatically
-    pub async fn apply_rules(&self, rules: Vec<String>) -> Result<(), Error> {
+    /// additionally the garbage collector inside the registry will be run automatically. When a
+    /// repository is provided the rules are only applied to this repository
+    pub async fn apply_rules(&self, rules: Vec<String>, repository: Option<&str>) -> Result<RunReport, Error> {
+        let start = Instant::now();
+        let mut report = RunReport::new(self.name.clone());
+        let plan = self.plan_rules(rules, repository, &mut report).await?;
+        self.execute_plan(&plan, &mut report).await?;
+        let rules = report.rules.join(", ");
+        let duration = start.elapsed();
+        info!(
+            registry = self.name.as_str(), rules = rules.as_str(), scanned = report.scanned, deleted = report.deleted.len(),
+            freed_bytes = report.freed_bytes(), errors = report.errors.len(), duration_ms = duration.as_millis() as u64, dry_run = self.dry_run;
+            "Summary of run in registry '{}': rules '{rules}', {} repositories scanned, {} tags deleted, ~{} freed, {} errors, took {duration:?}",
+            self.name, report.scanned, report.deleted.len(), format_size(report.freed_bytes()), report.errors.len()
+        );
+        if !report.failed_repositories.is_empty() {
+            warn!(
+                registry = self.name.as_str(), failed = report.failed_repositories.len();
+                "Unable to process {} repositories in registry '{}': '{}'", report.failed_repositories.len(), self.name, report.failed_repositories.join("', '")
+            );
+        }
+        if let Some(url) = &self.webhook {
+            match send_summary(url, &RunSummary::new(&report, self.dry_run)).await {
+                Ok(_) => debug!("Sent summary of run in registry '{}' to webhook", self.name),
+                Err(err) => warn!("Unable to send summary of run in registry '{}' to webhook. Reason: {err}", self.name)
+            }
+        }
+        Ok(report)
+    }
+
+    /// Apply the given rules to the registry or a single repository of it. Should the state require a dry run
+    /// the deletions are only logged. Runs of the same process wait for each other whereas runs are skipped while
+    /// another process holds the lock of the registry
+    pub async fn run_rules(&self, rules: Vec<String>, repository: Option<&str>, state: &StateStore) -> Result<RunReport, Error> {
+        let _lock = match state.lock(&self.name).await {
+            Ok(lock) => lock,
+            Err(LockError::Held(holder)) => {
+                warn!("Registry '{}' is locked by another abwart process ({holder}). Skipping run", self.name);
+                return Ok(RunReport::new(self.name.clone()))
+            },
+            Err(LockError::Unavailable(reason)) => return Err(Error::LockUnavailable(self.name.clone(), reason))
+        };
+        let start = Utc::now();
+        let report = if let Some(reason) = state.dry_run_reason(&self.name) {
+            warn!("{reason}. Only previewing the deletions in registry '{}'", self.name);
+            let mut report = RunReport::new(self.name.clone());
+            self.preview_plan(&self.plan_rules(rules.clone(), repository, &mut report).await?);
+            report
+        } else {
+            self.apply_rules(rules.clone(), repository).await?
+        };
+        // runs of a single pushed repository neither end the first run dry run nor replace a scheduled run of the rules
+        if repository.is_none() {
+            state.mark_run(&self.name);
+            state.mark_rules_run(&self.name, &rules, start);
+        }
+        Ok(report)
+    }
+
+    /// Log all deletions of a cleanup plan without deleting anything
+    pub fn preview_plan(&self, plan: &CleanupPlan) {
+        for repository in &plan.repositories {
+            for tag in &repository.tags {
+                info!("Would delete tag '{}' from repository '{}' in registry '{}' ({})", tag.name, repository.name, self.name, tag.reason);
+            }
+        }
+        let tags = plan.repositories.iter().flat_map(|repo| repo.tags.iter().map(|tag| (repo.name.as_str(), tag.digest.as_str(), tag.name.as_str())));
+        for image in group_aliases(tags, self.representative).into_iter().filter(|image| !image.aliases.is_empty()) {
+            info!("Would delete image '{}:{}' ({}) which is also tagged as '{}' in registry '{}'", image.repository, image.name, image.digest, image.aliases.join("', '"), self.name);
+        }
+        info!("Would delete {} tags from {} repositories in registry '{}'", plan.tag_count(), plan.repositories.len(), self.name);
+    }
+
+    /// Compute all deletions the given rules would make in the registry without deleting anything.
+    /// When a repository is provided only this repository is considered instead of the whole catalog. Errors which
+    /// don't abort the run are recorded in the report
+    pub async fn plan_rules(&self, rules: Vec<String>, repository: Option<&str>, report: &mut RunReport) -> Result<CleanupPlan, Error> {
         debug!("Applying rules to registry '{}'", self.name);
-        let distribution = Distribution::new(Arc::new(self.distribution.clone()));
-        let repositories = distribution.get_repositories().await?;
+        let mut plan = CleanupPlan::new(self.name.clone());
+        // the config artifact is loaded on every run to always use the latest configuration
+        let artifact_rules = self.get_artifact_rules().await;
+        let (default_rule, named_rules) = match &artifact_rules {
+            Some((default_rule, rules)) => (default_rule, rules),
+            None => (&self.default_rule, &self.rules)
+        };
+        let default_rule = self.scheduled_default_rule().map(|_| default_rule);
+        let rules = named_rules.values()
+            .chain(default_rule)
+            .filter(|rule| rules.contains(&rule.name) && rule.enabled)
+            .collect::<Vec<&Rule>>();
+
+        if rules.is_empty() {
+            info!("None of the requested rules are enabled in registry '{}'. Skipping it", self.name);
+            return Ok(plan)
+        }
+        report.rules = rules.iter().map(|rule| rule.name.clone()).collect();
+        report.rules.sort();
+
+        let mut repositories = match repository {
+            Some(repository) => vec![Repository::new(repository.to_string(), Arc::new(self.distribution.clone()))],
+            None => Distribution::new(Arc::new(self.distribution.clone())).get_repositories(self.catalog_prefix.as_deref()).await?
+        };
+        repositories.retain(|repository| !self.is_excluded(&repository.name));
 
         if repositories.is_empty() {
             info!("The registry '{}' doesn't contain any repositories. Skipping it", self.name);
-            return Ok(())
+            return Ok(plan)
         }
 
-        let rules = self.rules.iter()
-            .filter(|(name, _)| rules.contains(name))
-            .map(|(_, rule)| rule)
-            .collect::<Vec<&Rule>>();
+        // the tag count is only loaded when a rule depends on it since it requires listing the tags
+        // of every repository in the registry
+        let repositories = if rules.iter().any(|rule| rule.requires_tag_count()) {
+            let mut counted = Vec::with_capacity(repositories.len());
+            for repository in repositories {
+                let name = repository.name.clone();
+                match repository.with_tag_count().await {
+                    Ok(repository) => counted.push(repository),
+                    Err(err) => self.handle_error(format!("Unable to count tags of repository '{name}'"), Some(&name), err.into(), report)?
+                }
+            }
+            counted
+        } else {
+            repositories
+        };
 
+        // images used by workloads in the kubernetes cluster are resolved on every run
+        let deployed = deployed_images().await;
+        // the pull log is read on every run since it's usually updated by an external process
+        let pulls = match &self.pull_log {
+            Some(path) => match PullLog::read(path) {
+                Ok(log) => Some(log),
+                Err(err) => {
+                    self.handle_error(format!("Unable to read pull log '{path}'"), None, err, report)?;
+                    None
+                }
+            },
+            None => None
+        };
         let mut tag_cache = HashMap::new();
+        let mut failed_repositories = HashSet::new();
+        let mut budget = self.max_delete_bytes.map(DeleteBudget::new);
 
-        let mut affected_repositories = HashSet::new();
-        let mut deleted_tags = 0;
-        let mut tidy = false;
-        for rule in rules {
+        'rules: for rule in rules {
             let repositories = rule.affected_repositories(repositories.clone());
-            affected_repositories.extend(repositories.iter().map(|r| r.name.clone()));
             for repository in repositories {
-                let tags = tag_cache.entry(repository.name.clone()).or_insert(repository.get_tags_with_data().await?);
-                let affected_tags = rule.affected_tags(tags.clone());
-                for tag in &affected_tags {
-                    info!("Deleting tag '{}' from repository '{}' in registry '{}'", tag.name, repository.name, self.name);
-                    repository.delete_manifest(&tag.digest).await?;
-                    deleted_tags += 1;
-                    if rule.tidy.is_some_and(|val| val) {
-                        tidy = true
+                if budget.as_ref().is_some_and(|budget| budget.is_exhausted()) {
+                    break 'rules
+                }
+                if failed_repositories.contains(&repository.name) {
+                    continue
+                }
+                if !tag_cache.contains_key(&repository.name) {
+                    match repository.get_tags_with_data().await {
+                        Ok(tags) => {
+                            let tags = match &pulls {
+                                Some(pulls) => pulls.annotate(&repository.name, tags),
+                                None => tags
+                            };
+                            tag_cache.insert(repository.name.clone(), tags);
+                        },
+                        Err(err) => {
+                            self.handle_error(format!("Unable to get tags of repository '{}'", repository.name), Some(&repository.name), err.into(), report)?;
+                            failed_repositories.insert(repository.name.clone());
+                            continue
+                        }
+                    }
+                }
+                let tags = tag_cache.get_mut(&repository.name).expect("Tags should be cached");
+                let protected = self.protected_digests(tags, &repository.name, &deployed);
+                let mut affected_tags = rule.affected_tags_explained(tags.clone()).into_iter()
+                    .filter(|(tag, _)| !protected.contains(&tag.digest))
+                    .collect::<Vec<_>>();
+                // the oldest tags are planned first to get a stable selection within the budget and the deletion limit
+                affected_tags.sort_by(|(a, _), (b, _)| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+                if let Some(budget) = &mut budget {
+                    affected_tags.retain(|(tag, _)| budget.admit(tag));
+                    if budget.is_exhausted() {
+                        info!("Reached deletion budget of registry '{}' after selecting {} bytes. Skipping remaining tags", self.name, budget.used())
+                    }
+                }
+                if affected_tags.is_empty() {
+                    continue
+                }
+                if rule.tidy.is_some_and(|val| val) {
+                    plan.tidy = true
+                }
+                for (tag, explanation) in &affected_tags {
+                    plan.add(&repository.name, tag, Some(&rule.name), format!("rule '{}': {explanation}", rule.name));
+                }
+                // tags which are already planned for deletion aren't visible to the following rules
+                tags.retain(|tag| !affected_tags.iter().any(|(affected, _)| affected == tag))
+            }
+        }
+
+        if let Some(path) = &self.scan_report {
+            self.plan_scan_report(path, repository, &deployed, &mut plan, &mut tag_cache, report).await?;
+        }
+        if let Some(max) = self.guard_max_fraction {
+            self.guard_plan(max, &mut plan, &tag_cache);
+        }
+        report.scanned = tag_cache.len();
+
+        Ok(plan)
+    }
+
+    /// Remove all repositories from the plan which would lose more than the given fraction of their tags. The cached
+    /// tags are the tags which remain in the repository after the planned deletions
+    fn guard_plan(&self, max: f64, plan: &mut CleanupPlan, tag_cache: &HashMap<String, Vec<Tag>>) {
+        plan.repositories.retain(|planned| {
+            let total = planned.tags.len() + tag_cache.get(&planned.name).map(Vec::len).unwrap_or_default();
+            let fraction = planned.tags.len() as f64 / total.max(1) as f64;
+            if fraction > max {
+                warn!(
+                    registry = self.name.as_str(), repository = planned.name.as_str(), planned = planned.tags.len(), total = total;
+                    "Refusing to delete {} of {total} tags ({:.0}%) from repository '{}' in registry '{}' which exceeds the maximum fraction of {:.0}%. Skipping repository",
+                    planned.tags.len(), fraction * 100.0, planned.name, self.name, max * 100.0
+                );
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Add all images flagged by a scan report to the plan independent of the policies. Flagged images which
+    /// don't exist (anymore) are skipped and images used by workloads in the kubernetes cluster are kept
+    async fn plan_scan_report(&self, path: &str, repository: Option<&str>, deployed: &DeployedImages, plan: &mut CleanupPlan, tag_cache: &mut HashMap<String, Vec<Tag>>, report: &mut RunReport) -> Result<(), Error> {
+        let entries = match std::fs::read_to_string(path).map_err(|err| Error::InvalidScanReport(err.to_string())).and_then(|content| parse_scan_report(&content)) {
+            Ok(entries) => entries,
+            Err(err) => return self.handle_error(format!("Unable to read scan report '{path}'"), None, err, report)
+        };
+        let config = Arc::new(self.distribution.clone());
+        for entry in entries.into_iter().filter(|entry| repository.is_none_or(|repository| repository == entry.repository) && !self.is_excluded(&entry.repository)) {
+            if !tag_cache.contains_key(&entry.repository) {
+                let tags = match Repository::new(entry.repository.clone(), config.clone()).get_tags_with_data().await {
+                    Ok(tags) => tags,
+                    Err(ApiError::NotFound(_)) => vec![],
+                    Err(err) => {
+                        self.handle_error(format!("Unable to get tags of repository '{}'", entry.repository), Some(&entry.repository), err.into(), report)?;
+                        vec![]
                     }
+                };
+                tag_cache.insert(entry.repository.clone(), tags);
+            }
+            let tags = tag_cache.get_mut(&entry.repository).expect("Tags should be cached");
+            let protected = self.protected_digests(tags, &entry.repository, deployed);
+            let flagged = tags.iter()
+                .filter(|tag| match &entry.reference {
+                    ScanReference::Tag(name) => &tag.name == name,
+                    ScanReference::Digest(digest) => &tag.digest == digest
+                })
+                .filter(|tag| !protected.contains(&tag.digest))
+                .cloned()
+                .collect::<Vec<_>>();
+            if flagged.is_empty() {
+                info!("Image '{entry}' flagged by the scan report doesn't exist in registry '{}'. Skipping it", self.name);
+                continue
+            }
+            for tag in &flagged {
+                plan.add(&entry.repository, tag, None, format!("scan report '{path}'"));
+            }
+            tags.retain(|tag| !flagged.contains(tag));
+        }
+        Ok(())
+    }
+
+    /// Delete all tags of a reviewed cleanup plan from the registry. Since the registry may have changed since the plan was
+    /// created, planned tags which no longer exist, point to another digest or became protected are skipped. Like
+    /// [`Instance::run_rules`] the plan isn't applied while another process holds the lock of the registry
+    pub async fn apply_plan(&self, plan: &CleanupPlan, state: &StateStore, report: &mut RunReport) -> Result<(), Error> {
+        let _lock = state.lock(&self.name).await.map_err(|err| match err {
+            LockError::Held(holder) => Error::RegistryLocked(self.name.clone(), holder),
+            LockError::Unavailable(reason) => Error::LockUnavailable(self.name.clone(), reason)
+        })?;
+        let plan = self.revalidate_plan(plan, report).await?;
+        self.execute_plan(&plan, report).await
+    }
+
+    /// Remove all tags from the plan which don't point to the planned digest anymore or which are protected by the
+    /// instance, by a workload in the kubernetes cluster or by the requirement policies of the rule which planned them
+    async fn revalidate_plan(&self, plan: &CleanupPlan, report: &mut RunReport) -> Result<CleanupPlan, Error> {
+        let config = Arc::new(self.distribution.clone());
+        let artifact_rules = self.get_artifact_rules().await;
+        let (default_rule, named_rules) = match &artifact_rules {
+            Some((default_rule, rules)) => (default_rule, rules),
+            None => (&self.default_rule, &self.rules)
+        };
+        let deployed = deployed_images().await;
+        let mut validated = CleanupPlan { repositories: vec![], ..plan.clone() };
+        for planned in &plan.repositories {
+            let tags = match Repository::new(planned.name.clone(), config.clone()).get_tags_with_data().await {
+                Ok(tags) => tags,
+                Err(err) => {
+                    self.handle_error(format!("Unable to get tags of repository '{}'", planned.name), Some(&planned.name), err.into(), report)?;
+                    continue
+                }
+            };
+            let protected = self.protected_digests(&tags, &planned.name, &deployed);
+            let mut rule_protected = HashMap::new();
+            let mut remaining = vec![];
+            for tag in &planned.tags {
+                let Some(current) = tags.iter().find(|current| current.name == tag.name) else {
+                    info!("Planned tag '{}' doesn't exist in repository '{}' in registry '{}' anymore. Skipping tag", tag.name, planned.name, self.name);
+                    continue
+                };
+                if current.digest != tag.digest {
+                    warn!(
+                        "Planned tag '{}' in repository '{}' in registry '{}' points to '{}' instead of the planned digest '{}'. Skipping tag",
+                        tag.name, planned.name, self.name, current.digest, tag.digest
+                    );
+                    continue
                 }
-                if !affected_tags.is_empty() {
-                    tags.retain(|tag| !affected_tags.contains(tag))
+                let rule = tag.rule.as_ref().and_then(|name| named_rules.get(name).or(Some(default_rule).filter(|rule| &rule.name == name)));
+                let kept = rule.is_some_and(|rule| rule_protected.entry(rule.name.clone()).or_insert_with(|| rule.protected_digests(&tags)).contains(&tag.digest));
+                if kept || protected.contains(&tag.digest) {
+                    warn!("Planned tag '{}' in repository '{}' in registry '{}' is protected. Skipping tag", tag.name, planned.name, self.name);
+                    continue
                 }
+                remaining.push(tag.clone());
             }
+            validated.repositories.push(RepositoryPlan { name: planned.name.clone(), tags: remaining });
         }
+        Ok(validated)
+    }
 
-        if deleted_tags == 0 {
-            info!("Left all repositories in registry '{}' unmodified", self.name)
+    /// Delete all tags of a cleanup plan from the registry
+    pub async fn execute_plan(&self, plan: &CleanupPlan, report: &mut RunReport) -> Result<(), Error> {
+        let config = Arc::new(self.distribution.clone());
+        let deleted_before = report.deleted.len();
+        let tidy = plan.tidy && self.garbage_collector.enabled;
+        let reclaim = tidy && !self.dry_run && self.garbage_collector.mode == GarbageCollectorMode::Api;
+        let mut reclaimable = vec![];
+        let mut remaining = self.max_deletions;
+        let mut skipped = 0;
+        let mut previewed = CleanupPlan::new(self.name.clone());
+        for planned in &plan.repositories {
+            let repository = Repository::new(planned.name.clone(), config.clone());
+            // the planned tags are cut off once the deletion limit is reached
+            let tags = match remaining {
+                Some(limit) => {
+                    let count = limit.min(planned.tags.len());
+                    remaining = Some(limit - count);
+                    skipped += planned.tags.len() - count;
+                    &planned.tags[..count]
+                },
+                None => &planned.tags[..]
+            };
+            if self.dry_run {
+                for tag in tags {
+                    info!(
+                        registry = self.name.as_str(), repository = repository.name.as_str(), tag = tag.name.as_str(), digest = tag.digest.as_str(), dry_run = true;
+                        "[dry-run] Would delete tag '{}' from repository '{}' in registry '{}' ({})", tag.name, repository.name, self.name, tag.reason
+                    );
+                }
+                previewed.repositories.push(RepositoryPlan { name: planned.name.clone(), tags: tags.to_vec() });
+                continue
+            }
+            // the tags of a repository are deleted concurrently but their outcomes are recorded in the order of the plan
+            let mut candidates = HashSet::new();
+            let repository_ref = &repository;
+            let mut deletions = stream::iter(tags.iter().cloned())
+                .map(|tag| async move {
+                    let result = self.delete_tag(repository_ref, &tag, reclaim).await;
+                    (tag, result)
+                })
+                .buffered(self.distribution.concurrency.max(1));
+            while let Some((tag, result)) = deletions.next().await {
+                match result {
+                    Ok(Some(blobs)) => {
+                        report.record_deletion(&repository.name, &tag);
+                        candidates.extend(blobs);
+                    },
+                    Ok(None) => {},
+                    Err(err) => self.handle_error(format!("Unable to delete tag '{}' from repository '{}'", tag.name, repository.name), Some(&repository.name), err.into(), report)?
+                }
+            }
+            if !candidates.is_empty() {
+                reclaimable.push((repository.clone(), candidates));
+            }
+        }
+
+        if skipped > 0 {
+            warn!(
+                registry = self.name.as_str(), skipped = skipped;
+                "Reached the deletion limit of {} tags in registry '{}'. Skipped {skipped} planned deletions and the garbage collector",
+                self.max_deletions.unwrap_or_default(), self.name
+            );
+        }
+
+        if self.dry_run {
+            info!("[dry-run] Would delete {} tags from {} repositories in registry '{}'", plan.tag_count() - skipped, plan.repositories.len(), self.name);
+            if let Some(path) = &self.dry_run_output {
+                self.write_dry_run_output(path, &previewed);
+            }
+            if tidy && plan.tag_count() > 0 && skipped == 0 {
+                self.collect_garbage().await;
+            }
+        } else if report.deleted.len() == deleted_before {
+            info!(registry = self.name.as_str(); "Left all repositories in registry '{}' unmodified", self.name)
         } else {
-            info!("Deleted {deleted_tags} tags from {} repositories in registry '{}'", affected_repositories.len(), self.name);
-            if tidy {
-                info!("Running post deletion cleanup in registry '{}'", self.name);
-                self.run_garbage_collector().await;
+            let (deleted, repositories) = (report.deleted.len() - deleted_before, report.affected_repositories());
+            info!(registry = self.name.as_str(), deleted = deleted, repositories = repositories; "Deleted {deleted} tags from {repositories} repositories in registry '{}'", self.name);
+            for image in report.deleted_images(self.representative).into_iter().filter(|image| !image.aliases.is_empty()) {
+                info!("Deleted image '{}:{}' ({}) which was also tagged as '{}' in registry '{}'", image.repository, image.name, image.digest, image.aliases.join("', '"), self.name);
+            }
+            if skipped > 0 {
+                debug!("Skipping garbage collector in registry '{}' since the deletion limit was reached", self.name)
+            } else if reclaim {
+                self.reclaim_blobs(&reclaimable).await;
+            } else if tidy {
+                self.collect_garbage().await;
+            } else if plan.tidy {
+                info!("Skipping garbage collector in registry '{}' since it's disabled", self.name)
             }
         }
 
         Ok(())
     }
 
-    /// Exec the `registry garbage-collect` utility in the registry container to clean up dangling blobs
-    pub async fn run_garbage_collector(&self) {
-        debug!("Running garbage collector in registry '{}'", self.name);
-        let exec = self.client.create_exec(self.id.as_str(), CreateExecOptions::<&str>{
-            cmd: Some(vec!["/bin/registry", "garbage-collect", "--delete-untagged", "/etc/docker/registry/config.yml"]),
-            user: Some("root"),
-            ..CreateExecOptions::default()
-        }).await;
+    /// Write the deletions of a dry run as [`DryRunOutput`] to the given path or to stdout for `-`. Failures are only logged
+    /// since they don't affect the registry
+    fn write_dry_run_output(&self, path: &str, plan: &CleanupPlan) {
+        let content = match DryRunOutput::new(plan, Utc::now()).to_json() {
+            Ok(content) => content,
+            Err(err) => return warn!("Unable to serialize dry run output of registry '{}'. Reason: {err}", self.name)
+        };
+        if path == "-" {
+            if let Err(err) = writeln!(std::io::stdout().lock(), "{content}") {
+                warn!("Unable to write dry run output of registry '{}' to stdout. Reason: {err}", self.name)
+            }
+        } else if let Err(err) = std::fs::write(path, content) {
+            warn!("Unable to write dry run output of registry '{}' to '{path}'. Reason: {err}", self.name)
+        } else {
+            debug!("Wrote dry run output of registry '{}' to '{path}'", self.name)
+        }
+    }
+
+    /// Delete a single planned tag from a repository. <br>
+    /// Returns the blobs of the tag which can be reclaimed or `None` when the tag was skipped since it's immutable
+    /// or was already deleted
+    async fn delete_tag(&self, repository: &Repository, tag: &PlannedTag, reclaim: bool) -> Result<Option<HashSet<String>>, ApiError> {
+        info!(
+            registry = self.name.as_str(), repository = repository.name.as_str(), tag = tag.name.as_str(), digest = tag.digest.as_str(), reason = tag.reason.as_str();
+            "Deleting tag '{}' from repository '{}' in registry '{}' ({})", tag.name, repository.name, self.name, tag.reason
+        );
+        // the blobs of a manifest can only be read before it's deleted
+        let blobs = if reclaim {
+            repository.get_blobs(&tag.digest).await.unwrap_or_else(|err| {
+                warn!("Unable to get the blobs of tag '{}' from repository '{}' in registry '{}'. Its blobs aren't reclaimed. Reason: {err}", tag.name, repository.name, self.name);
+                HashSet::new()
+            })
+        } else {
+            HashSet::new()
+        };
+        match repository.delete_manifest(&tag.digest).await {
+            Ok(_) => {},
+            Err(ApiError::ImmutableManifest(reason)) => {
+                warn!("Registry '{}' refused to delete immutable tag '{}' from repository '{}'. Skipping tag. Reason: {reason}", self.name, tag.name, repository.name);
+                return Ok(None)
+            },
+            Err(ApiError::NotFound(_)) => {
+                // the manifest was deleted concurrently (e.g. by another run or through an alias of the tag)
+                info!("Tag '{}' was already deleted from repository '{}' in registry '{}'. Skipping tag", tag.name, repository.name, self.name);
+                return Ok(None)
+            },
+            Err(err) => return Err(err)
+        }
+        if self.verify_deletions {
+            match repository.verify_deleted(&tag.digest, DELETE_VERIFY_ATTEMPTS, DELETE_VERIFY_BACKOFF).await {
+                Ok(true) => debug!("Verified deletion of tag '{}' from repository '{}' in registry '{}'", tag.name, repository.name, self.name),
+                Ok(false) => warn!("Tag '{}' is still present in repository '{}' in registry '{}' after its deletion", tag.name, repository.name, self.name),
+                Err(err) => warn!("Unable to verify deletion of tag '{}' from repository '{}' in registry '{}'. Reason: {err}", tag.name, repository.name, self.name)
+            }
+        }
+        Ok(Some(blobs))
+    }
 
-        match exec {
-            Ok(exec) => {
-                match self.client.start_exec(exec.id.as_str(), None::<StartExecOptions>).await {
-                    Ok(_) => info!("Successfully ran garbage collector in registry '{}'", self.name),
-                    Err(err) => error!("Unable to run garbage collector in registry '{}'. Reason: {err}", self.name)
+    /// Handle an error which occurred whilst applying rules according to the `on-error` strategy of the instance.
+    /// The repository the error occurred in is recorded as failed. Returns the error when the run should be aborted
+    fn handle_error(&self, context: String, repository: Option<&str>, err: Error, report: &mut RunReport) -> Result<(), Error> {
+        match self.on_error {
+            ErrorStrategy::Abort => Err(err),
+            ErrorStrategy::Continue => {
+                error!("{context} in registry '{}'. Continuing with the remaining rules. Reason: {err}", self.name);
+                match repository {
+                    Some(repository) => report.record_repository_error(repository, format!("{context}: {err}")),
+                    None => report.record_error(format!("{context}: {err}"))
                 }
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the digests of all protected tags of a repository including the ones used by workloads in the kubernetes cluster. Since
+    /// tags are deleted by their digest, a tag sharing its digest with a protected tag can't be deleted without deleting the protected tag as well
+    fn protected_digests(&self, tags: &[Tag], repository: &str, deployed: &DeployedImages) -> HashSet<String> {
+        let mut digests = deployed.protected_digests(repository, tags);
+        digests.extend(tags.iter().filter(|tag| self.is_protected(tag, repository)).map(|tag| tag.digest.clone()));
+        digests
+    }
+
+    /// Whether a tag is never deleted independent of the rules since it's immutable or was created within
+    /// the `min-age-absolute` of the instance
+    fn is_protected(&self, tag: &Tag, repository: &str) -> bool {
+        self.is_immutable(tag, repository) || self.is_recent(tag, repository)
+    }
+
+    /// Whether a tag was created within the `min-age-absolute` of the instance. This protects tags which were
+    /// pushed whilst a run is in progress
+    fn is_recent(&self, tag: &Tag, repository: &str) -> bool {
+        let recent = self.min_age_absolute.is_some_and(|age| tag.created + age > Utc::now());
+        if recent {
+            debug!("Keeping recently created tag '{}' in repository '{repository}' in registry '{}'", tag.name, self.name);
+        }
+        recent
+    }
+
+    /// Whether a repository is filtered out by the `repositories.include` and `repositories.exclude` patterns of the instance
+    fn is_excluded(&self, repository: &str) -> bool {
+        let included = self.repositories_include.is_empty() || self.repositories_include.iter().any(|pattern| pattern.is_match(repository));
+        let excluded = !included || self.repositories_exclude.iter().any(|pattern| pattern.is_match(repository));
+        if excluded {
+            debug!("Skipping excluded repository '{repository}' in registry '{}'", self.name);
+        }
+        excluded
+    }
+
+    /// Whether a tag matches the `immutable-pattern` of the instance and is therefore never deleted
+    fn is_immutable(&self, tag: &Tag, repository: &str) -> bool {
+        let immutable = self.immutable_pattern.as_ref().is_some_and(|pattern| pattern.is_match(&tag.name));
+        if immutable {
+            debug!("Keeping immutable tag '{}' in repository '{repository}' in registry '{}'", tag.name, self.name);
+        }
+        immutable
+    }
+
+    /// Run the garbage collector once the deletions had time to propagate according to the `gc.delay` of the
+    /// instance. Returns whether the garbage collector succeeded
+    pub async fn collect_garbage(&self) -> bool {
+        let delay = self.garbage_collector.delay;
+        if !delay.is_zero() && !self.dry_run {
+            info!("Waiting {delay:?} for the deletions to propagate in registry '{}'", self.name);
+            sleep(delay).await
+        }
+        info!("Running post deletion cleanup in registry '{}'", self.name);
+        self.run_garbage_collector().await
+    }
+
+    /// Delete the blobs of the deleted tags which aren't referenced anymore using the registry api
+    async fn reclaim_blobs(&self, reclaimable: &[(Repository, HashSet<String>)]) {
+        info!("Reclaiming unreferenced blobs in registry '{}'", self.name);
+        for (repository, candidates) in reclaimable {
+            match reclaim_blobs(repository, candidates).await {
+                Ok(count) => info!("Deleted {count} unreferenced blobs from repository '{}' in registry '{}'", repository.name, self.name),
+                Err(err) => warn!("Unable to reclaim the blobs of repository '{}' in registry '{}'. Reason: {err}", repository.name, self.name)
+            }
+        }
+    }
+
+    /// Check whether the registry is reachable using the api and log the result. <br>
+    /// Returns whether the registry is reachable
+    pub async fn probe(&self) -> bool {
+        let (registry, host) = (self.name.as_str(), self.distribution.host.as_str());
+        match Distribution::new(Arc::new(self.distribution.clone())).ping().await {
+            Ok(_) => {
+                info!(registry = registry, host = host; "Registry '{registry}' is reachable at '{host}'");
+                true
             },
-            Err(err) => error!("Unable to create new exec in registry '{}'. Reason: {err}", self.name)
+            Err(ApiError::RequestError(err)) if err.is_connect() => {
+                warn!(registry = registry, host = host; "Registry '{registry}' is unreachable at '{host}'. Check the network and port of the registry. Reason: {err}");
+                false
+            },
+            Err(err) => {
+                warn!(registry = registry, host = host; "Registry '{registry}' at '{host}' failed the connectivity probe. Reason: {err}");
+                false
+            }
+        }
+    }
+
+    /// Exec the `registry garbage-collect` utility in the registry container to clean up dangling blobs. Failed
+    /// or timed out runs are retried with an exponential backoff as configured in the `gc.*` labels <br>
+    /// Returns whether the garbage collector ran successfully
+    pub async fn run_garbage_collector(&self) -> bool {
+        if self.dry_run {
+            info!("[dry-run] Would run garbage collector in registry '{}'", self.name);
+            return true
+        }
+        if self.garbage_collector.mode == GarbageCollectorMode::Api {
+            info!("Registry '{}' reclaims blobs using the api which only happens after deleting tags. Skipping garbage collector", self.name);
+            return true
+        }
+        if self.standalone {
+            warn!("Registry '{}' doesn't run in a container the garbage collector could be executed in. Use 'gc.mode=api' to reclaim blobs instead", self.name);
+            return false
         }
+        let config = &self.garbage_collector;
+        let attempts = config.retries + 1;
+        let mut backoff = config.backoff;
+        for attempt in 1..=attempts {
+            debug!("Running garbage collector in registry '{}' (attempt {attempt}/{attempts})", self.name);
+            let result = timeout(config.timeout, exec_garbage_collector(&self.client, &self.id, &config.command()))
+                .await
+                .unwrap_or(Err(Error::GarbageCollectorTimeout(config.timeout)));
+            match result {
+                Ok(_) => {
+                    info!("Successfully ran garbage collector in registry '{}' (attempt {attempt}/{attempts})", self.name);
+                    return true
+                },
+                Err(err) if attempt < attempts => {
+                    warn!("Garbage collector in registry '{}' failed (attempt {attempt}/{attempts}). Retrying in {backoff:?}. Reason: {err}", self.name);
+                    sleep(backoff).await;
+                    backoff *= 2;
+                },
+                Err(err) => error!("Unable to run garbage collector in registry '{}' after {attempts} attempts. Reason: {err}", self.name)
+            }
+        }
+        false
+    }
+}
+
+/// Read a credential from the file referenced by a label. Trailing newlines of the file are removed
+fn read_secret_label(labels: &HashMap<String, String>, name: &str) -> Option<String> {
+    let path = labels.get(&label(name))?;
+    match std::fs::read_to_string(path) {
+        Ok(content) => Some(content.trim_end_matches(['\r', '\n']).to_string()),
+        Err(err) => {
+            warn!("Unable to read '{name}' at '{path}'. Using none instead. Reason: {err}");
+            None
+        }
+    }
+}
+
+/// Parse a comma-separated list of regex patterns. Invalid patterns are skipped with a warning
+fn parse_patterns(value: &str, kind: &str) -> Vec<Regex> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(err) => {
+                warn!("Received invalid {kind} pattern '{pattern}'. Skipping it. Reason: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse an artifact reference in the `<repository>[:<tag>|@<digest>]` format into its repository and reference. Should the
+/// reference be missing the `latest` tag is used
+fn parse_artifact_reference(value: &str) -> Option<(String, String)> {
+    let value = value.trim();
+    let (repository, reference) = if let Some((repository, digest)) = value.split_once('@') {
+        (repository, digest)
+    } else {
+        match value.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+            _ => (value, "latest")
+        }
+    };
+    if repository.is_empty() || reference.is_empty() {
+        None
+    } else {
+        Some((repository.to_string(), reference.to_string()))
+    }
+}
+
+/// Select the network of a container deterministically. Networks with an ip address are preferred over networks
+/// without one and ties are resolved by the name of the network
+fn select_network(networks: &HashMap<String, EndpointSettings>) -> Option<String> {
+    networks.iter()
+        .min_by_key(|(name, settings)| (endpoint_address(settings).is_none(), name.as_str()))
+        .map(|(name, _)| name.clone())
+}
+
+/// Get the address of a container in a network. The ipv4 address is preferred over the global ipv6 address which
+/// is the only address in ipv6-only networks
+fn endpoint_address(settings: &EndpointSettings) -> Option<String> {
+    [&settings.ip_address, &settings.global_ipv6_address].into_iter()
+        .flatten()
+        .find(|address| !address.is_empty())
+        .cloned()
+}
+
+/// Combine an address and a port to a host. Ipv6 addresses are wrapped in brackets (e.g. `[fd00::2]:5000`)
+fn format_host(address: &str, port: u16) -> String {
+    match address.parse::<Ipv6Addr>() {
+        Ok(address) => format!("[{address}]:{port}"),
+        Err(_) => format!("{address}:{port}")
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::instance::Instance;
+    use std::collections::HashMap;
+    use bollard::secret::EndpointSettings;
+    use chrono::{Duration, Utc};
+    use crate::api::tag::Tag;
+    use crate::error::Error;
+    use crate::instance::{ErrorStrategy, format_host, Instance, parse_artifact_reference, select_network};
+    use crate::policies::age_max::AGE_MAX_LABEL;
+    use crate::policies::age_min::AGE_MIN_LABEL;
+    use crate::policies::revision::REVISION_LABEL;
+    use crate::policies::tag_pattern::TAG_PATTERN_LABEL;
+    use crate::plan::CleanupPlan;
+    use crate::report::{Representative, RunReport};
+    use crate::state::StateStore;
+    use crate::test::{get_docker_client, get_state_path, get_mock_docker_instance, get_mock_instance, mock_catalog, mock_delete, mock_manifest, mock_tag_list};
 
     #[test]
     fn test_rule_pattern() {
@@ -315,6 +1371,1003 @@ mod test {
     fn test_default_rule_pattern() {
         Instance::get_default_rule_pattern();
     }
-}
 
+    #[test]
+    fn test_hyphenated_policy_name() {
+        let labels = HashMap::from([(String::from("abwart.rule.bloated.repository.min-tags"), String::from("100"))]);
+        let (_, rules) = Instance::parse_rules("id", &labels);
+        assert!(rules.get("bloated").is_some_and(|rule| rule.requires_tag_count()));
+    }
+
+    #[test]
+    fn test_disabled_default_policy() {
+        let labels = HashMap::from([
+            (String::from("abwart.default.revisions"), String::from("off")),
+            (String::from("abwart.default.age.max"), String::from("Disabled")),
+            (String::from("abwart.rule.nightly.tag.pattern"), String::from("nightly-.+")),
+            (String::from("abwart.rule.releases.revisions"), String::from("5"))
+        ]);
+        let (default_rule, mut rules) = Instance::parse_rules("id", &labels);
+        Instance::apply_defaults(&default_rule, &mut rules);
+        assert!(!default_rule.tag_policies.contains_key(REVISION_LABEL));
+        assert!(!default_rule.tag_policies.contains_key(AGE_MAX_LABEL));
+        assert!(default_rule.tag_policies.contains_key(AGE_MIN_LABEL));
+        assert!(!rules["nightly"].tag_policies.contains_key(REVISION_LABEL));
+        assert!(!rules["nightly"].tag_policies.contains_key(AGE_MAX_LABEL));
+        assert!(rules["releases"].tag_policies.contains_key(REVISION_LABEL));
+    }
+
+    #[tokio::test]
+    async fn test_immutable_pattern() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "dev"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 0).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("immutable-pattern", "^v\\d+"), ("rule.all.tag.pattern", ".*")]);
+        assert!(instance.apply_rules(vec![String::from("all")], None).await.is_ok());
+        delete_v1.assert_async().await;
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_immutable_aliases() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "dev", "old"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:v1", created, 10).await;
+        let _old = mock_manifest(&mut server, "app", "old", "sha256:old", created, 10).await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 0).await;
+        let delete_old = mock_delete(&mut server, "app", "sha256:old", 202, "", 1).await;
+
+        // `dev` shares the digest of the immutable `v1` tag and can't be deleted without deleting `v1`
+        let instance = get_mock_instance(&server, vec![("immutable-pattern", "^v\\d+"), ("rule.all.tag.pattern", ".*")]);
+        assert!(instance.apply_rules(vec![String::from("all")], None).await.is_ok());
+        delete_v1.assert_async().await;
+        delete_old.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_repository_filter() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app", "api", "system/cache", "legacy"]).await;
+        let app_tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let api_tags = mock_tag_list(&mut server, "api", vec!["dev"]).await;
+        let system_tags = mock_tag_list(&mut server, "system/cache", vec!["dev"]).await;
+        let legacy_tags = mock_tag_list(&mut server, "legacy", vec!["dev"]).await;
+        let _app = mock_manifest(&mut server, "app", "dev", "sha256:app", created, 10).await;
+        let _api = mock_manifest(&mut server, "api", "dev", "sha256:api", created, 10).await;
+        let delete_system = mock_delete(&mut server, "system/cache", "sha256:system", 202, "", 0).await;
+
+        let instance = get_mock_instance(&server, vec![
+            ("repositories.include", "^a, ^system/"),
+            ("repositories.exclude", "^system/,["),
+            ("rule.dev.tag.pattern", "dev")
+        ]);
+        assert_eq!(instance.repositories_include.len(), 2);
+        assert_eq!(instance.repositories_exclude.len(), 1);
+        let mut plan = instance.plan_rules(vec![String::from("dev")], None, &mut RunReport::default()).await.unwrap();
+        plan.sort();
+        assert_eq!(plan.repositories.iter().map(|repository| repository.name.as_str()).collect::<Vec<_>>(), vec!["api", "app"]);
+
+        // excluded repositories are skipped when they're requested explicitly as well
+        let plan = instance.plan_rules(vec![String::from("dev")], Some("system/cache"), &mut RunReport::default()).await.unwrap();
+        assert!(plan.repositories.is_empty());
+        app_tags.assert_async().await;
+        api_tags.assert_async().await;
+        assert!(!system_tags.matched_async().await);
+        assert!(!legacy_tags.matched_async().await);
+        delete_system.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_plan_rules() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "dev"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 0).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("rule.dev.tidy", "true")]);
+        let plan = instance.plan_rules(vec![String::from("dev")], None, &mut RunReport::default()).await.unwrap();
+        assert_eq!(plan.registry, "test-registry");
+        assert!(plan.tidy);
+        assert_eq!(plan.repositories.len(), 1);
+        assert_eq!(plan.repositories[0].name, "app");
+        assert_eq!(plan.repositories[0].tags.len(), 1);
+        assert_eq!(plan.repositories[0].tags[0].digest, "sha256:dev");
+        assert_eq!(plan.repositories[0].tags[0].reason, "rule 'dev': tag.pattern: matches 'dev'");
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_budget() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "latest", "v2", "v3"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", Utc::now() - Duration::days(4), 400).await;
+        let _latest = mock_manifest(&mut server, "app", "latest", "sha256:v1", Utc::now() - Duration::days(4), 400).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", Utc::now() - Duration::days(3), 400).await;
+        let _v3 = mock_manifest(&mut server, "app", "v3", "sha256:v3", Utc::now() - Duration::days(2), 400).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.old.age.max", "1d"), ("max-delete-bytes", "1 KiB")]);
+        assert_eq!(instance.max_delete_bytes, Some(1024));
+        let plan = instance.plan_rules(vec![String::from("old")], None, &mut RunReport::default()).await.unwrap();
+        let mut planned = plan.repositories[0].tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        planned.sort();
+        assert_eq!(planned, vec!["latest", "v1", "v2"]);
+    }
+
+    #[tokio::test]
+    async fn test_max_deletions() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2", "v3"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", Utc::now() - Duration::days(4), 10).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", Utc::now() - Duration::days(3), 10).await;
+        let _v3 = mock_manifest(&mut server, "app", "v3", "sha256:v3", Utc::now() - Duration::days(2), 10).await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 1).await;
+        let delete_v2 = mock_delete(&mut server, "app", "sha256:v2", 202, "", 1).await;
+        let delete_v3 = mock_delete(&mut server, "app", "sha256:v3", 202, "", 0).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.old.age.max", "1d"), ("max_deletions", "2")]);
+        assert_eq!(instance.max_deletions, Some(2));
+        let report = instance.apply_rules(vec![String::from("old")], None).await.unwrap();
+        assert_eq!(report.deleted.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(), vec!["v1", "v2"]);
+        delete_v1.assert_async().await;
+        delete_v2.assert_async().await;
+        delete_v3.assert_async().await;
+
+        assert_eq!(get_mock_instance(&server, vec![("max_deletions", "0")]).max_deletions, None);
+        assert_eq!(get_mock_instance(&server, vec![("max_deletions", "many")]).max_deletions, None);
+    }
+
+    #[tokio::test]
+    async fn test_guard_max_fraction() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app", "api"]).await;
+        let _app_tags = mock_tag_list(&mut server, "app", vec!["v1", "v2", "v3", "v4"]).await;
+        let _api_tags = mock_tag_list(&mut server, "api", vec!["v1", "v2"]).await;
+        for (repository, tag, age) in [("app", "v1", 4), ("app", "v2", 3), ("app", "v3", 2), ("app", "v4", 0), ("api", "v1", 4), ("api", "v2", 0)] {
+            mock_manifest(&mut server, repository, tag, &format!("sha256:{repository}-{tag}"), Utc::now() - Duration::days(age), 10).await;
+        }
+
+        let instance = get_mock_instance(&server, vec![("rule.old.age.max", "1d"), ("rule.old.tag.pattern", "^none$"), ("guard.max_fraction", "0.5")]);
+        assert_eq!(instance.guard_max_fraction, Some(0.5));
+        let plan = instance.plan_rules(vec![String::from("old")], None, &mut RunReport::default()).await.unwrap();
+        assert_eq!(plan.repositories.len(), 1);
+        assert_eq!(plan.repositories[0].name, "api");
+
+        let instance = get_mock_instance(&server, vec![("rule.old.age.max", "1d"), ("rule.old.tag.pattern", "^none$"), ("guard.max_fraction", "0.8")]);
+        let plan = instance.plan_rules(vec![String::from("old")], None, &mut RunReport::default()).await.unwrap();
+        assert_eq!(plan.tag_count(), 4);
+
+        for invalid in ["0", "1.5", "most"] {
+            assert_eq!(get_mock_instance(&server, vec![("guard.max_fraction", invalid)]).guard_max_fraction, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_output() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", Utc::now() - Duration::days(4), 10).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", Utc::now(), 10).await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 0).await;
+
+        let path = get_state_path("dry-run-output");
+        let mut instance = get_mock_instance(&server, vec![("rule.old.age.max", "1d"), ("rule.old.tag.pattern", "^none$"), ("dryrun.output", &path)]);
+        instance.dry_run = true;
+        instance.apply_rules(vec![String::from("old")], None).await.unwrap();
+        delete_v1.assert_async().await;
+
+        let output = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(output["version"], 1);
+        assert_eq!(output["registry"], instance.name);
+        assert_eq!(output["total_tags"], 1);
+        assert_eq!(output["total_bytes"], 10);
+        assert_eq!(output["repositories"][0]["tags"][0]["name"], "v1");
+        assert!(output["repositories"][0]["tags"][0]["age_seconds"].as_i64().unwrap() >= Duration::days(4).num_seconds());
+    }
+
+    #[tokio::test]
+    async fn test_min_age_absolute() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev-old", "dev-new"]).await;
+        let _old = mock_manifest(&mut server, "app", "dev-old", "sha256:old", Utc::now() - Duration::minutes(10), 10).await;
+        let _new = mock_manifest(&mut server, "app", "dev-new", "sha256:new", Utc::now(), 10).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("min-age-absolute", "5m")]);
+        assert_eq!(instance.min_age_absolute, Some(Duration::minutes(5)));
+        let plan = instance.plan_rules(vec![String::from("dev")], None, &mut RunReport::default()).await.unwrap();
+        assert_eq!(plan.tag_count(), 1);
+        assert_eq!(plan.repositories[0].tags[0].name, "dev-old");
+    }
+
+    #[tokio::test]
+    async fn test_scan_report() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2", "v3"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", created, 10).await;
+        let _v3 = mock_manifest(&mut server, "app", "v3", "sha256:v3", created, 10).await;
+        let _gone = server.mock("GET", "/v2/gone/tags/list")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .with_body(r#"{"errors":[{"code":"NAME_UNKNOWN","message":"repository name not known to registry"}]}"#)
+            .create_async()
+            .await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 1).await;
+        let delete_v2 = mock_delete(&mut server, "app", "sha256:v2", 202, "", 1).await;
+        let delete_v3 = mock_delete(&mut server, "app", "sha256:v3", 202, "", 0).await;
+
+        let path = get_state_path("scan-report");
+        std::fs::write(&path, r#"["localhost:5000/app:v1", "app@sha256:v2", "app:v4", "gone:v1"]"#).unwrap();
+        let instance = get_mock_instance(&server, vec![("rule.none.tag.pattern", "^none$"), ("scan-report", &path)]);
+        let report = instance.apply_rules(vec![String::from("none")], None).await.unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.deleted.len(), 2);
+        assert_eq!(report.deleted[0].reason, format!("scan report '{path}'"));
+        delete_v1.assert_async().await;
+        delete_v2.assert_async().await;
+        delete_v3.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_pull_log() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(100);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["stale", "recent", "unknown"]).await;
+        let _stale = mock_manifest(&mut server, "app", "stale", "sha256:stale", created, 10).await;
+        let _recent = mock_manifest(&mut server, "app", "recent", "sha256:recent", created, 10).await;
+        let _unknown = mock_manifest(&mut server, "app", "unknown", "sha256:unknown", created, 10).await;
+        let delete_stale = mock_delete(&mut server, "app", "sha256:stale", 202, "", 1).await;
+        let delete_recent = mock_delete(&mut server, "app", "sha256:recent", 202, "", 0).await;
+        let delete_unknown = mock_delete(&mut server, "app", "sha256:unknown", 202, "", 0).await;
+
+        let path = get_state_path("pull-log");
+        std::fs::write(&path, serde_json::json!({
+            "app:stale": (Utc::now() - Duration::days(60)).to_rfc3339(),
+            "app:recent": (Utc::now() - Duration::days(1)).to_rfc3339()
+        }).to_string()).unwrap();
+        let instance = get_mock_instance(&server, vec![("rule.unused.last-pulled.max", "30d"), ("rule.unused.tag.pattern", "^none$"), ("pull-log", &path)]);
+        let report = instance.apply_rules(vec![String::from("unused")], None).await.unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.deleted.len(), 1);
+        delete_stale.assert_async().await;
+        delete_recent.assert_async().await;
+        delete_unknown.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_apply_rules_to_repository() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let catalog = mock_catalog(&mut server, vec!["app", "api"]).await.expect(0);
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("notify.delay", "5s")]);
+        assert_eq!(instance.notify_delay, std::time::Duration::from_secs(5));
+        assert!(instance.apply_rules(vec![String::from("dev")], Some("app")).await.is_ok());
+        catalog.assert_async().await;
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_summary() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app", "api"]).await;
+        let _app_tags = mock_tag_list(&mut server, "app", vec!["dev", "latest"]).await;
+        let _api_tags = mock_tag_list(&mut server, "api", vec!["v1"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let _latest = mock_manifest(&mut server, "app", "latest", "sha256:latest", created, 20).await;
+        let _v1 = mock_manifest(&mut server, "api", "v1", "sha256:v1", created, 30).await;
+        let _delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev")]);
+        let report = instance.apply_rules(vec![String::from("dev")], None).await.unwrap();
+        assert_eq!(report.rules, vec![String::from("dev")]);
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.freed_bytes(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_error() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["broken", "api", "app"]).await;
+        let broken = server.mock("GET", "/v2/broken/tags/list").match_query(mockito::Matcher::Any).with_status(500).expect(1).create_async().await;
+        let _api_tags = mock_tag_list(&mut server, "api", vec!["dev"]).await;
+        let _api_dev = mock_manifest(&mut server, "api", "dev", "sha256:api-dev", created, 10).await;
+        let _delete_api_dev = mock_delete(&mut server, "api", "sha256:api-dev", 500, "", 1).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("rule.all.tag.pattern", ".+")]);
+        assert_eq!(instance.on_error, ErrorStrategy::Continue);
+        let report = instance.apply_rules(vec![String::from("dev"), String::from("all")], None).await.unwrap();
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.failed_repositories, vec!["broken", "api"]);
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.deleted[0].repository, "app");
+        broken.assert_async().await;
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["broken"]).await;
+        let _broken = server.mock("GET", "/v2/broken/tags/list").match_query(mockito::Matcher::Any).with_status(500).create_async().await;
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("on-error", "abort")]);
+        assert_eq!(instance.on_error, ErrorStrategy::Abort);
+        assert!(instance.apply_rules(vec![String::from("dev")], None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_representative() {
+        let server = mockito::Server::new_async().await;
+        assert_eq!(get_mock_instance(&server, vec![]).representative, Representative::Semver);
+        assert_eq!(get_mock_instance(&server, vec![("report.representative", "lexical")]).representative, Representative::Lexical);
+        assert_eq!(get_mock_instance(&server, vec![("report.representative", "newest")]).representative, Representative::Semver);
+    }
+
+    #[tokio::test]
+    async fn test_first_run_dry() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let state = StateStore::new(get_state_path("first-run"), true, None);
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev")]);
+        // a run of a single pushed repository doesn't end the first run
+        assert!(instance.run_rules(vec![String::from("dev")], Some("app"), &state).await.is_ok());
+        assert!(!delete_dev.matched_async().await);
+        assert!(!state.has_run("test-registry"));
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        assert!(!delete_dev.matched_async().await);
+        assert!(state.has_run("test-registry"));
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_locked_registry() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let state = StateStore::new(get_state_path("locked"), false, None);
+        // another process sharing the state file
+        let other = StateStore::new(get_state_path("locked"), false, None);
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev")]);
+        let lock = other.lock("test-registry").await.unwrap();
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        assert!(!delete_dev.matched_async().await);
+        assert!(!state.has_run("test-registry"));
+        drop(lock);
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_bundles() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev", "old"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let _old = mock_manifest(&mut server, "app", "old", "sha256:old", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let delete_old = mock_delete(&mut server, "app", "sha256:old", 202, "", 1).await;
+        let state = StateStore::new(get_state_path("concurrent-bundles"), false, None);
+
+        // e.g. an hourly and a daily bundle ticking at midnight
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "^dev$"), ("rule.old.tag.pattern", "^old$"), ("rule.old.schedule", "0 0 0 * * * *")]);
+        let (dev, old) = tokio::join!(
+            instance.run_rules(vec![String::from("dev")], None, &state),
+            instance.run_rules(vec![String::from("old")], None, &state)
+        );
+        assert!(dev.is_ok_and(|report| report.deleted.len() == 1));
+        assert!(old.is_ok_and(|report| report.deleted.len() == 1));
+        delete_dev.assert_async().await;
+        delete_old.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_apply_plan() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev", "moved", "v1", "old", "latest"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let _moved = mock_manifest(&mut server, "app", "moved", "sha256:new", created, 10).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _old = mock_manifest(&mut server, "app", "old", "sha256:old", created, 10).await;
+        let _latest = mock_manifest(&mut server, "app", "latest", "sha256:old", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let delete_other = server.mock("DELETE", mockito::Matcher::Regex(String::from("^/v2/app/manifests/sha256:(moved|new|gone|v1|old)$")))
+            .with_status(202)
+            .expect(0)
+            .create_async()
+            .await;
+        let state = StateStore::new(get_state_path("apply-plan"), false, None);
+
+        let instance = get_mock_instance(&server, vec![("immutable-pattern", "^v\\d+"), ("rule.dev.tag.pattern", "dev"), ("rule.dev.keep.tags", "latest")]);
+        let mut plan = CleanupPlan::new(instance.name.clone());
+        for (name, digest) in [("dev", "sha256:dev"), ("moved", "sha256:moved"), ("gone", "sha256:gone"), ("v1", "sha256:v1"), ("old", "sha256:old")] {
+            plan.add("app", &Tag::new(String::from(name), String::from(digest), created, 10), Some("dev"), String::from("rule 'dev'"));
+        }
+
+        let lock = StateStore::new(get_state_path("apply-plan"), false, None).lock(&instance.name).await.unwrap();
+        assert!(matches!(instance.apply_plan(&plan, &state, &mut RunReport::default()).await, Err(Error::RegistryLocked(_, _))));
+        assert!(!delete_dev.matched_async().await);
+        drop(lock);
+
+        // only `dev` still points to its planned digest and isn't protected
+        let mut report = RunReport::default();
+        assert!(instance.apply_plan(&plan, &state, &mut report).await.is_ok());
+        assert_eq!(report.deleted.len(), 1);
+        delete_dev.assert_async().await;
+        delete_other.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_safety_period() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let state = StateStore::new(get_state_path("safety-period-run"), false, Some(Duration::milliseconds(200)));
 
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev")]);
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        assert!(!delete_dev.matched_async().await);
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        assert!(instance.run_rules(vec![String::from("dev")], None, &state).await.is_ok());
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rejected_immutable_delete() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "dev"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let body = r#"{"errors":[{"code":"PRECONDITION","message":"the tag is immutable"}]}"#;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 412, body, 1).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.all.tag.pattern", ".*")]);
+        assert!(instance.apply_rules(vec![String::from("all")], None).await.is_ok());
+        delete_v1.assert_async().await;
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deletions() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let names = (0..6).map(|index| format!("v{index}")).collect::<Vec<_>>();
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", names.iter().map(String::as_str).collect()).await;
+        let mut mocks = vec![];
+        for name in &names {
+            mocks.push(mock_manifest(&mut server, "app", name, &format!("sha256:{name}"), created, 10).await);
+        }
+        let mut deletions = vec![];
+        for name in &names {
+            deletions.push(mock_delete(&mut server, "app", &format!("sha256:{name}"), 202, "", 1).await);
+        }
+
+        let instance = get_mock_instance(&server, vec![("rule.all.tag.pattern", ".+"), ("request.concurrency", "4")]);
+        let mut report = RunReport::new(instance.name.clone());
+        let plan = instance.plan_rules(vec![String::from("all")], None, &mut report).await.unwrap();
+        instance.execute_plan(&plan, &mut report).await.unwrap();
+        // the deletions are recorded in the order of the plan independent of when they finished
+        let planned = plan.repositories[0].tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(report.deleted.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(), planned);
+        assert_eq!(report.deleted.len(), 6);
+        for deletion in deletions {
+            deletion.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_already_deleted() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "dev"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", created, 10).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let body = r#"{"errors":[{"code":"MANIFEST_UNKNOWN","message":"manifest unknown"}]}"#;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 404, body, 1).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let instance = get_mock_instance(&server, vec![("rule.all.tag.pattern", ".*"), ("on-error", "abort")]);
+        let report = instance.apply_rules(vec![String::from("all")], None).await.unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.deleted.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(), vec!["dev"]);
+        delete_v1.assert_async().await;
+        delete_dev.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_disabled_rule() {
+        let mut server = mockito::Server::new_async().await;
+        let catalog = server.mock("GET", "/v2/_catalog").match_query(mockito::Matcher::Any).expect(0).create_async().await;
+
+        let instance = get_mock_instance(&server, vec![
+            ("rule.teama.revisions", "1"),
+            ("rule.teama.schedule", "0 0 1 * * * *"),
+            ("rule.teama.enabled", "false"),
+            ("rule.teamb.revisions", "1"),
+            ("rule.teamb.schedule", "0 0 2 * * * *"),
+        ]);
+        assert!(!instance.rules["teama"].enabled);
+        assert!(instance.rules["teamb"].enabled);
+
+        let bundles = instance.get_bundled_rules();
+        assert_eq!(bundles.len(), 1);
+        assert_eq!(bundles.get("0 0 2 * * * *"), Some(&vec![String::from("teamb")]));
+
+        assert!(instance.apply_rules(vec![String::from("teama")], None).await.is_ok());
+        catalog.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_default_rule_only() {
+        let mut server = mockito::Server::new_async().await;
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1", "v2", "v3"]).await;
+        let _v1 = mock_manifest(&mut server, "app", "v1", "sha256:v1", Utc::now() - Duration::days(3), 1).await;
+        let _v2 = mock_manifest(&mut server, "app", "v2", "sha256:v2", Utc::now() - Duration::days(2), 1).await;
+        let _v3 = mock_manifest(&mut server, "app", "v3", "sha256:v3", Utc::now() - Duration::days(1), 1).await;
+        let delete_v1 = mock_delete(&mut server, "app", "sha256:v1", 202, "", 1).await;
+        let delete_v2 = mock_delete(&mut server, "app", "sha256:v2", 202, "", 0).await;
+
+        let instance = get_mock_instance(&server, vec![("default.tag.pattern", "^v1$")]);
+        assert!(instance.rules.is_empty());
+        let bundles = instance.get_bundled_rules();
+        assert_eq!(bundles.get("0 0 0 * * * *"), Some(&vec![instance.default_rule.name.clone()]));
+
+        let report = instance.apply_rules(instance.rule_names(), None).await.unwrap();
+        assert_eq!(report.deleted.len(), 1);
+        delete_v1.assert_async().await;
+        delete_v2.assert_async().await;
+    }
+
+    #[test]
+    fn test_default_rule_with_named_rules() {
+        let server = mockito::Server::new();
+        // without default labels the global defaults aren't applied on their own
+        assert!(get_mock_instance(&server, vec![]).get_bundled_rules().is_empty());
+        // with named rules the default rule only holds their defaults
+        let instance = get_mock_instance(&server, vec![("default.revisions", "2"), ("rule.dev.tag.pattern", "dev")]);
+        assert_eq!(instance.rule_names(), vec![String::from("dev")]);
+        assert_eq!(instance.get_bundled_rules().values().flatten().collect::<Vec<_>>(), vec!["dev"]);
+    }
+
+    #[test]
+    fn test_artifact_reference() {
+        assert_eq!(parse_artifact_reference("_abwart/config:latest"), Some((String::from("_abwart/config"), String::from("latest"))));
+        assert_eq!(parse_artifact_reference("_abwart/config"), Some((String::from("_abwart/config"), String::from("latest"))));
+        assert_eq!(parse_artifact_reference("config@sha256:abc"), Some((String::from("config"), String::from("sha256:abc"))));
+        assert_eq!(parse_artifact_reference(":latest"), None);
+    }
+
+    #[tokio::test]
+    async fn test_config_artifact() {
+        let mut server = mockito::Server::new_async().await;
+        let _manifest = server.mock("GET", "/v2/_abwart/config/manifests/latest")
+            .with_header("Docker-Content-Digest", "sha256:artifact")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "mediaType": "application/vnd.oci.empty.v1+json", "digest": "sha256:empty", "size": 2 },
+                "layers": [{ "mediaType": "application/yaml", "digest": "sha256:content", "size": 20 }]
+            }"#)
+            .create_async()
+            .await;
+        let _blob = server.mock("GET", "/v2/_abwart/config/blobs/sha256:content")
+            .with_body("default:\n  revisions: 5\nrule:\n  nightly:\n    tag.pattern: nightly-.+\n    schedule: 0 0 4 * * * *\n")
+            .create_async()
+            .await;
+
+        let mut instance = get_mock_instance(&server, vec![
+            ("config-artifact", "_abwart/config:latest"),
+            ("rule.weekly.revisions", "20")
+        ]);
+        instance.load_config_artifact().await;
+        assert_eq!(instance.rules.len(), 2);
+        assert_eq!(instance.rules["nightly"].schedule, "0 0 4 * * * *");
+        assert!(instance.rules["nightly"].tag_policies.contains_key(TAG_PATTERN_LABEL));
+        assert!(instance.rules["nightly"].tag_policies.contains_key(REVISION_LABEL));
+        assert!(instance.rules.contains_key("weekly"));
+    }
+
+    #[test]
+    fn test_tls_labels() {
+        let server = mockito::Server::new();
+        let instance = get_mock_instance(&server, vec![]);
+        assert!(instance.distribution.insecure);
+
+        let ca = std::env::temp_dir().join("abwart-test-ca.pem");
+        std::fs::write(&ca, "").unwrap();
+        let instance = get_mock_instance(&server, vec![("ca", ca.to_str().unwrap())]);
+        assert_eq!(instance.distribution.ca, Some(ca.to_str().unwrap().to_string()));
+        assert!(!instance.distribution.insecure);
+
+        let instance = get_mock_instance(&server, vec![("ca", "/nonexistent/ca.pem"), ("tls.accept-invalid-certs", "true")]);
+        assert_eq!(instance.distribution.ca, None);
+        assert!(instance.distribution.accept_invalid_certs);
+        assert!(!instance.distribution.insecure);
+    }
+
+    #[test]
+    fn test_credential_files() {
+        let server = mockito::Server::new();
+        let username = std::env::temp_dir().join("abwart-test-username");
+        let password = std::env::temp_dir().join("abwart-test-password");
+        std::fs::write(&username, "admin\n").unwrap();
+        std::fs::write(&password, "secret\r\n").unwrap();
+        let (username, password) = (username.to_str().unwrap(), password.to_str().unwrap());
+
+        let instance = get_mock_instance(&server, vec![("username_file", username), ("password_file", password)]);
+        assert_eq!(instance.distribution.username, Some(String::from("admin")));
+        assert_eq!(instance.distribution.password, Some(String::from("secret")));
+
+        // explicit values take precedence over the files
+        let instance = get_mock_instance(&server, vec![("username_file", username), ("password", "explicit"), ("password_file", password)]);
+        assert_eq!(instance.distribution.username, Some(String::from("admin")));
+        assert_eq!(instance.distribution.password, Some(String::from("explicit")));
+
+        let instance = get_mock_instance(&server, vec![("password_file", "/nonexistent/password")]);
+        assert_eq!(instance.distribution.password, None);
+    }
+
+    #[tokio::test]
+    async fn test_standalone() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+
+        let labels = HashMap::from([
+            (crate::label("host"), server.host_with_port()),
+            (crate::label("insecure"), String::from("true")),
+            (crate::label("rule.dev.tag.pattern"), String::from("dev"))
+        ]);
+        let instance = Instance::standalone(String::from("hosted"), labels.clone(), get_docker_client()).unwrap();
+        assert!(instance.standalone);
+        assert_eq!(instance.id, "hosted");
+        assert_eq!(instance.distribution.host, server.host_with_port());
+        assert!(instance.distribution.insecure);
+        assert!(instance.apply_rules(vec![String::from("dev")], None).await.is_ok());
+        delete_dev.assert_async().await;
+        assert!(!instance.run_garbage_collector().await);
+
+        let secure = HashMap::from([(crate::label("host"), String::from("registry.example.com"))]);
+        assert!(!Instance::standalone(String::from("hosted"), secure, get_docker_client()).unwrap().distribution.insecure);
+        assert!(Instance::standalone(String::from("hosted"), HashMap::new(), get_docker_client()).is_err());
+    }
+
+    #[test]
+    fn test_select_network() {
+        let endpoint = |address: &str| EndpointSettings { ip_address: Some(address.to_string()), ..EndpointSettings::default() };
+        let networks = HashMap::from([
+            (String::from("frontend"), endpoint("172.20.0.2")),
+            (String::from("backend"), endpoint("172.21.0.2")),
+            (String::from("a-detached"), endpoint(""))
+        ]);
+        assert_eq!(select_network(&networks), Some(String::from("backend")));
+        assert_eq!(select_network(&HashMap::new()), None);
+
+        let instance = Instance::new(String::from("test-id"), String::from("test-registry"), HashMap::new(), networks.clone(), get_docker_client()).unwrap();
+        assert_eq!(instance.distribution.host, "172.21.0.2:5000");
+        let labels = HashMap::from([(crate::label("network"), String::from("frontend"))]);
+        let instance = Instance::new(String::from("test-id"), String::from("test-registry"), labels, networks, get_docker_client()).unwrap();
+        assert_eq!(instance.distribution.host, "172.20.0.2:5000");
+    }
+
+    #[test]
+    fn test_ipv6_address() {
+        assert_eq!(format_host("172.20.0.2", 5000), "172.20.0.2:5000");
+        assert_eq!(format_host("fd00::2", 5000), "[fd00::2]:5000");
+        assert_eq!(format_host("registry", 443), "registry:443");
+
+        let ipv4 = HashMap::from([(String::from("bridge"), EndpointSettings { ip_address: Some(String::from("172.17.0.2")), ..EndpointSettings::default() })]);
+        let instance = Instance::new(String::from("test-id"), String::from("test-registry"), HashMap::new(), ipv4, get_docker_client()).unwrap();
+        assert_eq!(instance.distribution.url("/v2/"), "http://172.17.0.2:5000/v2/");
+
+        let ipv6 = HashMap::from([(String::from("ipv6"), EndpointSettings {
+            ip_address: Some(String::new()),
+            global_ipv6_address: Some(String::from("fd00:dead:beef::2")),
+            ..EndpointSettings::default()
+        })]);
+        let instance = Instance::new(String::from("test-id"), String::from("test-registry"), HashMap::new(), ipv6, get_docker_client()).unwrap();
+        assert_eq!(instance.distribution.host, "[fd00:dead:beef::2]:5000");
+        assert_eq!(instance.distribution.url("/v2/"), "http://[fd00:dead:beef::2]:5000/v2/");
+        assert!(reqwest::Url::parse(&instance.distribution.url("/v2/")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let mut server = mockito::Server::new_async().await;
+        let _version = server.mock("GET", "/v2/").with_header("Docker-Distribution-API-Version", "registry/2.0").create_async().await;
+        assert!(get_mock_instance(&server, vec![]).probe().await);
+
+        let labels = HashMap::from([(crate::label("host"), String::from("127.0.0.1:1")), (crate::label("insecure"), String::from("true"))]);
+        assert!(!Instance::standalone(String::from("unreachable"), labels, get_docker_client()).unwrap().probe().await);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let server = mockito::Server::new();
+        let instance = get_mock_instance(&server, vec![]);
+        assert_eq!(instance.jitter, None);
+        assert_eq!(instance.jitter_offset(), std::time::Duration::ZERO);
+
+        let instance = get_mock_instance(&server, vec![("jitter", "5m")]);
+        assert_eq!(instance.jitter, Some(std::time::Duration::from_secs(300)));
+        let offset = instance.jitter_offset();
+        assert!(offset < std::time::Duration::from_secs(300));
+        assert_eq!(get_mock_instance(&server, vec![("jitter", "5m")]).jitter_offset(), offset);
+
+        assert_eq!(get_mock_instance(&server, vec![("jitter", "invalid")]).jitter, None);
+        assert_eq!(get_mock_instance(&server, vec![("jitter", "0s")]).jitter, None);
+    }
+
+    #[tokio::test]
+    async fn test_cached_config_artifact() {
+        let mut server = mockito::Server::new_async().await;
+        let head = server.mock("HEAD", "/v2/_abwart/config/manifests/latest")
+            .with_header("Docker-Content-Digest", "sha256:artifact")
+            .expect(2)
+            .create_async()
+            .await;
+        let manifest = server.mock("GET", "/v2/_abwart/config/manifests/latest")
+            .with_header("Docker-Content-Digest", "sha256:artifact")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "mediaType": "application/vnd.oci.empty.v1+json", "digest": "sha256:empty", "size": 2 },
+                "layers": [{ "mediaType": "application/yaml", "digest": "sha256:content", "size": 20 }]
+            }"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let blob = server.mock("GET", "/v2/_abwart/config/blobs/sha256:content")
+            .with_body("rule:\n  nightly:\n    tag.pattern: nightly-.+\n")
+            .expect(1)
+            .create_async()
+            .await;
+
+        // the unchanged artifact is only downloaded once
+        let mut instance = get_mock_instance(&server, vec![("config-artifact", "_abwart/config:latest")]);
+        for _ in 0..2 {
+            instance.load_config_artifact().await;
+            assert!(instance.rules.contains_key("nightly"));
+        }
+        head.assert_async().await;
+        manifest.assert_async().await;
+        blob.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_missing_config_artifact() {
+        let mut server = mockito::Server::new_async().await;
+        let manifest = server.mock("GET", "/v2/_abwart/config/manifests/latest")
+            .with_status(404)
+            .with_body(r#"{"errors":[{"code":"MANIFEST_UNKNOWN","message":"manifest unknown"}]}"#)
+            .create_async()
+            .await;
+
+        let mut instance = get_mock_instance(&server, vec![
+            ("config-artifact", "_abwart/config:latest"),
+            ("rule.weekly.revisions", "20")
+        ]);
+        instance.load_config_artifact().await;
+        assert_eq!(instance.rules.len(), 1);
+        assert!(instance.rules.contains_key("weekly"));
+        manifest.assert_async().await;
+    }
+
+    async fn mock_exec(server: &mut mockito::ServerGuard, exit_code: i64, running: bool, hits: usize) -> (mockito::Mock, mockito::Mock, mockito::Mock) {
+        let create = server.mock("POST", mockito::Matcher::Regex(String::from(r"^(/v[\d.]+)?/containers/test-id/exec$")))
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"Id":"exec-id"}"#)
+            .expect(hits)
+            .create_async()
+            .await;
+        let start = server.mock("POST", mockito::Matcher::Regex(String::from(r"^(/v[\d.]+)?/exec/exec-id/start$")))
+            .with_status(200)
+            .expect(hits)
+            .create_async()
+            .await;
+        let inspect = server.mock("GET", mockito::Matcher::Regex(String::from(r"^(/v[\d.]+)?/exec/exec-id/json$")))
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{"ID":"exec-id","Running":{running},"ExitCode":{exit_code}}}"#))
+            .create_async()
+            .await;
+        (create, start, inspect)
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector() {
+        let registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, false, 1).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, vec![("gc.retries", "2"), ("gc.backoff", "1ms")]);
+        assert!(instance.run_garbage_collector().await);
+        create.assert_async().await;
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector_only() {
+        let mut registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let catalog = registry.mock("GET", "/v2/_catalog").match_query(mockito::Matcher::Any).expect(0).create_async().await;
+        let delete = registry.mock("DELETE", mockito::Matcher::Any).expect(0).create_async().await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, false, 1).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, vec![("rule.dev.tag.pattern", "dev")]);
+        assert!(instance.collect_garbage().await);
+        create.assert_async().await;
+        start.assert_async().await;
+        catalog.assert_async().await;
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let mut registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut registry, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut registry, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut registry, "app", "dev", "sha256:dev", created, 10).await;
+        let delete = mock_delete(&mut registry, "app", "sha256:dev", 202, "", 0).await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, false, 0).await;
+
+        let mut instance = get_mock_docker_instance(&registry, &docker, vec![("rule.dev.tag.pattern", "dev"), ("rule.dev.tidy", "true")]);
+        instance.dry_run = true;
+        let report = instance.apply_rules(vec![String::from("dev")], None).await.unwrap();
+        assert!(report.deleted.is_empty());
+        assert!(instance.run_garbage_collector().await);
+        delete.assert_async().await;
+        create.assert_async().await;
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector_retries() {
+        let registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 1, false, 3).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, vec![("gc.retries", "2"), ("gc.backoff", "1ms")]);
+        assert!(!instance.run_garbage_collector().await);
+        create.assert_async().await;
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector_timeout() {
+        let registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, true, 2).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, vec![("gc.retries", "1"), ("gc.backoff", "1ms"), ("gc.timeout", "50ms")]);
+        assert!(!instance.run_garbage_collector().await);
+        create.assert_async().await;
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_blobs() {
+        let mut server = mockito::Server::new_async().await;
+        let _dev = mock_manifest(&mut server, "app", "sha256:dev", "sha256:dev", Utc::now(), 1).await;
+        let delete = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["v1"]).await;
+        // the remaining tag shares the layer of the deleted tag
+        let _v1 = server.mock("GET", "/v2/app/manifests/v1")
+            .with_header("Docker-Content-Digest", "sha256:v1")
+            .with_body(r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": { "mediaType": "application/vnd.docker.container.image.v1+json", "digest": "sha256:v1-config", "size": 1 },
+                "layers": [{ "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": "sha256:dev-layer", "size": 1 }]
+            }"#)
+            .create_async()
+            .await;
+        let delete_config = server.mock("DELETE", "/v2/app/blobs/sha256:dev-config").with_status(202).expect(1).create_async().await;
+        let delete_layer = server.mock("DELETE", "/v2/app/blobs/sha256:dev-layer").with_status(202).expect(0).create_async().await;
+
+        let instance = get_mock_instance(&server, vec![("gc.mode", "api")]);
+        let mut plan = CleanupPlan::new(instance.name.clone());
+        plan.add("app", &Tag::new(String::from("dev"), String::from("sha256:dev"), Utc::now(), 1), Some("dev"), String::from("rule 'dev'"));
+        plan.tidy = true;
+        instance.execute_plan(&plan, &mut RunReport::default()).await.unwrap();
+        delete.assert_async().await;
+        delete_config.assert_async().await;
+        delete_layer.assert_async().await;
+    }
+
+    async fn run_tidy_plan(labels: Vec<(&str, &str)>) -> std::time::Duration {
+        let mut registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let delete = mock_delete(&mut registry, "app", "sha256:dev", 202, "", 1).await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, false, 1).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, labels);
+        let mut plan = CleanupPlan::new(instance.name.clone());
+        plan.add("app", &Tag::new(String::from("dev"), String::from("sha256:dev"), Utc::now(), 1), Some("dev"), String::from("rule 'dev'"));
+        plan.tidy = true;
+        let start_time = std::time::Instant::now();
+        instance.execute_plan(&plan, &mut RunReport::default()).await.unwrap();
+        let elapsed = start_time.elapsed();
+        delete.assert_async().await;
+        create.assert_async().await;
+        start.assert_async().await;
+        elapsed
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector_disabled() {
+        let mut registry = mockito::Server::new_async().await;
+        let mut docker = mockito::Server::new_async().await;
+        let delete = mock_delete(&mut registry, "app", "sha256:dev", 202, "", 1).await;
+        let (create, start, _inspect) = mock_exec(&mut docker, 0, false, 0).await;
+
+        let instance = get_mock_docker_instance(&registry, &docker, vec![("gc.enable", "false")]);
+        let mut plan = CleanupPlan::new(instance.name.clone());
+        plan.add("app", &Tag::new(String::from("dev"), String::from("sha256:dev"), Utc::now(), 1), Some("dev"), String::from("rule 'dev'"));
+        plan.tidy = true;
+        instance.execute_plan(&plan, &mut RunReport::default()).await.unwrap();
+        delete.assert_async().await;
+        create.assert_async().await;
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collector_delay() {
+        assert!(run_tidy_plan(vec![("gc.delay", "300ms")]).await >= std::time::Duration::from_millis(300));
+        assert!(run_tidy_plan(vec![]).await < std::time::Duration::from_millis(300));
+    }
+}
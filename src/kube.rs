@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+use log::{info, warn};
+use crate::api::tag::Tag;
+use crate::scan::strip_host;
+
+pub const KUBE_ENV: &str = "ABWART_KUBE";
+
+/// Image reference used by a workload in the kubernetes cluster
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeployedImage {
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>
+}
+
+/// All images used by the workloads in the kubernetes cluster
+#[derive(Debug, Clone, Default)]
+pub struct DeployedImages {
+    images: HashSet<DeployedImage>
+}
+
+impl DeployedImages {
+    #[cfg_attr(not(feature = "kube"), allow(dead_code))]
+    pub fn new(references: &[String]) -> Self {
+        Self { images: references.iter().filter_map(|reference| parse_image(reference)).collect() }
+    }
+
+    /// Whether the tag of a repository is used by a workload. A tag is used when a workload references it
+    /// by its name or by its digest
+    pub fn protects(&self, repository: &str, tag: &Tag) -> bool {
+        self.images.iter().any(|image| {
+            image.repository == repository && (image.tag.as_ref() == Some(&tag.name) || image.digest.as_ref() == Some(&tag.digest))
+        })
+    }
+
+    /// Get the digests of all tags of a repository which are used by a workload. Workloads referencing a tag by its name
+    /// use the digest the tag currently points to, therefore all tags sharing this digest are used as well
+    pub fn protected_digests(&self, repository: &str, tags: &[Tag]) -> HashSet<String> {
+        tags.iter().filter(|tag| self.protects(repository, tag)).map(|tag| tag.digest.clone()).collect()
+    }
+}
+
+/// Parse an image reference of a workload (e.g. `localhost:5000/app:v1` or `docker-pullable://app@sha256:...`).
+/// Like for scan reports the registry host is ignored. References without tag and digest use the `latest` tag
+#[cfg_attr(not(feature = "kube"), allow(dead_code))]
+pub fn parse_image(reference: &str) -> Option<DeployedImage> {
+    let reference = reference.trim();
+    let reference = reference.split_once("://").map(|(_, reference)| reference).unwrap_or(reference);
+    let reference = strip_host(reference);
+    let (name, digest) = match reference.split_once('@') {
+        Some((name, digest)) => (name, Some(digest.to_string())),
+        None => (reference, None)
+    };
+    let (repository, tag) = match name.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag.to_string())),
+        _ => (name, None)
+    };
+    let tag = if tag.is_none() && digest.is_none() { Some(String::from("latest")) } else { tag };
+    if repository.is_empty() || repository.starts_with("sha256") {
+        return None
+    }
+    Some(DeployedImage { repository: repository.to_string(), tag, digest })
+}
+
+fn enabled() -> bool {
+    std::env::var(KUBE_ENV).is_ok_and(|value| value.parse::<bool>().unwrap_or_else(|_| {
+        warn!("Received invalid value '{value}' for '{KUBE_ENV}'. Expected boolean. Using default (false) instead");
+        false
+    }))
+}
+
+/// Get the images of all workloads in the kubernetes cluster abwart is running in when enabled with `ABWART_KUBE=true`.
+/// When the kubernetes api is unreachable no images are returned
+pub async fn deployed_images() -> DeployedImages {
+    if !enabled() {
+        return DeployedImages::default()
+    }
+    #[cfg(feature = "kube")]
+    {
+        match client::list_images().await {
+            Ok(references) => {
+                let images = DeployedImages::new(&references);
+                info!("Protecting {} images used by workloads in the kubernetes cluster", images.images.len());
+                images
+            },
+            Err(err) => {
+                warn!("Unable to get the images used in the kubernetes cluster. Not protecting any deployed images. Reason: {err}");
+                DeployedImages::default()
+            }
+        }
+    }
+    #[cfg(not(feature = "kube"))]
+    {
+        info!("'{KUBE_ENV}' is set but abwart was built without the 'kube' feature. Not protecting any deployed images");
+        DeployedImages::default()
+    }
+}
+
+#[cfg(feature = "kube")]
+mod client {
+    use reqwest::{Certificate, Client};
+    use serde::de::DeserializeOwned;
+    use serde::Deserialize;
+    use crate::error::Error;
+
+    const SERVICE_ACCOUNT_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+    #[derive(Debug, Deserialize)]
+    struct List<T> {
+        #[serde(default = "Vec::new")]
+        items: Vec<T>
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PodSpec {
+        #[serde(default)]
+        containers: Vec<Container>,
+        #[serde(default)]
+        init_containers: Vec<Container>,
+        #[serde(default)]
+        ephemeral_containers: Vec<Container>
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    struct Container {
+        #[serde(default)]
+        image: String
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PodStatus {
+        #[serde(default)]
+        container_statuses: Vec<ContainerStatus>,
+        #[serde(default)]
+        init_container_statuses: Vec<ContainerStatus>
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    struct ContainerStatus {
+        #[serde(default, rename = "imageID")]
+        image_id: String
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Pod {
+        #[serde(default)]
+        spec: PodSpec,
+        #[serde(default)]
+        status: PodStatus
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Deployment {
+        spec: DeploymentSpec
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct DeploymentSpec {
+        template: PodTemplate
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PodTemplate {
+        #[serde(default)]
+        spec: PodSpec
+    }
+
+    impl PodSpec {
+        fn images(&self) -> impl Iterator<Item = String> + '_ {
+            self.containers.iter().chain(&self.init_containers).chain(&self.ephemeral_containers).map(|container| container.image.clone())
+        }
+    }
+
+    /// List the image references of all pods and deployments in the cluster using the service account of the pod.
+    /// The image ids of the container statuses are included since they contain the digest of the pulled image
+    pub async fn list_images() -> Result<Vec<String>, Error> {
+        let host = std::env::var("KUBERNETES_SERVICE_HOST").map_err(|_| Error::KubernetesApi(String::from("Not running inside a kubernetes cluster")))?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or(String::from("443"));
+        let host = if host.contains(':') { format!("[{host}]") } else { host };
+        let token = std::fs::read_to_string(format!("{SERVICE_ACCOUNT_PATH}/token")).map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        let ca = std::fs::read(format!("{SERVICE_ACCOUNT_PATH}/ca.crt")).map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        let certificate = Certificate::from_pem(&ca).map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        let client = Client::builder().add_root_certificate(certificate).build().map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        let base = format!("https://{host}:{port}");
+
+        let pods = get::<Pod>(&client, &format!("{base}/api/v1/pods"), token.trim()).await?;
+        let deployments = get::<Deployment>(&client, &format!("{base}/apis/apps/v1/deployments"), token.trim()).await?;
+
+        let mut images = vec![];
+        for pod in pods {
+            images.extend(pod.spec.images());
+            images.extend(pod.status.container_statuses.iter().chain(&pod.status.init_container_statuses).map(|status| status.image_id.clone()));
+        }
+        for deployment in deployments {
+            images.extend(deployment.spec.template.spec.images());
+        }
+        images.retain(|image| !image.is_empty());
+        Ok(images)
+    }
+
+    async fn get<T: DeserializeOwned>(client: &Client, url: &str, token: &str) -> Result<Vec<T>, Error> {
+        let response = client.get(url).bearer_auth(token).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        let list = response.json::<List<T>>().await.map_err(|err| Error::KubernetesApi(err.to_string()))?;
+        Ok(list.items)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::kube::{DeployedImage, DeployedImages, parse_image};
+    use crate::test::get_tags_with_digest;
+
+    #[test]
+    fn test_parse_image() {
+        assert_eq!(parse_image("localhost:5000/app:v1"), Some(DeployedImage { repository: String::from("app"), tag: Some(String::from("v1")), digest: None }));
+        assert_eq!(parse_image("registry.example.com/team/api"), Some(DeployedImage { repository: String::from("team/api"), tag: Some(String::from("latest")), digest: None }));
+        assert_eq!(parse_image("docker-pullable://localhost:5000/app@sha256:abc"), Some(DeployedImage { repository: String::from("app"), tag: None, digest: Some(String::from("sha256:abc")) }));
+        assert_eq!(parse_image("app:v2@sha256:def"), Some(DeployedImage { repository: String::from("app"), tag: Some(String::from("v2")), digest: Some(String::from("sha256:def")) }));
+        assert_eq!(parse_image("sha256:abc"), None);
+    }
+
+    #[test]
+    fn test_protects() {
+        let tags = get_tags_with_digest(vec![("v1", "sha256:abc"), ("v2", "sha256:def"), ("v3", "sha256:ghi"), ("latest", "sha256:ghi")]);
+        let images = DeployedImages::new(&[
+            String::from("localhost:5000/app:v1"),
+            String::from("docker-pullable://localhost:5000/app@sha256:ghi"),
+            String::from("localhost:5000/other:v2")
+        ]);
+        let protected = tags.iter().filter(|tag| images.protects("app", tag)).map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(protected, vec!["v1", "v3", "latest"]);
+        assert!(!images.protects("other", &tags[0]));
+        assert!(images.protects("other", &tags[1]));
+    }
+
+    #[test]
+    fn test_protected_digests() {
+        let tags = get_tags_with_digest(vec![("latest", "sha256:abc"), ("v1", "sha256:abc"), ("v2", "sha256:def")]);
+        let images = DeployedImages::new(&[String::from("localhost:5000/app")]);
+        let digests = images.protected_digests("app", &tags);
+        // `v1` isn't referenced by the workload but deleting it would delete the running `latest` image
+        let protected = tags.iter().filter(|tag| digests.contains(&tag.digest)).map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(protected, vec!["latest", "v1"]);
+        assert!(images.protected_digests("other", &tags).is_empty());
+    }
+}
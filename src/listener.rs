@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+pub const NOTIFY_PORT_ENV: &str = "NOTIFY_PORT";
+/// Path prefix of the endpoint which receives the notifications of a registry. The name of the
+/// registry is appended to the prefix (e.g. `/events/registry`)
+const EVENTS_PATH: &str = "/events/";
+
+/// Envelope of the notifications sent by the registry
+/// See [docs](https://distribution.github.io/distribution/about/notifications/)
+#[derive(Deserialize, Debug)]
+struct Envelope {
+    #[serde(default)]
+    events: Vec<Event>
+}
+
+#[derive(Deserialize, Debug)]
+struct Event {
+    action: String,
+    target: EventTarget
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EventTarget {
+    repository: String,
+    #[serde(default)]
+    media_type: Option<String>,
+    #[serde(default)]
+    tag: Option<String>
+}
+
+/// Push of a manifest into a repository of a registry
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct PushEvent {
+    pub registry: String,
+    pub repository: String
+}
+
+/// Get the pushed repositories from a notification of a registry. Blob pushes are ignored since
+/// they are always followed by a manifest push
+pub fn parse_events(registry: &str, body: &[u8]) -> Result<Vec<PushEvent>, serde_json::Error> {
+    let envelope = serde_json::from_slice::<Envelope>(body)?;
+    let mut events = Vec::new();
+    for event in envelope.events {
+        let is_manifest = event.target.tag.is_some() || event.target.media_type.is_some_and(|media_type| media_type.contains("manifest"));
+        if event.action != "push" || !is_manifest {
+            continue
+        }
+        let push = PushEvent { registry: registry.to_string(), repository: event.target.repository };
+        if !events.contains(&push) {
+            events.push(push)
+        }
+    }
+    Ok(events)
+}
+
+/// Start the http server which receives the notifications of the registries on the given port
+pub fn start_listener(port: u16, tx: Sender<PushEvent>) {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle_request(req, tx.clone()))) }
+    });
+    match Server::try_bind(&address) {
+        Ok(builder) => {
+            info!("Listening for registry notifications on port {port}");
+            tokio::spawn(async move {
+                if let Err(err) = builder.serve(service).await {
+                    error!("Registry notification listener stopped. Reason: {err}")
+                }
+            });
+        },
+        Err(err) => error!("Unable to listen for registry notifications on port {port}. Reason: {err}")
+    }
+}
+
+async fn handle_request(req: Request<Body>, tx: Sender<PushEvent>) -> Result<Response<Body>, Infallible> {
+    let registry = match req.uri().path().strip_prefix(EVENTS_PATH) {
+        Some(registry) if !registry.is_empty() && req.method() == Method::POST => registry.to_string(),
+        _ => return Ok(response(StatusCode::NOT_FOUND))
+    };
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(err) => {
+            warn!("Unable to read notification of registry '{registry}'. Reason: {err}");
+            return Ok(response(StatusCode::BAD_REQUEST))
+        }
+    };
+    match parse_events(&registry, &body) {
+        Ok(events) => {
+            debug!("Received {} push events from registry '{registry}'", events.len());
+            for event in events {
+                if let Err(err) = tx.send(event).await {
+                    error!("Unable to forward push event of registry '{registry}'. Reason: {err}");
+                    return Ok(response(StatusCode::INTERNAL_SERVER_ERROR))
+                }
+            }
+            Ok(response(StatusCode::OK))
+        },
+        Err(err) => {
+            warn!("Received invalid notification from registry '{registry}'. Reason: {err}");
+            Ok(response(StatusCode::BAD_REQUEST))
+        }
+    }
+}
+
+fn response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Delays the execution of a future until no other future was triggered for the same push event
+/// within the delay
+#[derive(Default)]
+pub struct Debouncer {
+    pending: HashMap<PushEvent, JoinHandle<()>>
+}
+
+impl Debouncer {
+    pub fn trigger<F>(&mut self, event: PushEvent, delay: Duration, run: F) where F: Future<Output = ()> + Send + 'static {
+        self.pending.retain(|_, handle| !handle.is_finished());
+        if let Some(handle) = self.pending.remove(&event) {
+            debug!("Postponing cleanup of repository '{}' in registry '{}'", event.repository, event.registry);
+            handle.abort()
+        }
+        // the future is spawned separately to prevent aborting an already running cleanup
+        let handle = tokio::spawn(async move {
+            sleep(delay).await;
+            tokio::spawn(run);
+        });
+        self.pending.insert(event, handle);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use hyper::{Body, Request, StatusCode};
+    use tokio::time::sleep;
+    use crate::listener::{Debouncer, handle_request, parse_events, PushEvent};
+
+    const NOTIFICATION: &str = r#"{
+        "events": [
+            {
+                "id": "1",
+                "action": "push",
+                "target": { "mediaType": "application/octet-stream", "digest": "sha256:layer", "repository": "app" }
+            },
+            {
+                "id": "2",
+                "action": "push",
+                "target": { "mediaType": "application/vnd.docker.distribution.manifest.v2+json", "digest": "sha256:abc", "repository": "app", "tag": "v1" }
+            },
+            {
+                "id": "3",
+                "action": "pull",
+                "target": { "mediaType": "application/vnd.docker.distribution.manifest.v2+json", "digest": "sha256:def", "repository": "api", "tag": "v1" }
+            },
+            {
+                "id": "4",
+                "action": "push",
+                "target": { "mediaType": "application/vnd.docker.distribution.manifest.v2+json", "digest": "sha256:abc", "repository": "app", "tag": "latest" }
+            }
+        ]
+    }"#;
+
+    fn push_event(repository: &str) -> PushEvent {
+        PushEvent { registry: String::from("registry"), repository: String::from(repository) }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        assert_eq!(parse_events("registry", NOTIFICATION.as_bytes()).unwrap(), vec![push_event("app")]);
+        assert_eq!(parse_events("registry", br#"{"events":[]}"#).unwrap(), vec![]);
+        assert!(parse_events("registry", b"invalid").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle_request() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let req = Request::post("/events/registry").body(Body::from(NOTIFICATION)).unwrap();
+        assert_eq!(handle_request(req, tx.clone()).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(rx.recv().await, Some(push_event("app")));
+
+        let req = Request::post("/events/").body(Body::from(NOTIFICATION)).unwrap();
+        assert_eq!(handle_request(req, tx.clone()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let req = Request::get("/events/registry").body(Body::empty()).unwrap();
+        assert_eq!(handle_request(req, tx.clone()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let req = Request::post("/events/registry").body(Body::from("invalid")).unwrap();
+        assert_eq!(handle_request(req, tx).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_debounce() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut debouncer = Debouncer::default();
+        for repository in ["app", "app", "app", "api"] {
+            let runs = runs.clone();
+            debouncer.trigger(push_event(repository), Duration::from_millis(50), async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::Write;
+use chrono::{SecondsFormat, Utc};
+use log::kv::{Error, Key, Value, Visitor};
+use log::{Record, warn};
+use serde_json::{Map, Value as JsonValue};
+
+pub const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+
+/// Format of the log lines written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines of `env_logger`
+    #[default]
+    Text,
+    /// One json object per line containing the level, target, message and the structured fields of the record
+    Json
+}
+
+impl LogFormat {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None
+        }
+    }
+}
+
+/// Initialize the logger using the format of the `LOG_FORMAT` environment variable and the filter of the
+/// `RUST_LOG` environment variable
+pub fn init_logger() {
+    let value = std::env::var(LOG_FORMAT_ENV).ok();
+    let format = value.as_deref().map(LogFormat::from_str).unwrap_or(Some(LogFormat::default()));
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("info"));
+    if format == Some(LogFormat::Json) {
+        builder.format(|buf, record| writeln!(buf, "{}", format_json(record)));
+    }
+    builder.init();
+    if format.is_none() {
+        warn!("Received invalid value '{}' for '{LOG_FORMAT_ENV}'. Expected 'text' or 'json'. Using default (text) instead", value.unwrap_or_default())
+    }
+}
+
+/// Format a record as json object. The structured fields of the record are added next to the message
+fn format_json(record: &Record) -> JsonValue {
+    let mut object = Map::new();
+    object.insert(String::from("timestamp"), JsonValue::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)));
+    object.insert(String::from("level"), JsonValue::from(record.level().as_str()));
+    object.insert(String::from("target"), JsonValue::from(record.target()));
+    object.insert(String::from("message"), JsonValue::from(record.args().to_string()));
+    let _ = record.key_values().visit(&mut FieldVisitor(&mut object));
+    JsonValue::Object(object)
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> Visitor<'kvs> for FieldVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = if let Some(value) = value.to_u64() {
+            JsonValue::from(value)
+        } else if let Some(value) = value.to_i64() {
+            JsonValue::from(value)
+        } else if let Some(value) = value.to_bool() {
+            JsonValue::from(value)
+        } else {
+            JsonValue::from(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::{Level, Record};
+    use serde_json::json;
+    use crate::logging::{format_json, LogFormat};
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(LogFormat::from_str("json"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::from_str(" Text "), Some(LogFormat::Text));
+        assert_eq!(LogFormat::from_str("logfmt"), None);
+    }
+
+    #[test]
+    fn test_format_json() {
+        let fields: &[(&str, &dyn log::kv::ToValue)] = &[("registry", &"registry"), ("deleted", &3u64), ("dry_run", &false)];
+        let mut line = format_json(&Record::builder()
+            .level(Level::Info)
+            .target("abwart::instance")
+            .args(format_args!("Deleted {} tags", 3))
+            .key_values(&fields)
+            .build());
+        assert!(line.as_object_mut().unwrap().remove("timestamp").is_some_and(|timestamp| timestamp.is_string()));
+        assert_eq!(line, json!({
+            "level": "INFO",
+            "target": "abwart::instance",
+            "message": "Deleted 3 tags",
+            "registry": "registry",
+            "deleted": 3,
+            "dry_run": false
+        }));
+    }
+}
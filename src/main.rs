@@ -1,3 +1,4 @@
+mod admin;
 mod instance;
 mod scheduler;
 mod error;
@@ -6,46 +7,95 @@ mod rule;
 mod api;
 mod policies;
 mod config;
+mod docker;
+mod dump;
+mod garbage_collector;
+mod kube;
+mod listener;
+mod logging;
+mod notify;
+mod order;
+mod plan;
+mod pulls;
+mod report;
+mod scan;
+mod state;
 #[cfg(test)]
 mod test;
 
 use bollard::container::ListContainersOptions;
 use bollard::system::EventsOptions;
-use bollard::{API_DEFAULT_VERSION, Docker};
+use bollard::Docker;
 use futures::StreamExt;
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use bollard::service::EventMessage;
+use clap::Parser;
 use log::{error, info, warn};
 use tokio::select;
+use tokio::signal::unix::{signal, SignalKind};
+use crate::admin::{ADMIN_ADDR_ENV, RunRequest, start_admin_server};
 use crate::config::{Config, watch_config};
+use crate::docker::{DOCKER_HOST_ENV, DockerEndpoint};
+use crate::dump::dump_registry;
 use crate::error::Error;
+use crate::garbage_collector::GarbageCollectorMode;
 use crate::instance::Instance;
-use crate::scheduler::{DescheduleReason, Scheduler, ScheduleReason};
+use crate::listener::{NOTIFY_PORT_ENV, PushEvent, start_listener};
+use crate::logging::init_logger;
+use crate::plan::PlanResource;
+use crate::report::RunReport;
+use crate::scheduler::{DescheduleReason, PROBE_ENV, RUN_ON_START_ENV, Scheduler, ScheduleReason};
+use crate::state::StateStore;
 
 pub const NAME: &str = "abwart";
+/// How long the runs in progress are awaited when abwart is asked to shut down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Compute the deletions of all rules once without deleting anything and write them as
+    /// cleanup plan to the given file. Use `-` to write to stdout
+    #[arg(long, value_name = "FILE", conflicts_with = "apply_plan")]
+    plan: Option<PathBuf>,
+
+    /// Delete all tags listed in a previously written cleanup plan and exit
+    #[arg(long, value_name = "FILE", conflicts_with = "dump")]
+    apply_plan: Option<PathBuf>,
+
+    /// Write the metadata of all tags in the given registry as json to stdout without deleting anything and exit
+    #[arg(long, value_name = "REGISTRY", conflicts_with = "plan")]
+    dump: Option<String>,
+
+    /// Run the garbage collector in the given registry without applying any rules and exit
+    #[arg(long, value_name = "REGISTRY", conflicts_with_all = ["plan", "apply_plan", "dump"])]
+    gc: Option<String>,
+
+    /// Only log the tags which would be deleted and the garbage collector runs which would happen without
+    /// deleting anything or running the garbage collector
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Apply the rules of every registry once when it's scheduled instead of waiting for the first tick of their
+    /// schedules. Can also be enabled using the `ABWART_RUN_ON_START` environment variable
+    #[arg(long)]
+    run_on_start: bool,
+
+    /// Check whether every registry is reachable using the api when it's scheduled and log the result. Can also be
+    /// enabled using the `ABWART_PROBE` environment variable
+    #[arg(long)]
+    probe: bool
+}
 
 #[tokio::main]
 async fn main() {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-
-    let docker: Arc<Docker>;
-    match Docker::connect_with_unix("/var/run/docker.sock", 30, API_DEFAULT_VERSION) {
-        Ok(client) => {
-            match client.ping().await {
-                Ok(_) => docker = Arc::new(client),
-                Err(_) => {
-                    error!("Ping to docker client failed");
-                    exit(1)
-                }
-            }
-        },
-        Err(err) => {
-            error!("Unable to connect to docker socket. Reason: {err}");
-            exit(1)
-        }
-    }
+    let args = Args::parse();
+    init_logger();
 
     let config = match Config::parse() {
         Ok(config) => {
@@ -56,62 +106,290 @@ async fn main() {
         },
         Err(err) => {
             error!("Error whilst parsing static configuration file. Reason: {err}");
-            Arc::new(Mutex::new(Config::default()))
+            exit(1)
+        }
+    };
+    let standalone = config.lock().map(|config| config.get_standalone_registries()).unwrap_or_default();
+
+    let endpoint = match DockerEndpoint::from_env() {
+        Ok(endpoint) => endpoint,
+        Err(err) => {
+            error!("Received invalid value for '{DOCKER_HOST_ENV}'. Reason: {err}");
+            exit(1)
+        }
+    };
+    let docker = match endpoint.connect() {
+        Ok(client) => Arc::new(client),
+        Err(err) => {
+            error!("Unable to connect to docker daemon at {endpoint}. Reason: {err}");
+            exit(1)
+        }
+    };
+    // standalone registries are reached using their host which allows running them without a docker daemon
+    let containers_available = match docker.ping().await {
+        Ok(_) => {
+            info!("Connected to docker daemon at {endpoint}");
+            true
+        },
+        Err(err) if !standalone.is_empty() => {
+            warn!("Ping to docker daemon at {endpoint} failed. Only using standalone registries. Reason: {err}");
+            false
+        },
+        Err(err) => {
+            error!("Ping to docker daemon at {endpoint} failed. Reason: {err}");
+            exit(1)
         }
     };
 
     let mut filters = HashMap::new();
     filters.insert(String::from("label"), vec![format!("{}=true", label("enable"))]);
 
-    let mut scheduler = Scheduler::new();
+    let state = Arc::new(StateStore::from_env());
+    let mut scheduler = Scheduler::new(state.clone())
+        .with_dry_run(args.dry_run)
+        .with_run_on_start(args.run_on_start || flag_from_env(RUN_ON_START_ENV))
+        .with_probe(args.probe || flag_from_env(PROBE_ENV));
 
     let options = ListContainersOptions {
         filters,
         ..ListContainersOptions::default()
     };
-    let containers = docker.list_containers(Some(options)).await
-        .map_err(|err| error!("Unable to get existing running registries. Reason: {err}"))
-        .unwrap_or_default();
+    let containers = if containers_available {
+        docker.list_containers(Some(options)).await
+            .map_err(|err| error!("Unable to get existing running registries. Reason: {err}"))
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
 
+    let mut instances = Vec::new();
     for container in containers {
         if !&container.image.clone().unwrap_or_default().starts_with("registry") {
             warn!("Potentially found running container which is enabled and doesn't use image 'registry'");
         }
         match Instance::from_container(container, docker.clone(), config.clone()) {
-            Ok(instance) => scheduler.schedule_instance(instance, ScheduleReason::RegistryRunning).await,
+            Ok(mut instance) => {
+                instance.dry_run = args.dry_run;
+                instances.push(instance)
+            },
             Err(err) => error!("Unable to add registry to schedule. Reason: {err}")
         }
     }
 
-    subscribe_events(docker, config, scheduler).await;
+    for (name, labels) in standalone {
+        match Instance::standalone(name, labels, docker.clone()) {
+            Ok(mut instance) => {
+                instance.dry_run = args.dry_run;
+                instances.push(instance)
+            },
+            Err(err) => error!("Unable to add standalone registry to schedule. Reason: {err}")
+        }
+    }
+
+    if let Some(path) = args.plan {
+        exit(write_plan(instances, path).await)
+    }
+    if let Some(path) = args.apply_plan {
+        exit(apply_plan(instances, path, &state).await)
+    }
+    if let Some(registry) = args.dump {
+        exit(dump(instances, registry).await)
+    }
+    if let Some(registry) = args.gc {
+        exit(collect_garbage(instances, registry).await)
+    }
+
+    for instance in instances {
+        scheduler.schedule_instance(instance, ScheduleReason::RegistryRunning).await
+    }
+
+    subscribe_events(docker, containers_available, config, scheduler).await;
+}
+
+/// Whether the boolean environment variable with the given name is set to `true`
+fn flag_from_env(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| value.parse::<bool>().unwrap_or_else(|_| {
+        warn!("Received invalid value '{value}' for '{name}'. Expected boolean. Using default (false) instead");
+        false
+    }))
+}
+
+/// Plan all rules of the given instances and write the plans as [`PlanResource`] to the given path.
+/// Returns the exit code of the program
+async fn write_plan(instances: Vec<Instance>, path: PathBuf) -> i32 {
+    let mut plans = Vec::new();
+    for mut instance in instances {
+        instance.load_config_artifact().await;
+        let rules = instance.rule_names();
+        match instance.plan_rules(rules, None, &mut RunReport::new(instance.name.clone())).await {
+            Ok(plan) => {
+                info!("Planned deletion of {} tags in registry '{}'", plan.tag_count(), instance.name);
+                plans.push(plan)
+            },
+            Err(err) => {
+                error!("Unable to plan rules of registry '{}'. Reason: {err}", instance.name);
+                return 1
+            }
+        }
+    }
+
+    let content = match PlanResource::new(plans).to_yaml() {
+        Ok(content) => content,
+        Err(err) => {
+            error!("{err}");
+            return 1
+        }
+    };
+    if path.as_os_str() == "-" {
+        print!("{content}");
+    } else if let Err(err) = std::fs::write(&path, content) {
+        error!("Unable to write cleanup plan to '{}'. Reason: {err}", path.display());
+        return 1
+    } else {
+        info!("Wrote cleanup plan to '{}'", path.display())
+    }
+    0
+}
+
+/// Write the inventory of the registry with the given name to stdout. Returns the exit code of the program
+async fn dump(instances: Vec<Instance>, registry: String) -> i32 {
+    let Some(instance) = instances.iter().find(|instance| instance.name == registry) else {
+        error!("Registry '{registry}' isn't running");
+        return 1
+    };
+    match dump_registry(&instance.distribution, &mut std::io::stdout().lock()).await {
+        Ok(count) => {
+            info!("Dumped {count} tags of registry '{registry}'");
+            0
+        },
+        Err(err) => {
+            error!("Unable to dump registry '{registry}'. Reason: {err}");
+            1
+        }
+    }
+}
+
+/// Run the garbage collector in the registry with the given name without applying any rules. Returns the exit
+/// code of the program
+async fn collect_garbage(instances: Vec<Instance>, registry: String) -> i32 {
+    let Some(instance) = instances.iter().find(|instance| instance.name == registry) else {
+        error!("Registry '{registry}' isn't running");
+        return 1
+    };
+    if instance.garbage_collector.mode == GarbageCollectorMode::Api {
+        error!("Registry '{registry}' reclaims blobs using the api which only happens after deleting tags. Use 'gc.mode=exec' to run the garbage collector on its own");
+        return 1
+    }
+    let start = Instant::now();
+    if instance.collect_garbage().await {
+        info!("Garbage collector in registry '{registry}' finished after {:?}", start.elapsed());
+        0
+    } else {
+        1
+    }
+}
+
+/// Delete all tags of the plan at the given path in the matching instances. Returns the exit code
+/// of the program
+async fn apply_plan(instances: Vec<Instance>, path: PathBuf, state: &StateStore) -> i32 {
+    let resource = match std::fs::read_to_string(&path).map_err(|err| err.to_string())
+        .and_then(|content| PlanResource::from_yaml(&content).map_err(|err| err.to_string())) {
+        Ok(resource) => resource,
+        Err(err) => {
+            error!("Unable to read cleanup plan from '{}'. Reason: {err}", path.display());
+            return 1
+        }
+    };
+
+    let mut code = 0;
+    for plan in resource.spec.registries {
+        let Some(instance) = instances.iter().find(|instance| instance.name == plan.registry) else {
+            error!("Cleanup plan contains registry '{}' which isn't running", plan.registry);
+            code = 1;
+            continue
+        };
+        let mut report = RunReport::new(instance.name.clone());
+        if let Err(err) = instance.apply_plan(&plan, state, &mut report).await {
+            error!("Unable to apply cleanup plan to registry '{}'. Reason: {err}", plan.registry);
+            code = 1
+        } else if !report.errors.is_empty() {
+            error!("Applied cleanup plan to registry '{}' with {} errors", plan.registry, report.errors.len());
+            code = 1
+        }
+    }
+    code
 }
 
-async fn subscribe_events(docker: Arc<Docker>, config: Arc<Mutex<Config>>, mut scheduler: Scheduler) {
+/// Handle the events of the docker daemon, the static configuration file, the registry notifications and the admin
+/// server until abwart is shut down. Without a reachable docker daemon only the other events are handled
+async fn subscribe_events(docker: Arc<Docker>, containers_available: bool, config: Arc<Mutex<Config>>, mut scheduler: Scheduler) {
     let mut filters = HashMap::new();
     filters.insert(String::from("label"), vec![format!("{}=true", label("enable"))]);
     filters.insert(String::from("type"), vec![String::from("container")]);
     let (tx, mut rx) = tokio::sync::mpsc::channel::<Config>(1);
+    let (push_tx, mut push_rx) = tokio::sync::mpsc::channel::<PushEvent>(16);
+    let (run_tx, mut run_rx) = tokio::sync::mpsc::channel::<RunRequest>(16);
 
     let options = EventsOptions::<String> {
         filters,
         ..EventsOptions::<String>::default()
     };
-    let mut events = docker.events(Some(options));
+    let mut events = if containers_available {
+        docker.events(Some(options)).boxed()
+    } else {
+        futures::stream::pending().boxed()
+    };
     if let Err(err) = watch_config(tx.clone()) {
         error!("Unable to watch config file at '{}'. Disabled static config hot reloading. Reason: {err}", Config::path())
     }
+    if let Ok(port) = std::env::var(NOTIFY_PORT_ENV) {
+        match port.parse::<u16>() {
+            Ok(port) => start_listener(port, push_tx),
+            Err(_) => error!("Received invalid notification port '{port}'. Disabled registry notifications")
+        }
+    }
+    if let Ok(address) = std::env::var(ADMIN_ADDR_ENV) {
+        match address.parse::<SocketAddr>() {
+            Ok(address) => start_admin_server(address, run_tx),
+            Err(_) => error!("Received invalid admin address '{address}'. Expected '<ip>:<port>'. Disabled admin server")
+        }
+    }
+
+    let (mut terminate, mut interrupt) = match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+        (Ok(terminate), Ok(interrupt)) => (terminate, interrupt),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("Unable to install signal handlers. Reason: {err}");
+            exit(1)
+        }
+    };
 
     loop {
         select! {
+            _ = terminate.recv() => {
+                info!("Received SIGTERM. Shutting down");
+                break
+            },
+            _ = interrupt.recv() => {
+                info!("Received SIGINT. Shutting down");
+                break
+            },
             Some(event) = events.next() => {
                 let result = handle_event(&event, &mut scheduler, docker.clone(), config.clone()).await;
                 if let Err(err) = result {
                     info!("{err}")
                 }
             },
-            Some(new_config) = rx.recv() => handle_config_update(&new_config, &mut scheduler, docker.clone(), config.clone()).await
+            Some(new_config) = rx.recv() => handle_config_update(&new_config, &mut scheduler, docker.clone(), config.clone()).await,
+            Some(event) = push_rx.recv() => scheduler.schedule_push(event),
+            Some(request) = run_rx.recv() => {
+                let scheduled = scheduler.trigger_run(&request.registry);
+                let _ = request.respond.send(scheduled);
+            }
         }
     };
+
+    scheduler.shutdown(SHUTDOWN_TIMEOUT).await;
+    info!("Shutdown complete");
 }
 
 async fn handle_event(event: &Result<EventMessage, bollard::errors::Error>, scheduler: &mut Scheduler, docker: Arc<Docker>, config: Arc<Mutex<Config>>) -> Result<(), String> {
@@ -141,16 +419,26 @@ async fn handle_event(event: &Result<EventMessage, bollard::errors::Error>, sche
 }
 
 async fn handle_config_update(new_config: &Config, scheduler: &mut Scheduler, docker: Arc<Docker>, config: Arc<Mutex<Config>>) {
-    let updatable = match config.lock() {
+    let (updatable, standalone) = match config.lock() {
         Ok(mut config) => {
             let new_registries = new_config.get_registries();
+            let old_standalone = config.get_standalone_registries();
             let updatable = config.get_registries().iter()
-                .filter(|(key, old_value)| new_registries.get(*key).map_or(true, |v| old_value.ne(&v)))
+                .filter(|(key, _)| !old_standalone.contains_key(*key))
+                .filter(|(key, old_value)| new_registries.get(*key).is_none_or(|v| old_value.ne(&v)))
                 .filter_map(|(key, _)| scheduler.get_instance(key))
                 .collect::<Vec<String>>();
+            // standalone registries which were added, changed or removed
+            let new_standalone = new_config.get_standalone_registries();
+            let mut standalone = old_standalone.keys().chain(new_standalone.keys())
+                .filter(|name| old_standalone.get(*name) != new_standalone.get(*name))
+                .map(|name| (name.clone(), new_standalone.get(name).cloned()))
+                .collect::<Vec<_>>();
+            standalone.sort_by(|(a, _), (b, _)| a.cmp(b));
+            standalone.dedup_by(|(a, _), (b, _)| a == b);
 
             *config = new_config.clone();
-            updatable
+            (updatable, standalone)
         }
         Err(err) => {
             error!("Unable to lock old config. Reason: {err}");
@@ -158,6 +446,18 @@ async fn handle_config_update(new_config: &Config, scheduler: &mut Scheduler, do
         }
     };
 
+    for (name, labels) in standalone {
+        if let Some(id) = scheduler.get_instance(&name) {
+            scheduler.deschedule_instance(id, DescheduleReason::ConfigUpdate).await;
+        }
+        if let Some(labels) = labels {
+            match Instance::standalone(name, labels, docker.clone()) {
+                Ok(instance) => scheduler.schedule_instance(instance, ScheduleReason::ConfigUpdate).await,
+                Err(err) => error!("Unable to add standalone registry to schedule. Reason: {err}")
+            }
+        }
+    }
+
     if updatable.is_empty() {
         info!("Received config update affecting no running instances")
     } else {
@@ -0,0 +1,91 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::report::{DeletedTag, RunReport};
+
+/// Maximum duration of a webhook request
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Payload which is sent to the webhook of a registry after each run
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunSummary<'a> {
+    pub registry: &'a str,
+    pub rules: &'a [String],
+    pub deleted: &'a [DeletedTag],
+    pub freed_bytes: u64,
+    pub errors: &'a [String],
+    pub dry_run: bool,
+    pub timestamp: DateTime<Utc>
+}
+
+impl<'a> RunSummary<'a> {
+    pub fn new(report: &'a RunReport, dry_run: bool) -> Self {
+        Self {
+            registry: &report.registry,
+            rules: &report.rules,
+            deleted: &report.deleted,
+            freed_bytes: report.freed_bytes(),
+            errors: &report.errors,
+            dry_run,
+            timestamp: Utc::now()
+        }
+    }
+}
+
+/// Post the summary of a run as json to the webhook url
+pub async fn send_summary(url: &str, summary: &RunSummary<'_>) -> Result<(), String> {
+    let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build().map_err(|err| err.to_string())?;
+    let response = client.post(url).json(summary).send().await.map_err(|err| err.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Webhook responded with status {}", response.status()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mockito::Matcher;
+    use serde_json::json;
+    use crate::plan::PlannedTag;
+    use crate::report::RunReport;
+    use crate::notify::{RunSummary, send_summary};
+
+    fn get_report() -> RunReport {
+        let mut report = RunReport::new(String::from("registry"));
+        report.rules = vec![String::from("dev")];
+        report.record_deletion("app", &PlannedTag { name: String::from("dev"), digest: String::from("sha256:dev"), size: 10, created: None, rule: Some(String::from("dev")), reason: String::from("rule 'dev'") });
+        report.record_error("Unable to get tags of repository 'api'");
+        report
+    }
+
+    #[tokio::test]
+    async fn test_send_summary() {
+        let mut server = mockito::Server::new_async().await;
+        let hook = server.mock("POST", "/hook")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::PartialJson(json!({
+                "registry": "registry",
+                "rules": ["dev"],
+                "deleted": [{ "repository": "app", "name": "dev", "digest": "sha256:dev", "size": 10, "reason": "rule 'dev'" }],
+                "freed_bytes": 10,
+                "errors": ["Unable to get tags of repository 'api'"],
+                "dry_run": false
+            })))
+            .with_status(204)
+            .create_async().await;
+
+        let report = get_report();
+        assert!(send_summary(&format!("{}/hook", server.url()), &RunSummary::new(&report, false)).await.is_ok());
+        hook.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_summary() {
+        let mut server = mockito::Server::new_async().await;
+        let _hook = server.mock("POST", "/hook").with_status(500).create_async().await;
+
+        let report = get_report();
+        assert!(send_summary(&format!("{}/hook", server.url()), &RunSummary::new(&report, true)).await.is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use log::{debug, warn};
+use tokio::sync::Notify;
+use tokio::time::sleep;
+
+/// Per default runs which start within 2 seconds of each other are considered co-scheduled
+pub const DEFAULT_ORDER_WINDOW: Duration = Duration::from_secs(2);
+
+/// Orders the co-scheduled runs of registries according to their `after` constraints. A run of a registry
+/// waits until the runs of all registries it should run after are finished
+#[derive(Debug)]
+pub struct RunOrder {
+    /// Time a run waits for the runs of its dependencies to start
+    window: Duration,
+    state: Mutex<OrderState>,
+    notify: Notify
+}
+
+#[derive(Debug, Default)]
+struct OrderState {
+    dependencies: HashMap<String, Vec<String>>,
+    /// Amount of pending or running runs per registry
+    active: HashMap<String, usize>
+}
+
+impl OrderState {
+    /// Whether `to` can be reached from `from` by following the dependencies
+    fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut stack = vec![from];
+        let mut visited = vec![];
+        while let Some(current) = stack.pop() {
+            if current == to {
+                return true
+            }
+            if visited.contains(&current) {
+                continue
+            }
+            visited.push(current);
+            if let Some(dependencies) = self.dependencies.get(current) {
+                stack.extend(dependencies.iter().map(String::as_str))
+            }
+        }
+        false
+    }
+}
+
+/// Marks a run as finished once dropped
+struct ActiveRun<'a> {
+    order: &'a RunOrder,
+    name: &'a str
+}
+
+impl Drop for ActiveRun<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.order.state.lock() {
+            if let Some(count) = state.active.get_mut(self.name) {
+                *count = count.saturating_sub(1)
+            }
+        }
+        self.order.notify.notify_waiters()
+    }
+}
+
+impl RunOrder {
+    pub fn new(window: Duration) -> Self {
+        Self { window, state: Mutex::new(OrderState::default()), notify: Notify::new() }
+    }
+
+    /// Register the registries after which the runs of a registry should happen. Constraints which would
+    /// result in a cycle are ignored
+    pub fn register(&self, name: &str, after: &[String]) {
+        let Ok(mut state) = self.state.lock() else {
+            return
+        };
+        state.dependencies.remove(name);
+        let mut dependencies = vec![];
+        for dependency in after {
+            if state.reaches(dependency, name) {
+                warn!("Ordering registry '{name}' after '{dependency}' would result in a cycle. Ignoring constraint");
+                continue
+            }
+            dependencies.push(dependency.clone());
+        }
+        state.dependencies.insert(name.to_string(), dependencies);
+    }
+
+    pub fn unregister(&self, name: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.dependencies.remove(name);
+        }
+    }
+
+    /// Run the future once the co-scheduled runs of all registries the registry should run after are finished
+    pub async fn run<F: Future>(&self, name: &str, run: F) -> F::Output {
+        let dependencies = match self.state.lock() {
+            Ok(mut state) => {
+                *state.active.entry(name.to_string()).or_default() += 1;
+                state.dependencies.get(name).cloned().unwrap_or_default()
+            },
+            Err(_) => vec![]
+        };
+        let _active = ActiveRun { order: self, name };
+
+        if !dependencies.is_empty() {
+            // give the runs of the dependencies which were scheduled at the same time a chance to start
+            sleep(self.window).await;
+            loop {
+                let notified = self.notify.notified();
+                let waiting = self.state.lock().map(|state| {
+                    dependencies.iter().any(|dependency| state.active.get(dependency).is_some_and(|count| *count > 0))
+                }).unwrap_or(false);
+                if !waiting {
+                    break
+                }
+                debug!("Waiting for the runs of '{}' to finish before running registry '{name}'", dependencies.join(", "));
+                notified.await
+            }
+        }
+
+        run.await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::sleep;
+    use crate::order::RunOrder;
+
+    async fn run_all(order: Arc<RunOrder>, names: Vec<&'static str>) -> Vec<&'static str> {
+        let finished = Arc::new(Mutex::new(vec![]));
+        let handles = names.into_iter().map(|name| {
+            let order = order.clone();
+            let finished = finished.clone();
+            tokio::spawn(async move {
+                order.run(name, async {
+                    sleep(Duration::from_millis(50)).await;
+                    finished.lock().unwrap().push(name)
+                }).await
+            })
+        }).collect::<Vec<_>>();
+        for handle in handles {
+            handle.await.unwrap()
+        }
+        let finished = finished.lock().unwrap().clone();
+        finished
+    }
+
+    #[tokio::test]
+    async fn test_ordering() {
+        let order = Arc::new(RunOrder::new(Duration::from_millis(20)));
+        order.register("base", &[String::from("app")]);
+        order.register("app", &[]);
+        assert_eq!(run_all(order, vec!["base", "app"]).await, vec!["app", "base"]);
+    }
+
+    #[tokio::test]
+    async fn test_cycle() {
+        let order = Arc::new(RunOrder::new(Duration::from_millis(20)));
+        order.register("base", &[String::from("app")]);
+        order.register("app", &[String::from("base")]);
+        let state = order.state.lock().unwrap();
+        assert_eq!(state.dependencies.get("base").unwrap(), &vec![String::from("app")]);
+        assert!(state.dependencies.get("app").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_without_dependency_run() {
+        let order = Arc::new(RunOrder::new(Duration::from_millis(20)));
+        order.register("base", &[String::from("app")]);
+        assert_eq!(run_all(order, vec!["base"]).await, vec!["base"]);
+    }
+}
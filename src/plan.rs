@@ -0,0 +1,399 @@
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::api::tag::Tag;
+use crate::error::Error;
+
+pub const PLAN_API_VERSION: &str = "abwart/v1alpha1";
+pub const PLAN_KIND: &str = "AbwartCleanupPlan";
+const PLAN_NAME: &str = "cleanup-plan";
+/// Version of the dry run output format which is increased on breaking changes
+pub const DRY_RUN_OUTPUT_VERSION: u32 = 1;
+
+/// All deletions which would be made in a registry by applying its rules
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CleanupPlan {
+    pub registry: String,
+    /// Whether the garbage collector should be run after the deletions
+    #[serde(default)]
+    pub tidy: bool,
+    #[serde(default)]
+    pub repositories: Vec<RepositoryPlan>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepositoryPlan {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<PlannedTag>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedTag {
+    pub name: String,
+    pub digest: String,
+    /// Compressed size of the tag in bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Creation time of the tag. Not available for plans written before it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// Name of the rule which planned the deletion. Tags planned due to the scan report don't have a rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    pub reason: String
+}
+
+impl CleanupPlan {
+    pub fn new(registry: String) -> Self {
+        Self { registry, tidy: false, repositories: vec![] }
+    }
+
+    /// Add a tag of a repository which was planned by the given rule to the plan
+    pub fn add(&mut self, repository: &str, tag: &Tag, rule: Option<&str>, reason: String) {
+        let planned = PlannedTag {
+            name: tag.name.clone(), digest: tag.digest.clone(), size: tag.size, created: Some(tag.created), rule: rule.map(str::to_string), reason
+        };
+        match self.repositories.iter_mut().find(|repo| repo.name == repository) {
+            Some(repo) => repo.tags.push(planned),
+            None => self.repositories.push(RepositoryPlan { name: repository.to_string(), tags: vec![planned] })
+        }
+    }
+
+    /// Total amount of tags in the plan
+    pub fn tag_count(&self) -> usize {
+        self.repositories.iter().map(|repo| repo.tags.len()).sum()
+    }
+
+    /// Sort the repositories and tags by their name to get a stable output
+    pub fn sort(&mut self) {
+        self.repositories.retain(|repo| !repo.tags.is_empty());
+        self.repositories.sort_by(|a, b| a.name.cmp(&b.name));
+        for repository in &mut self.repositories {
+            repository.tags.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.digest.cmp(&b.digest)))
+        }
+    }
+}
+
+/// Upper bound of the bytes which are selected for deletion in a single run. Tags sharing their
+/// digest with an already selected tag don't count against the budget since deleting the manifest
+/// only frees its blobs once
+#[derive(Debug, Clone, Default)]
+pub struct DeleteBudget {
+    max: u64,
+    used: u64,
+    digests: HashSet<String>,
+    exhausted: bool
+}
+
+impl DeleteBudget {
+    pub fn new(max: u64) -> Self {
+        Self { max, ..Self::default() }
+    }
+
+    /// Select the tag for deletion when it still fits into the budget. Once a tag doesn't fit the
+    /// budget is exhausted and no further tags are selected
+    pub fn admit(&mut self, tag: &Tag) -> bool {
+        if self.exhausted {
+            return false
+        }
+        if self.digests.contains(&tag.digest) {
+            return true
+        }
+        if self.used + tag.size > self.max {
+            self.exhausted = true;
+            return false
+        }
+        self.used += tag.size;
+        self.digests.insert(tag.digest.clone());
+        true
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+}
+
+/// Machine-readable summary of the deletions a dry run would make in a registry. The format is documented
+/// and only changed together with [`DRY_RUN_OUTPUT_VERSION`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunOutput {
+    pub version: u32,
+    pub registry: String,
+    pub generated_at: DateTime<Utc>,
+    pub total_tags: usize,
+    /// Bytes which would be freed. Tags sharing their digest are only counted once
+    pub total_bytes: u64,
+    pub repositories: Vec<DryRunRepository>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunRepository {
+    pub name: String,
+    pub total_tags: usize,
+    pub total_bytes: u64,
+    pub tags: Vec<DryRunTag>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunTag {
+    pub name: String,
+    pub digest: String,
+    pub size: u64,
+    pub created: Option<DateTime<Utc>>,
+    /// Age of the tag in seconds at the time the output was generated
+    pub age_seconds: Option<i64>,
+    pub reason: String
+}
+
+impl DryRunOutput {
+    pub fn new(plan: &CleanupPlan, now: DateTime<Utc>) -> Self {
+        let mut plan = plan.clone();
+        plan.sort();
+        let repositories = plan.repositories.into_iter().map(|repository| {
+            let mut digests = HashSet::new();
+            let total_bytes = repository.tags.iter().filter(|tag| digests.insert(tag.digest.clone())).map(|tag| tag.size).sum();
+            let tags = repository.tags.into_iter().map(|tag| DryRunTag {
+                age_seconds: tag.created.map(|created| (now - created).num_seconds()),
+                name: tag.name,
+                digest: tag.digest,
+                size: tag.size,
+                created: tag.created,
+                reason: tag.reason
+            }).collect::<Vec<_>>();
+            DryRunRepository { name: repository.name, total_tags: tags.len(), total_bytes, tags }
+        }).collect::<Vec<_>>();
+        Self {
+            version: DRY_RUN_OUTPUT_VERSION,
+            registry: plan.registry,
+            generated_at: now,
+            total_tags: repositories.iter().map(|repository| repository.total_tags).sum(),
+            total_bytes: repositories.iter().map(|repository| repository.total_bytes).sum(),
+            repositories
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::InvalidPlan(err.to_string()))
+    }
+}
+
+/// Cleanup plans of multiple registries in the shape of a kubernetes resource. This allows
+/// committing the plan for review before applying it with `--apply-plan`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanResource {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: PlanMetadata,
+    pub spec: PlanSpec
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanMetadata {
+    pub name: String
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlanSpec {
+    #[serde(default)]
+    pub registries: Vec<CleanupPlan>
+}
+
+impl PlanResource {
+    pub fn new(mut plans: Vec<CleanupPlan>) -> Self {
+        plans.iter_mut().for_each(CleanupPlan::sort);
+        plans.sort_by(|a, b| a.registry.cmp(&b.registry));
+        Self {
+            api_version: String::from(PLAN_API_VERSION),
+            kind: String::from(PLAN_KIND),
+            metadata: PlanMetadata { name: String::from(PLAN_NAME) },
+            spec: PlanSpec { registries: plans }
+        }
+    }
+
+    pub fn to_yaml(&self) -> Result<String, Error> {
+        serde_yaml::to_string(self).map_err(|err| Error::InvalidPlan(err.to_string()))
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, Error> {
+        let resource = serde_yaml::from_str::<Self>(content).map_err(|err| Error::InvalidPlan(err.to_string()))?;
+        if resource.kind != PLAN_KIND {
+            return Err(Error::InvalidPlan(format!("Expected kind '{PLAN_KIND}' but received '{}'", resource.kind)))
+        }
+        if resource.api_version != PLAN_API_VERSION {
+            return Err(Error::InvalidPlan(format!("Unsupported api version '{}'", resource.api_version)))
+        }
+        Ok(resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Duration, Utc};
+    use crate::api::tag::Tag;
+    use crate::plan::{CleanupPlan, DeleteBudget, DryRunOutput, PlanResource};
+
+    fn get_created() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn get_plans() -> Vec<CleanupPlan> {
+        let tags = [("latest", "sha256:a"), ("v1", "sha256:b"), ("v2", "sha256:c")]
+            .map(|(name, digest)| Tag::new(name.to_string(), digest.to_string(), get_created(), 1));
+        let mut second = CleanupPlan::new(String::from("registry-b"));
+        second.add("app", &tags[2], Some("old"), String::from("rule 'old'"));
+        second.add("app", &tags[1], Some("old"), String::from("rule 'old'"));
+        second.add("api", &tags[0], Some("all"), String::from("rule 'all'"));
+        second.tidy = true;
+        let first = CleanupPlan::new(String::from("registry-a"));
+        vec![second, first]
+    }
+
+    #[test]
+    fn test_plan_yaml() {
+        let resource = PlanResource::new(get_plans());
+        let expected = r#"apiVersion: abwart/v1alpha1
+kind: AbwartCleanupPlan
+metadata:
+  name: cleanup-plan
+spec:
+  registries:
+  - registry: registry-a
+    tidy: false
+    repositories: []
+  - registry: registry-b
+    tidy: true
+    repositories:
+    - name: api
+      tags:
+      - name: latest
+        digest: sha256:a
+        size: 1
+        created: 2024-01-01T00:00:00Z
+        rule: all
+        reason: rule 'all'
+    - name: app
+      tags:
+      - name: v1
+        digest: sha256:b
+        size: 1
+        created: 2024-01-01T00:00:00Z
+        rule: old
+        reason: rule 'old'
+      - name: v2
+        digest: sha256:c
+        size: 1
+        created: 2024-01-01T00:00:00Z
+        rule: old
+        reason: rule 'old'
+"#;
+        assert_eq!(resource.to_yaml().unwrap(), expected);
+        assert_eq!(PlanResource::from_yaml(expected).unwrap(), resource);
+    }
+
+    #[test]
+    fn test_plan_stable_order() {
+        let mut reversed = get_plans();
+        reversed.reverse();
+        reversed.iter_mut().for_each(|plan| plan.repositories.reverse());
+        assert_eq!(PlanResource::new(reversed).to_yaml().unwrap(), PlanResource::new(get_plans()).to_yaml().unwrap());
+    }
+
+    #[test]
+    fn test_plan_invalid_kind() {
+        let content = "apiVersion: abwart/v1alpha1\nkind: Deployment\nmetadata:\n  name: test\nspec: {}\n";
+        assert!(PlanResource::from_yaml(content).is_err());
+        let content = "apiVersion: abwart/v1alpha1\nkind: AbwartCleanupPlan\nmetadata:\n  name: test\nspec: {}\n";
+        assert!(PlanResource::from_yaml(content).unwrap().spec.registries.is_empty());
+    }
+
+    #[test]
+    fn test_plan_without_created() {
+        let content = "apiVersion: abwart/v1alpha1\nkind: AbwartCleanupPlan\nmetadata:\n  name: test\nspec:\n  registries:\n  - registry: registry\n    repositories:\n    - name: app\n      tags:\n      - name: v1\n        digest: sha256:a\n        reason: rule 'old'\n";
+        let resource = PlanResource::from_yaml(content).unwrap();
+        assert_eq!(resource.spec.registries[0].repositories[0].tags[0].created, None);
+    }
+
+    #[test]
+    fn test_dry_run_output() {
+        let mut plan = get_plans().remove(0);
+        plan.add("app", &Tag::new(String::from("latest"), String::from("sha256:c"), get_created(), 1), Some("old"), String::from("rule 'old'"));
+        let output = DryRunOutput::new(&plan, get_created() + Duration::days(2));
+        let expected = r#"{
+  "version": 1,
+  "registry": "registry-b",
+  "generated_at": "2024-01-03T00:00:00Z",
+  "total_tags": 4,
+  "total_bytes": 3,
+  "repositories": [
+    {
+      "name": "api",
+      "total_tags": 1,
+      "total_bytes": 1,
+      "tags": [
+        {
+          "name": "latest",
+          "digest": "sha256:a",
+          "size": 1,
+          "created": "2024-01-01T00:00:00Z",
+          "age_seconds": 172800,
+          "reason": "rule 'all'"
+        }
+      ]
+    },
+    {
+      "name": "app",
+      "total_tags": 3,
+      "total_bytes": 2,
+      "tags": [
+        {
+          "name": "latest",
+          "digest": "sha256:c",
+          "size": 1,
+          "created": "2024-01-01T00:00:00Z",
+          "age_seconds": 172800,
+          "reason": "rule 'old'"
+        },
+        {
+          "name": "v1",
+          "digest": "sha256:b",
+          "size": 1,
+          "created": "2024-01-01T00:00:00Z",
+          "age_seconds": 172800,
+          "reason": "rule 'old'"
+        },
+        {
+          "name": "v2",
+          "digest": "sha256:c",
+          "size": 1,
+          "created": "2024-01-01T00:00:00Z",
+          "age_seconds": 172800,
+          "reason": "rule 'old'"
+        }
+      ]
+    }
+  ]
+}"#;
+        assert_eq!(output.to_json().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_delete_budget() {
+        let tag = |name: &str, digest: &str, size: u64| Tag::new(name.to_string(), digest.to_string(), Utc::now(), size);
+        let mut budget = DeleteBudget::new(100);
+        assert!(budget.admit(&tag("v1", "sha256:a", 60)));
+        // shares the digest with the first tag and therefore frees no additional bytes
+        assert!(budget.admit(&tag("latest", "sha256:a", 60)));
+        assert!(budget.admit(&tag("v2", "sha256:b", 40)));
+        assert!(!budget.is_exhausted());
+        assert!(!budget.admit(&tag("v3", "sha256:c", 1)));
+        assert!(budget.is_exhausted());
+        assert!(!budget.admit(&tag("v1", "sha256:a", 60)));
+        assert_eq!(budget.used(), 100);
+    }
+}
@@ -1,7 +1,7 @@
 use chrono::{Duration, Utc};
 use log::info;
 use crate::api::tag::Tag;
-use crate::policies::{AffectionType, parse_duration, Policy};
+use crate::policies::{AffectionType, format_duration, parse_duration, Policy};
 
 pub const AGE_MAX_LABEL: &str = "age.max";
 
@@ -53,6 +53,11 @@ impl Policy<Tag> for AgeMaxPolicy {
     fn enabled(&self) -> bool {
         self.age.is_some()
     }
+
+    fn reason(&self, tag: &Tag) -> String {
+        let age = self.age.map(format_duration).unwrap_or_default();
+        format!("{AGE_MAX_LABEL}: {} > {age}", format_duration(Utc::now() - tag.created))
+    }
 }
 
 #[cfg(test)]
@@ -97,4 +102,12 @@ mod test {
         assert!(policy.age.is_none());
         assert_eq!(policy.affects(tags), vec![])
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_reason() {
+        let tags = get_current_tags();
+        let policy = AgeMaxPolicy { age: Some(Duration::minutes(10)) };
+        assert_eq!(policy.reason(&tags[0]), "age.max: 5h > 10m");
+        assert_eq!(policy.reason(&tags[2]), "age.max: 30m > 10m");
+    }
+}
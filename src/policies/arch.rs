@@ -0,0 +1,100 @@
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const ARCH_LABEL: &str = "arch";
+
+/// Policy to match all multi-arch tags which contain a platform outside the allowed platforms.
+/// The platforms are separated by a comma and are in the `<os>/<architecture>[/<variant>]` format.
+/// Single-arch tags are never matched since their platform isn't known
+/// # Example
+/// ```
+/// let policy = ArchPolicy::new("linux/amd64,linux/arm64");
+///
+/// // returns all multi-arch tags which contain a platform other than `linux/amd64` or `linux/arm64`
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArchPolicy {
+    allowed: Vec<String>
+}
+
+impl ArchPolicy {
+    pub fn new(value: &str) -> Self {
+        let allowed = value.split(',').map(|platform| platform.trim().to_string()).filter(|platform| !platform.is_empty()).collect();
+        Self { allowed }
+    }
+
+    fn is_allowed(&self, platform: &str) -> bool {
+        self.allowed.iter().any(|allowed| platform_matches(platform, allowed))
+    }
+}
+
+/// Whether a platform matches an allowed platform. When the allowed platform doesn't contain a variant any
+/// variant of the platform matches
+fn platform_matches(platform: &str, allowed: &str) -> bool {
+    let platform = platform.split('/').collect::<Vec<_>>();
+    let allowed = allowed.split('/').collect::<Vec<_>>();
+    allowed.len() >= 2 && allowed.len() <= platform.len() && allowed.iter().zip(&platform).all(|(allowed, platform)| allowed == platform)
+}
+
+impl Policy<Tag> for ArchPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if !self.enabled() {
+            return vec![]
+        }
+        elements.into_iter().filter(|tag| tag.platforms.iter().any(|platform| !self.is_allowed(platform))).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        ARCH_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        !self.allowed.is_empty()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        let stray = tag.platforms.iter().filter(|platform| !self.is_allowed(platform)).cloned().collect::<Vec<_>>();
+        format!("{ARCH_LABEL}: {} not in {}", stray.join(","), self.allowed.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::arch::ArchPolicy;
+    use crate::test::get_tags_by_name;
+
+    fn platforms(raw: Vec<&str>) -> Vec<String> {
+        raw.into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    pub fn test_stray_platforms() {
+        let tags = get_tags_by_name(vec!["single", "allowed", "stray", "variant"], Duration::seconds(-1), 1);
+        let tags = vec![
+            tags[0].clone(),
+            tags[1].clone().with_platforms(platforms(vec!["linux/amd64", "linux/arm64/v8"])),
+            tags[2].clone().with_platforms(platforms(vec!["linux/amd64", "linux/s390x"])),
+            tags[3].clone().with_platforms(platforms(vec!["linux/arm/v7"]))
+        ];
+        let policy = ArchPolicy::new("linux/amd64, linux/arm64,linux/arm/v6");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[2].clone(), tags[3].clone()]);
+        assert_eq!(policy.reason(&tags[2]), "arch: linux/s390x not in linux/amd64,linux/arm64,linux/arm/v6");
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let tags = get_tags_by_name(vec!["stray"], Duration::seconds(-1), 1);
+        let tags = vec![tags[0].clone().with_platforms(platforms(vec!["linux/s390x"]))];
+        let policy = ArchPolicy::new("");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer};
+
+pub const BASE_IMAGE_LABEL: &str = "keep.base";
+
+/// Policy to protect the newest tags of every base image in a repository
+///
+/// The tags are grouped by a fingerprint of their base image which is the digest of their bottom-most layer.
+/// Images built from the same base image share their lower layers whereby the bottom-most layer identifies the
+/// base image most reliably. Tags without any known layers aren't grouped and therefore never protected
+/// # Example
+/// ```
+/// let policy = BaseImagePolicy::new(String::from("2"));
+///
+/// // returns the two newest tags of every group of tags sharing the same base layer
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BaseImagePolicy {
+    revisions: Option<usize>
+}
+
+impl BaseImagePolicy {
+    pub fn new(value: String) -> Self {
+        if value.is_empty() {
+            return Self { revisions: None }
+        }
+        match parse_integer(value.clone()) {
+            Some(revisions) if revisions > 0 => Self { revisions: Some(revisions as usize) },
+            _ => {
+                info!("Received invalid base image revisions value '{value}'. Expected non-zero positive integer");
+                Self { revisions: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for BaseImagePolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if let Some(revisions) = self.revisions {
+            let mut groups = HashMap::<String, Vec<Tag>>::new();
+            elements.into_iter()
+                .filter_map(|tag| tag.layers.first().cloned().map(|fingerprint| (fingerprint, tag)))
+                .for_each(|(fingerprint, tag)| groups.entry(fingerprint).or_default().push(tag));
+
+            groups.into_values()
+                .flat_map(|mut tags| {
+                    tags.sort_by_key(|tag| tag.created);
+                    tags.into_iter().rev().take(revisions)
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        BASE_IMAGE_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.revisions.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::api::tag::Tag;
+    use crate::policies::base_image::BaseImagePolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags;
+
+    fn get_current_tags() -> Vec<Tag> {
+        let layers = [
+            vec!["sha256:alpine", "sha256:app-1"],
+            vec!["sha256:debian", "sha256:app-2"],
+            vec!["sha256:alpine", "sha256:app-3"],
+            vec!["sha256:alpine", "sha256:app-4"],
+            vec!["sha256:debian", "sha256:app-5"],
+            vec![]
+        ];
+        get_tags(vec![
+            ("first", Duration::hours(-5), 1_000_000),
+            ("second", Duration::hours(-4), 1_000_000),
+            ("third", Duration::hours(-3), 1_000_000),
+            ("fourth", Duration::hours(-2), 1_000_000),
+            ("fifth", Duration::hours(-1), 1_000_000),
+            ("sixth", Duration::minutes(-1), 1_000_000)
+        ]).into_iter().zip(layers).map(|(tag, layers)| tag.with_layers(layers.into_iter().map(String::from).collect())).collect()
+    }
+
+    fn sorted(mut tags: Vec<Tag>) -> Vec<Tag> {
+        tags.sort_by_key(|tag| tag.created);
+        tags
+    }
+
+    #[test]
+    pub fn test_keeping_one() {
+        let tags = get_current_tags();
+        let policy = BaseImagePolicy::new(String::from("1"));
+        assert!(policy.revisions.is_some());
+        assert_eq!(sorted(policy.affects(tags.clone())), vec![tags[3].clone(), tags[4].clone()])
+    }
+
+    #[test]
+    pub fn test_keeping_two() {
+        let tags = get_current_tags();
+        let policy = BaseImagePolicy::new(String::from("2"));
+        assert_eq!(sorted(policy.affects(tags.clone())), vec![tags[1].clone(), tags[2].clone(), tags[3].clone(), tags[4].clone()])
+    }
+
+    #[test]
+    pub fn test_not_sharing_base() {
+        let tags = get_tags(vec![
+            ("first", Duration::hours(-2), 1),
+            ("second", Duration::hours(-1), 1)
+        ]).into_iter().enumerate().map(|(i, tag)| tag.with_layers(vec![format!("sha256:base-{i}")])).collect::<Vec<_>>();
+        let policy = BaseImagePolicy::new(String::from("1"));
+        assert_eq!(sorted(policy.affects(tags.clone())), tags)
+    }
+
+    #[test]
+    pub fn test_invalid_integer() {
+        let tags = get_current_tags();
+        let policy = BaseImagePolicy::new(String::from("asdf"));
+        assert!(policy.revisions.is_none());
+        assert_eq!(policy.affects(tags), vec![])
+    }
+}
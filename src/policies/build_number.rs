@@ -0,0 +1,126 @@
+use log::info;
+use regex::Regex;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer};
+
+pub const BUILD_NUMBER_LABEL: &str = "build-number.keep";
+pub const BUILD_NUMBER_PATTERN_LABEL: &str = "build-number.pattern";
+/// Per default the build number is the integer at the end of the tag name
+const DEFAULT_BUILD_NUMBER_PATTERN: &str = "(\\d+)$";
+
+/// Policy to match all tags except the ones with the highest build numbers. The build number
+/// is read from the first capture group of the pattern and compared numerically which keeps
+/// e.g. `release-1000` above `release-999`. Tags without a build number are never matched
+/// # Example
+/// ```
+/// let policy = BuildNumberPolicy::new("5", None);
+///
+/// // returns all tags with a trailing build number except the 5
+/// // tags with the highest build number
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BuildNumberPolicy {
+    keep: Option<usize>,
+    pattern: Regex
+}
+
+impl BuildNumberPolicy {
+    pub fn new(value: String, pattern: Option<String>) -> Self {
+        let default_pattern = || Regex::new(DEFAULT_BUILD_NUMBER_PATTERN).expect("Default regex should compile");
+        let pattern = match pattern {
+            Some(pattern) => Regex::new(&pattern).unwrap_or_else(|err| {
+                info!("Received invalid build number pattern '{pattern}'. Using default ({DEFAULT_BUILD_NUMBER_PATTERN}) instead. Reason: {err}");
+                default_pattern()
+            }),
+            None => default_pattern()
+        };
+        match parse_integer(value.clone()) {
+            Some(keep) => Self { keep: Some(keep as usize), pattern },
+            None => {
+                info!("Received invalid build number keep value '{value}'. Expected positive integer");
+                Self { keep: None, pattern }
+            }
+        }
+    }
+
+    /// Get the build number of a tag using the first capture group of the pattern or the whole match
+    /// when the pattern doesn't contain any capture group
+    fn build_number(&self, tag: &Tag) -> Option<u64> {
+        let captures = self.pattern.captures(&tag.name)?;
+        captures.get(1).or(captures.get(0))?.as_str().parse::<u64>().ok()
+    }
+}
+
+impl Policy<Tag> for BuildNumberPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let Some(keep) = self.keep else {
+            return vec![]
+        };
+        let mut numbered = elements.into_iter()
+            .filter_map(|tag| self.build_number(&tag).map(|number| (number, tag)))
+            .collect::<Vec<_>>();
+        numbered.sort_by_key(|(number, tag)| (*number, tag.created));
+        let length = numbered.len();
+        numbered.into_iter().take(length.saturating_sub(keep)).map(|(_, tag)| tag).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        BUILD_NUMBER_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.keep.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        let number = self.build_number(tag).map(|number| number.to_string()).unwrap_or_default();
+        format!("{BUILD_NUMBER_LABEL}: build {number} isn't within the highest {}", self.keep.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::build_number::BuildNumberPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_rollover() {
+        let tags = get_tags_by_name(vec!["release-998", "release-1000", "release-999", "release-1001"], Duration::seconds(1), 1);
+        let policy = BuildNumberPolicy::new(String::from("2"), None);
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_without_build_number() {
+        let tags = get_tags_by_name(vec!["release-1", "latest", "release-2", "main"], Duration::seconds(1), 1);
+        let policy = BuildNumberPolicy::new(String::from("1"), None);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+        let policy = BuildNumberPolicy::new(String::from("5"), None);
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_custom_pattern() {
+        let tags = get_tags_by_name(vec!["build-9-amd64", "build-10-amd64", "release-1", "build-11"], Duration::seconds(1), 1);
+        let policy = BuildNumberPolicy::new(String::from("1"), Some(String::from("^build-(\\d+)-amd64$")));
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    pub fn test_invalid_values() {
+        let tags = get_tags_by_name(vec!["release-1", "release-2"], Duration::seconds(1), 1);
+        let policy = BuildNumberPolicy::new(String::from("abc"), None);
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![]);
+        let policy = BuildNumberPolicy::new(String::from("1"), Some(String::from("(\\d+")));
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::cmp::Ordering;
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, parse_size, Policy};
+
+pub const CAP_SIZE_LABEL: &str = "cap.size";
+
+/// Policy to match the oldest tags of a repository until the cumulative blob size of the remaining
+/// tags is within a given cap. Tags are accumulated from newest to oldest and every tag which pushes
+/// the total over the cap is matched. Tags with the same creation date are ordered by their name
+/// where the lexicographically greater name is considered newer
+/// # Example
+/// ```
+/// let policy = CapSizePolicy::new("10 GiB");
+///
+/// // returns the oldest tags which exceed the cap of 10 GiB
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CapSizePolicy {
+    cap: Option<u64>
+}
+
+impl CapSizePolicy {
+    pub fn new(value: &str) -> Self {
+        if value.is_empty() {
+            Self { cap: None }
+        } else {
+            let cap = parse_size(value);
+            if cap.is_none() {
+                info!("Received invalid size cap '{value}'")
+            }
+            Self { cap }
+        }
+    }
+}
+
+/// Order tags from newest to oldest
+fn newest_first(t1: &Tag, t2: &Tag) -> Ordering {
+    t2.created.cmp(&t1.created).then_with(|| t2.name.cmp(&t1.name))
+}
+
+impl Policy<Tag> for CapSizePolicy {
+    fn affects(&self, mut elements: Vec<Tag>) -> Vec<Tag> {
+        let Some(cap) = self.cap else {
+            return vec![]
+        };
+        elements.sort_by(newest_first);
+        let mut total = 0u64;
+        elements.into_iter().filter(|tag| {
+            total = total.saturating_add(tag.size);
+            total > cap
+        }).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        CAP_SIZE_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.cap.is_some()
+    }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        format!("{CAP_SIZE_LABEL}: repository exceeds {} bytes", self.cap.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::api::tag::Tag;
+    use crate::policies::cap_size::CapSizePolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags;
+
+    fn get_current_tags() -> Vec<Tag> {
+        get_tags(vec![
+            ("first", Duration::hours(-5), 400),
+            ("second", Duration::minutes(-5), 300),
+            ("third", Duration::minutes(-30), 200),
+            ("fourth", Duration::minutes(-10), 500),
+            ("fifth", Duration::seconds(-15), 100)
+        ])
+    }
+
+    #[test]
+    pub fn test_trimming_oldest() {
+        let tags = get_current_tags();
+        let policy = CapSizePolicy::new("1000");
+        assert!(policy.enabled());
+        // fifth (100) + second (300) + fourth (500) = 900 fit into the cap
+        assert_eq!(policy.affects(tags.clone()), vec![tags[2].clone(), tags[0].clone()])
+    }
+
+    #[test]
+    pub fn test_within_cap() {
+        let tags = get_current_tags();
+        let policy = CapSizePolicy::new("1500");
+        assert_eq!(policy.affects(tags), vec![])
+    }
+
+    #[test]
+    pub fn test_same_creation_date() {
+        let tags = get_tags(vec![("a", Duration::hours(-1), 100), ("b", Duration::hours(-1), 100)]);
+        let policy = CapSizePolicy::new("100");
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()])
+    }
+
+    #[test]
+    pub fn test_invalid_size() {
+        let tags = get_current_tags();
+        for value in ["", "120 asdf", "-1 MiB"] {
+            let policy = CapSizePolicy::new(value);
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![])
+        }
+    }
+}
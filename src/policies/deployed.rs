@@ -0,0 +1,108 @@
+use log::debug;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const DEPLOYED_LABEL: &str = "protect-deployed";
+/// Prefix of the tags which point to the currently deployed image of an environment
+pub const DEPLOYMENT_MARKER_PREFIX: &str = "deployed-";
+
+/// Policy to protect all tags which share their digest with the deployment marker
+/// tag (`deployed-<environment>`) of one of the given environments
+/// # Example
+/// ```
+/// let policy = DeployedPolicy::new("prod,staging");
+///
+/// // returns all tags which point to the same image as the `deployed-prod`
+/// // or `deployed-staging` tags
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DeployedPolicy {
+    environments: Vec<String>
+}
+
+impl DeployedPolicy {
+    pub fn new(value: &str) -> Self {
+        let environments = value.split(',')
+            .map(|environment| environment.trim().to_string())
+            .filter(|environment| !environment.is_empty())
+            .collect();
+        Self { environments }
+    }
+}
+
+impl Policy<Tag> for DeployedPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let digests = self.environments.iter()
+            .filter_map(|environment| {
+                let marker = format!("{DEPLOYMENT_MARKER_PREFIX}{environment}");
+                let digest = elements.iter().find(|tag| tag.name == marker).map(|tag| tag.digest.clone());
+                if digest.is_none() {
+                    debug!("Found no deployment marker '{marker}'. Protecting no tags for environment '{environment}'");
+                }
+                digest
+            })
+            .collect::<Vec<_>>();
+
+        elements.into_iter().filter(|tag| digests.contains(&tag.digest)).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        DEPLOYED_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        !self.environments.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policies::deployed::DeployedPolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags_with_digest;
+
+    #[test]
+    pub fn test_moving_marker() {
+        let policy = DeployedPolicy::new("prod");
+        assert!(policy.enabled());
+
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("v2", "sha256:2"), ("v3", "sha256:3"), ("deployed-prod", "sha256:2")]);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[3].clone()]);
+
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("v2", "sha256:2"), ("v3", "sha256:3"), ("deployed-prod", "sha256:3")]);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[2].clone(), tags[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_multiple_environments() {
+        let policy = DeployedPolicy::new("prod, staging");
+        let tags = get_tags_with_digest(vec![
+            ("v1", "sha256:1"),
+            ("v2", "sha256:2"),
+            ("latest", "sha256:2"),
+            ("deployed-prod", "sha256:1"),
+            ("deployed-staging", "sha256:2")
+        ]);
+        assert_eq!(policy.affects(tags.clone()), tags);
+    }
+
+    #[test]
+    pub fn test_missing_marker() {
+        let policy = DeployedPolicy::new("prod,staging");
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("v2", "sha256:2"), ("deployed-staging", "sha256:1")]);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let policy = DeployedPolicy::new(" , ");
+        assert!(!policy.enabled());
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("deployed-prod", "sha256:1")]);
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use log::info;
+use regex::Regex;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer, without_kept_digests};
+
+pub const GROUP_LABEL: &str = "keep.group";
+/// Name of the capture group whose value groups the tags
+const GROUP_NAME: &str = "group";
+
+/// Policy to match all tags except the newest ones of every group. The tags are grouped by the
+/// value of the `group` capture group of a regex. The value of the policy is the regex and the amount
+/// of tags to keep separated by the last `;` (e.g. `feature-(?<group>\d+)-;2`). Tags which don't match
+/// the regex are never matched
+/// # Example
+/// ```
+/// let policy = GroupPolicy::new(r"feature-(?<group>\d+)-;1");
+///
+/// // returns all tags of every feature except the newest one
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GroupPolicy {
+    pattern: Option<Regex>,
+    keep: usize
+}
+
+impl GroupPolicy {
+    pub fn new(value: &str) -> Self {
+        let Some((pattern, keep)) = value.rsplit_once(';') else {
+            if !value.is_empty() {
+                info!("Received invalid group value '{value}'. Expected '<regex>;<count>'")
+            }
+            return Self::default()
+        };
+        let keep = match parse_integer(keep.trim().to_string()) {
+            Some(keep) if keep > 0 => keep as usize,
+            _ => {
+                info!("Received invalid group count '{keep}'. Expected non-zero positive integer");
+                return Self::default()
+            }
+        };
+        match Regex::new(pattern) {
+            Ok(regex) if regex.capture_names().any(|name| name == Some(GROUP_NAME)) => Self { pattern: Some(regex), keep },
+            Ok(_) => {
+                info!("Received group pattern '{pattern}' without capture group '{GROUP_NAME}'");
+                Self::default()
+            },
+            Err(err) => {
+                info!("Received invalid group pattern '{pattern}'. Reason: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    fn group<'a>(&self, tag: &'a Tag) -> Option<&'a str> {
+        let captures = self.pattern.as_ref()?.captures(&tag.name)?;
+        captures.name(GROUP_NAME).map(|group| group.as_str())
+    }
+}
+
+impl Policy<Tag> for GroupPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let mut groups: HashMap<String, Vec<Tag>> = HashMap::new();
+        for tag in elements {
+            if let Some(group) = self.group(&tag).map(str::to_string) {
+                groups.entry(group).or_default().push(tag)
+            }
+        }
+        let mut affected = vec![];
+        let mut kept = vec![];
+        for mut tags in groups.into_values() {
+            tags.sort_by_key(|tag| tag.created);
+            kept.extend(tags.split_off(tags.len().saturating_sub(self.keep)));
+            affected.extend(tags);
+        }
+        affected.sort_by_key(|tag| tag.created);
+        without_kept_digests(affected, &kept)
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        GROUP_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.pattern.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        format!("{GROUP_LABEL}: older than the newest {} of group '{}'", self.keep, self.group(tag).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::api::tag::Tag;
+    use crate::policies::group::GroupPolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags;
+
+    fn get_current_tags() -> Vec<Tag> {
+        get_tags(vec![
+            ("feature-123-build-1", Duration::hours(-5), 1),
+            ("feature-123-build-2", Duration::hours(-4), 1),
+            ("feature-123-build-3", Duration::hours(-3), 1),
+            ("feature-456-build-1", Duration::hours(-2), 1),
+            ("feature-456-build-2", Duration::hours(-1), 1),
+            ("main-build-7", Duration::hours(-6), 1)
+        ])
+    }
+
+    #[test]
+    pub fn test_multiple_groups() {
+        let tags = get_current_tags();
+        let policy = GroupPolicy::new(r"feature-(?<group>\d+)-;1");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[1].clone(), tags[3].clone()]);
+        assert_eq!(policy.reason(&tags[0]), "keep.group: older than the newest 1 of group '123'");
+    }
+
+    #[test]
+    pub fn test_aliases_of_kept_tags() {
+        let mut tags = get_current_tags();
+        // the old build of feature 123 is an alias of the newest build of feature 456
+        tags[0].digest = tags[4].digest.clone();
+        let policy = GroupPolicy::new(r"feature-(?<group>\d+)-;1");
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_keeping_more() {
+        let tags = get_current_tags();
+        let policy = GroupPolicy::new(r"feature-(?<group>\d+)-;2");
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    pub fn test_invalid_value() {
+        let tags = get_current_tags();
+        for value in ["", r"feature-(?<group>\d+)-", r"feature-(?<group>\d+)-;0", r"feature-(\d+)-;1", "feature-(;1"] {
+            let policy = GroupPolicy::new(value);
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![]);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use globset::{GlobBuilder, GlobMatcher};
+use log::info;
+use crate::api::repository::Repository;
+use crate::policies::{AffectionType, Policy};
+
+pub const IMAGE_GLOB_LABEL: &str = "image.glob";
+
+/// Policy to match all repositories whose whole name matches the provided
+/// shell-style glob pattern. Like in a shell `*` and `?` don't match the `/`
+/// between the path segments of a repository whereas `**` does
+/// # Example
+/// ```
+/// let policy = ImageGlobPolicy::new("team/*");
+///
+/// // returns all repositories directly below `team/` (e.g. `team/api`)
+/// let affected = policy.affects(&repositories);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageGlobPolicy {
+    glob: Option<GlobMatcher>
+}
+
+impl ImageGlobPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.trim() == "" {
+            return Self { glob: None }
+        }
+        match GlobBuilder::new(value).literal_separator(true).build() {
+            Ok(glob) => Self { glob: Some(glob.compile_matcher()) },
+            Err(err) => {
+                info!("Received invalid glob '{value}'. Reason: {err}");
+                Self { glob: None }
+            }
+        }
+    }
+}
+
+impl Policy<Repository> for ImageGlobPolicy {
+    fn affects(&self, elements: Vec<Repository>) -> Vec<Repository> {
+        if let Some(glob) = &self.glob {
+            elements.into_iter().filter(|repo| glob.is_match(&repo.name)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        IMAGE_GLOB_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.glob.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policies::image_glob::ImageGlobPolicy;
+    use crate::policies::Policy;
+    use crate::test::get_repositories;
+
+    #[test]
+    pub fn test_matching() {
+        let repositories = get_repositories(vec!["app-rc1", "app", "team/app-rc2", "my-app-rc"]);
+        let policy = ImageGlobPolicy::new("*-rc*");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(repositories.clone()), vec![repositories[0].clone(), repositories[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_path_segments() {
+        let repositories = get_repositories(vec!["team/api", "team/backend/api", "other/api", "team/ui"]);
+        assert_eq!(ImageGlobPolicy::new("team/*").affects(repositories.clone()), vec![repositories[0].clone(), repositories[3].clone()]);
+        assert_eq!(ImageGlobPolicy::new("team/**").affects(repositories.clone()), vec![repositories[0].clone(), repositories[1].clone(), repositories[3].clone()]);
+        assert_eq!(ImageGlobPolicy::new("team/?i").affects(repositories.clone()), vec![repositories[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let repositories = get_repositories(vec!["app", "api"]);
+        let policy = ImageGlobPolicy::new("");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(repositories), vec![]);
+    }
+}
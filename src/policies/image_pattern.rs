@@ -4,6 +4,8 @@ use crate::api::repository::Repository;
 use crate::policies::{AffectionType, Policy};
 
 pub const IMAGE_PATTERN_LABEL: &str = "image.pattern";
+/// Whether the image pattern has to match the whole name of a repository
+pub const IMAGE_PATTERN_ANCHORED_LABEL: &str = "image.pattern.anchored";
 
 /// Policy to match all repositories whose name matches the provided
 /// regex pattern
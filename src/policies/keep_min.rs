@@ -0,0 +1,85 @@
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer};
+
+pub const KEEP_MIN_LABEL: &str = "keep.min";
+
+/// Policy to always keep the given amount of newest tags of a repository independent of the target
+/// policies. Unlike the [`RevisionPolicy`](crate::policies::revision::RevisionPolicy) it doesn't
+/// select any tags for deletion itself
+/// # Example
+/// ```
+/// let policy = KeepMinPolicy::new(String::from("3"));
+///
+/// // returns the 3 newest tags which are protected from deletion
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeepMinPolicy {
+    count: Option<usize>
+}
+
+impl KeepMinPolicy {
+    pub fn new(value: String) -> Self {
+        match parse_integer(value.clone()) {
+            Some(count) if count > 0 => Self { count: Some(count as usize) },
+            _ => {
+                info!("Received invalid minimum tag count '{value}'. Expected non-zero positive integer");
+                Self { count: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for KeepMinPolicy {
+    fn affects(&self, mut elements: Vec<Tag>) -> Vec<Tag> {
+        let Some(count) = self.count else {
+            return vec![]
+        };
+        // the name breaks ties between tags which were created at the same time to keep the selection stable
+        elements.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| a.name.cmp(&b.name)));
+        elements.truncate(count);
+        elements
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        KEEP_MIN_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.count.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::keep_min::KeepMinPolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags;
+
+    #[test]
+    pub fn test_keep_newest() {
+        let tags = get_tags(vec![
+            ("v1", Duration::days(-4), 1),
+            ("v4", Duration::days(-1), 1),
+            ("v2", Duration::days(-3), 1),
+            ("v3", Duration::days(-2), 1)
+        ]);
+        let policy = KeepMinPolicy::new(String::from("2"));
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[3].clone()]);
+        assert_eq!(KeepMinPolicy::new(String::from("10")).affects(tags.clone()).len(), 4);
+    }
+
+    #[test]
+    pub fn test_invalid_count() {
+        for value in ["0", "-1", "asdf"] {
+            assert!(!KeepMinPolicy::new(String::from(value)).enabled());
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{Duration, Utc};
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, format_duration, parse_duration, Policy};
+
+pub const LAST_PULLED_MAX_LABEL: &str = "last-pulled.max";
+
+/// Policy to match all tags which weren't pulled within a given duration. Tags without pull data
+/// (see [`crate::pulls::PullSource`]) are never matched
+/// # Example
+/// ```
+/// let policy = LastPulledMaxPolicy::new(String::from("30d"));
+///
+/// // returns all tags which were last pulled more than 30 days ago
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LastPulledMaxPolicy {
+    age: Option<Duration>
+}
+
+impl LastPulledMaxPolicy {
+    pub fn new(value: String) -> Self {
+        if value.is_empty() {
+            Self { age: None }
+        } else {
+            let age = parse_duration(value.clone());
+            if age.is_none() {
+                info!("Received invalid max last pulled duration '{value}'")
+            }
+            Self { age }
+        }
+    }
+}
+
+impl Policy<Tag> for LastPulledMaxPolicy {
+    fn affects(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        if let Some(age) = self.age {
+            let now = Utc::now();
+            tags.into_iter().filter(|tag| tag.last_pulled.is_some_and(|pulled| pulled + age <= now)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        LAST_PULLED_MAX_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.age.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        let age = self.age.map(format_duration).unwrap_or_default();
+        let pulled = tag.last_pulled.map(|pulled| format_duration(Utc::now() - pulled)).unwrap_or_default();
+        format!("{LAST_PULLED_MAX_LABEL}: {pulled} > {age}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use crate::api::tag::Tag;
+    use crate::policies::last_pulled::LastPulledMaxPolicy;
+    use crate::policies::Policy;
+    use crate::test::get_tags_by_name;
+
+    fn get_current_tags() -> Vec<Tag> {
+        let now = Utc::now();
+        get_tags_by_name(vec!["stale", "recent", "unknown"], Duration::days(-100), 1).into_iter()
+            .zip([Some(now - Duration::days(45)), Some(now - Duration::days(2)), None])
+            .map(|(tag, pulled)| tag.with_last_pulled(pulled))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_last_pulled() {
+        let tags = get_current_tags();
+        let policy = LastPulledMaxPolicy::new(String::from("30d"));
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+        assert_eq!(policy.reason(&tags[0]), "last-pulled.max: 45d > 30d");
+    }
+
+    #[test]
+    pub fn test_without_pull_data() {
+        let tags = get_tags_by_name(vec!["first", "second"], Duration::days(-100), 1);
+        assert_eq!(LastPulledMaxPolicy::new(String::from("1d")).affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_invalid_duration() {
+        let policy = LastPulledMaxPolicy::new(String::from("asdf"));
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(get_current_tags()), vec![]);
+    }
+}
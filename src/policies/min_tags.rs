@@ -0,0 +1,113 @@
+use log::info;
+use crate::api::repository::Repository;
+use crate::policies::{AffectionType, Policy, parse_integer};
+
+pub const MIN_TAGS_LABEL: &str = "repository.min-tags";
+
+/// Policy to only keep repositories targeted which contain at least a given amount of tags
+/// # Example
+/// ```
+/// let policy = MinTagsPolicy::new(String::from("100"));
+///
+/// // returns all repositories which have less than 100 tags and therefore
+/// // shouldn't be touched by the rule
+/// let affected = policy.affects(&repositories);
+/// ```
+///
+/// **Important**: The policy relies on [`Repository::tag_count`] which is only loaded when a rule
+/// contains an enabled `MinTagsPolicy`. Repositories without a known tag count are always un-targeted
+#[derive(Debug, Clone, Default)]
+pub struct MinTagsPolicy {
+    tags: Option<usize>
+}
+
+impl MinTagsPolicy {
+    pub fn new(value: String) -> Self {
+        if value.is_empty() {
+            return Self { tags: None }
+        }
+        match parse_integer(value.clone()) {
+            Some(tags) if tags > 0 => Self { tags: Some(tags as usize) },
+            _ => {
+                info!("Received invalid minimum tags value '{value}'. Expected non-zero positive integer");
+                Self { tags: None }
+            }
+        }
+    }
+}
+
+impl Policy<Repository> for MinTagsPolicy {
+    fn affects(&self, elements: Vec<Repository>) -> Vec<Repository> {
+        if let Some(tags) = self.tags {
+            elements.into_iter().filter(|repo| repo.tag_count.is_none_or(|count| count < tags)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        MIN_TAGS_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.tags.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api::repository::Repository;
+    use crate::policies::min_tags::MinTagsPolicy;
+    use crate::policies::Policy;
+    use crate::test::{get_repositories, get_repositories_with_tag_count};
+
+    fn get_current_repositories() -> Vec<Repository> {
+        get_repositories_with_tag_count(vec![
+            ("small", 3),
+            ("bloated", 250),
+            ("exact", 100),
+            ("empty", 0)
+        ])
+    }
+
+    #[test]
+    pub fn test_skipping_small() {
+        let repositories = get_current_repositories();
+        let policy = MinTagsPolicy::new(String::from("100"));
+        assert!(policy.tags.is_some());
+        assert_eq!(policy.affects(repositories.clone()), vec![repositories[0].clone(), repositories[3].clone()])
+    }
+
+    #[test]
+    pub fn test_unknown_tag_count() {
+        let repositories = get_repositories(vec!["unknown"]);
+        let policy = MinTagsPolicy::new(String::from("1"));
+        assert_eq!(policy.affects(repositories.clone()), repositories)
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let repositories = get_current_repositories();
+        let policy = MinTagsPolicy::new(String::new());
+        assert!(policy.tags.is_none());
+        assert_eq!(policy.affects(repositories), vec![])
+    }
+
+    #[test]
+    pub fn test_invalid_integer() {
+        let repositories = get_current_repositories();
+        let policy = MinTagsPolicy::new(String::from("asdf"));
+        assert!(policy.tags.is_none());
+        assert_eq!(policy.affects(repositories), vec![])
+    }
+
+    #[test]
+    pub fn test_zero() {
+        let policy = MinTagsPolicy::new(String::from("0"));
+        assert!(policy.tags.is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use chrono::Duration;
 use duration_string::DurationString;
@@ -8,10 +8,31 @@ use crate::api::tag::Tag;
 
 pub mod age_max;
 pub mod age_min;
+pub mod arch;
+pub mod base_image;
+pub mod build_number;
+pub mod cap_size;
+pub mod deployed;
+pub mod group;
+pub mod image_glob;
 pub mod image_pattern;
+pub mod keep_min;
+pub mod last_pulled;
+pub mod min_tags;
+pub mod not;
+pub mod prefix;
+pub mod protect;
+pub mod protect_pattern;
 pub mod revision;
+pub mod semver;
+pub mod semver_group;
+pub mod tag_glob;
 pub mod tag_pattern;
+pub mod tag_allowed;
+pub mod signed;
 pub mod size;
+pub mod size_min;
+pub mod window;
 
 pub type PolicyMap<T> = HashMap<&'static str, Box<dyn Policy<T>>>;
 
@@ -32,7 +53,8 @@ dyn_clone::clone_trait_object!(Policy<Tag>);
 pub trait Policy<T>: Debug + Send + Sync + DynClone {
     /// All repositories/tags which are affected by this policy <br>
     /// **Important:** When the policy is of [`AffectionType::Requirement`] the inverse is returned. Means
-    /// instead of returning which repositories/tags should be targeted it returns which should be un-targeted
+    /// instead of returning which repositories/tags should be targeted it returns which should be un-targeted. Requirement
+    /// policies always receive all repositories/tags to allow policies which depend on other elements
     fn affects(&self, elements: Vec<T>) -> Vec<T>;
 
     /// Affection type of the policy
@@ -43,6 +65,30 @@ pub trait Policy<T>: Debug + Send + Sync + DynClone {
     fn id(&self) -> &'static str;
 
     fn enabled(&self) -> bool;
+
+    /// Why the repository/tag was affected by this policy. Only called for repositories/tags which
+    /// were returned by [`Policy::affects`] of an [`AffectionType::Target`] policy. Per default the
+    /// identifier of the policy is used
+    fn reason(&self, _element: &T) -> String {
+        self.id().to_string()
+    }
+}
+
+/// Wrap a regex pattern to only match whole values instead of any substring (e.g. `test` doesn't match `mytest-thing`).
+/// Empty patterns are kept as they are
+pub fn anchor_pattern(pattern: &str) -> String {
+    if pattern.trim().is_empty() {
+        pattern.to_string()
+    } else {
+        format!("^(?:{pattern})$")
+    }
+}
+
+/// Remove all tags sharing their digest with one of the kept tags. Tags are deleted by their digest, therefore
+/// deleting such a tag would delete the kept tag as well
+pub fn without_kept_digests(tags: Vec<Tag>, kept: &[Tag]) -> Vec<Tag> {
+    let kept = kept.iter().map(|tag| tag.digest.as_str()).collect::<HashSet<_>>();
+    tags.into_iter().filter(|tag| !kept.contains(tag.digest.as_str())).collect()
 }
 
 pub fn parse_integer(value: String) -> Option<u32> {
@@ -50,14 +96,111 @@ pub fn parse_integer(value: String) -> Option<u32> {
 }
 
 /// Parse a duration <br>
-/// **Important**: Allowed duration values have to match the following regex `[0-9]+(ns|us|ms|[smhdwy])`
+/// **Important**: Allowed duration values have to match the following regex `[0-9]+(ns|us|ms|[smhdwy])`. Long-form
+/// units (e.g. `30days` or `2 weeks`) and fractional values (e.g. `1.5h`) are normalized to this format beforehand
 pub fn parse_duration(duration_str: String) -> Option<Duration> {
+    if let Some(duration) = parse_long_duration(&duration_str) {
+        return Some(duration)
+    }
     match DurationString::from_string(duration_str.clone()) {
         Ok(duration_str) => Duration::from_std(duration_str.into()).ok(),
         Err(_) => None
     }
 }
 
+/// Parse a duration consisting of a single (fractional) value followed by a short or long-form unit
+fn parse_long_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value.find(|char: char| char.is_ascii_alphabetic())?;
+    let (amount, unit) = (value[..split].trim(), value[split..].trim());
+    if amount.is_empty() || !amount.chars().all(|char| char.is_ascii_digit() || char == '.') {
+        return None
+    }
+    let unit = match unit.to_lowercase().as_str() {
+        "ns" | "nanosecond" | "nanoseconds" => "ns",
+        "us" | "microsecond" | "microseconds" => "us",
+        "ms" | "millisecond" | "milliseconds" => "ms",
+        "s" | "sec" | "secs" | "second" | "seconds" => "s",
+        "m" | "min" | "mins" | "minute" | "minutes" => "m",
+        "h" | "hr" | "hrs" | "hour" | "hours" => "h",
+        "d" | "day" | "days" => "d",
+        "w" | "week" | "weeks" => "w",
+        "y" | "year" | "years" => "y",
+        _ => return None
+    };
+    // the length of a unit is taken from the duration_string crate to keep the short forms unchanged
+    let unit: std::time::Duration = DurationString::from_string(format!("1{unit}")).ok()?.into();
+    let amount = amount.parse::<f64>().ok().filter(|amount| amount.is_finite())?;
+    let nanos = unit.as_nanos() as f64 * amount;
+    if nanos > i64::MAX as f64 {
+        return None
+    }
+    Some(Duration::nanoseconds(nanos.round() as i64))
+}
+
+/// Parse a duration as [`std::time::Duration`]. See [`parse_duration`] for the allowed values
+pub fn parse_std_duration(value: &str) -> Option<std::time::Duration> {
+    parse_duration(value.to_string()).and_then(|duration| duration.to_std().ok())
+}
+
+/// Format a duration using its biggest whole unit (e.g. `45d` or `3h`)
+pub fn format_duration(duration: Duration) -> String {
+    if duration.num_days() != 0 {
+        format!("{}d", duration.num_days())
+    } else if duration.num_hours() != 0 {
+        format!("{}h", duration.num_hours())
+    } else if duration.num_minutes() != 0 {
+        format!("{}m", duration.num_minutes())
+    } else {
+        format!("{}s", duration.num_seconds())
+    }
+}
+
 pub fn parse_size(size_str: &str) -> Option<u64> {
     parse_size::parse_size(size_str).ok()
-}
\ No newline at end of file
+}
+
+/// Format a size in bytes using its biggest binary unit (e.g. `1.5 GiB`)
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if size < 1024 {
+        return format!("{size} B")
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::age_max::AgeMaxPolicy;
+    use crate::policies::{parse_duration, Policy};
+
+    #[test]
+    fn test_parse_short_duration() {
+        assert_eq!(parse_duration(String::from("2w")), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration(String::from("10s")), Some(Duration::seconds(10)));
+        assert_eq!(parse_duration(String::from("250ms")), Some(Duration::milliseconds(250)));
+    }
+
+    #[test]
+    fn test_parse_long_duration() {
+        assert_eq!(parse_duration(String::from("30days")), Some(Duration::days(30)));
+        assert_eq!(parse_duration(String::from("2 weeks")), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration(String::from("1 Hour")), Some(Duration::hours(1)));
+        assert_eq!(parse_duration(String::from("1.5h")), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration(String::from("0.5 days")), Some(Duration::hours(12)));
+    }
+
+    #[test]
+    fn test_parse_invalid_duration() {
+        for value in ["5 bananas", "1.2.3h", "h", "-1d", ""] {
+            assert_eq!(parse_duration(String::from(value)), None, "{value}");
+        }
+        assert!(!AgeMaxPolicy::new(String::from("5 bananas")).enabled());
+    }
+}
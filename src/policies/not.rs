@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Mutex, OnceLock};
+use crate::policies::{AffectionType, Policy};
+
+/// Suffix of a policy label which negates the policy (e.g. `tag.pattern.not`)
+pub const NOT_SUFFIX: &str = ".not";
+
+/// Policy which negates another policy by matching all elements the inner policy doesn't match. The
+/// affection type of the inner policy is kept
+/// # Example
+/// ```
+/// let policy = NotPolicy::new(Box::new(TagPatternPolicy::new("^v\\d+")));
+///
+/// // returns all tags which don't start with a version
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug)]
+pub struct NotPolicy<T> {
+    inner: Box<dyn Policy<T>>,
+    id: &'static str
+}
+
+impl<T> Clone for NotPolicy<T> where Box<dyn Policy<T>>: Clone {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone(), id: self.id }
+    }
+}
+
+impl<T> NotPolicy<T> {
+    pub fn new(inner: Box<dyn Policy<T>>) -> Self {
+        let id = negated_id(inner.id());
+        Self { inner, id }
+    }
+}
+
+/// Get the identifier of a negated policy. The identifiers are interned since policy identifiers are
+/// static and there is only a fixed amount of policies
+fn negated_id(id: &'static str) -> &'static str {
+    static IDS: OnceLock<Mutex<HashMap<&'static str, &'static str>>> = OnceLock::new();
+    let mut ids = IDS.get_or_init(Default::default).lock().unwrap_or_else(|err| err.into_inner());
+    ids.entry(id).or_insert_with(|| Box::leak(format!("{id}{NOT_SUFFIX}").into_boxed_str()))
+}
+
+impl<T> Policy<T> for NotPolicy<T> where T: Clone + PartialEq + Debug + Send + Sync + 'static, Box<dyn Policy<T>>: Clone {
+    fn affects(&self, elements: Vec<T>) -> Vec<T> {
+        let affected = self.inner.affects(elements.clone());
+        elements.into_iter().filter(|element| !affected.contains(element)).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        self.inner.affection_type()
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::{AffectionType, Policy};
+    use crate::policies::age_min::AgeMinPolicy;
+    use crate::policies::not::NotPolicy;
+    use crate::policies::tag_pattern::TagPatternPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_complement() {
+        let tags = get_tags_by_name(vec!["v1", "v2", "latest", "dev-v3", "main"], Duration::seconds(-1), 1);
+        let inner = TagPatternPolicy::new(r"^v\d+");
+        let matched = inner.affects(tags.clone());
+        let policy = NotPolicy::new(Box::new(inner));
+        assert_eq!(policy.id(), "tag.pattern.not");
+        assert!(policy.enabled());
+        assert!(policy.affection_type() == AffectionType::Target);
+
+        let negated = policy.affects(tags.clone());
+        assert_eq!(negated, vec![tags[2].clone(), tags[3].clone(), tags[4].clone()]);
+        assert!(tags.iter().all(|tag| matched.contains(tag) != negated.contains(tag)));
+    }
+
+    #[test]
+    pub fn test_inner_properties() {
+        let policy = NotPolicy::new(Box::new(AgeMinPolicy::new(String::from("asdf"))));
+        assert!(policy.affection_type() == AffectionType::Requirement);
+        assert!(!policy.enabled());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const PREFIX_LABEL: &str = "protect-newest-per-prefix";
+
+/// Policy to protect the newest tag of every given tag prefix. Tags which don't start
+/// with any of the prefixes aren't protected
+/// # Example
+/// ```
+/// let policy = PrefixPolicy::new("prod-,staging-");
+///
+/// // returns the newest tag starting with `prod-` and the newest
+/// // tag starting with `staging-`
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrefixPolicy {
+    prefixes: Vec<String>
+}
+
+impl PrefixPolicy {
+    pub fn new(value: &str) -> Self {
+        let prefixes = value.split(',')
+            .map(|prefix| prefix.trim().to_string())
+            .filter(|prefix| !prefix.is_empty())
+            .collect();
+        Self { prefixes }
+    }
+
+    /// Get the prefix group of the tag. When multiple prefixes match the longest one is used
+    fn group(&self, tag: &Tag) -> Option<usize> {
+        self.prefixes.iter()
+            .enumerate()
+            .filter(|(_, prefix)| tag.name.starts_with(prefix.as_str()))
+            .max_by_key(|(_, prefix)| prefix.len())
+            .map(|(index, _)| index)
+    }
+}
+
+impl Policy<Tag> for PrefixPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let mut newest: Vec<Option<&Tag>> = vec![None; self.prefixes.len()];
+        for tag in &elements {
+            if let Some(group) = self.group(tag) {
+                if newest[group].is_none_or(|current| tag.created > current.created) {
+                    newest[group] = Some(tag)
+                }
+            }
+        }
+        let protected = newest.into_iter().flatten().map(|tag| tag.name.clone()).collect::<Vec<_>>();
+        elements.into_iter().filter(|tag| protected.contains(&tag.name)).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        PREFIX_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        !self.prefixes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::prefix::PrefixPolicy;
+    use crate::test::get_tags;
+
+    #[test]
+    pub fn test_newest_per_prefix() {
+        let policy = PrefixPolicy::new("prod-, staging-,dev-");
+        assert!(policy.enabled());
+
+        let tags = get_tags(vec![
+            ("prod-1", Duration::days(-3), 1),
+            ("prod-2", Duration::days(-2), 1),
+            ("staging-1", Duration::days(-5), 1),
+            ("dev-1", Duration::hours(-2), 1),
+            ("dev-2", Duration::hours(-1), 1),
+            ("feature-1", Duration::minutes(-1), 1)
+        ]);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[2].clone(), tags[4].clone()]);
+    }
+
+    #[test]
+    pub fn test_longest_prefix() {
+        let policy = PrefixPolicy::new("prod-,prod-eu-");
+        let tags = get_tags(vec![
+            ("prod-1", Duration::days(-2), 1),
+            ("prod-eu-1", Duration::days(-1), 1)
+        ]);
+        assert_eq!(policy.affects(tags.clone()), tags);
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let policy = PrefixPolicy::new(" , ");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(get_tags(vec![("prod-1", Duration::days(-1), 1)])), vec![]);
+    }
+}
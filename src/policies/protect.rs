@@ -0,0 +1,69 @@
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const PROTECT_LABEL: &str = "keep.tags";
+
+/// Policy to protect all tags whose name exactly matches one of the given names
+/// # Example
+/// ```
+/// let policy = ProtectPolicy::new("latest,stable");
+///
+/// // returns the `latest` and `stable` tags
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProtectPolicy {
+    tags: Vec<String>
+}
+
+impl ProtectPolicy {
+    pub fn new(value: &str) -> Self {
+        let tags = value.split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        Self { tags }
+    }
+}
+
+impl Policy<Tag> for ProtectPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        elements.into_iter().filter(|tag| self.tags.contains(&tag.name)).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        PROTECT_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        !self.tags.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::protect::ProtectPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_exact_match() {
+        let tags = get_tags_by_name(vec!["latest", "stable", "Latest", "latest-1", "prod", "v1"], Duration::seconds(-1), 1);
+        let policy = ProtectPolicy::new("latest, stable,prod");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[1].clone(), tags[4].clone()]);
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let tags = get_tags_by_name(vec!["latest"], Duration::seconds(-1), 1);
+        let policy = ProtectPolicy::new(" ,");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+}
@@ -0,0 +1,86 @@
+use log::info;
+use regex::Regex;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const PROTECT_PATTERN_LABEL: &str = "protect.pattern";
+/// Whether the protection pattern has to match the whole name of a tag
+pub const PROTECT_PATTERN_ANCHORED_LABEL: &str = "protect.pattern.anchored";
+
+/// Policy to restrict the deletions of a rule to tags whose name matches the provided regex
+/// pattern. Unlike the [`TagPatternPolicy`](crate::policies::tag_pattern::TagPatternPolicy) it doesn't
+/// select any tags for deletion itself but protects all tags which don't match
+/// # Example
+/// ```
+/// let policy = ProtectPatternPolicy::new("nightly-.*");
+///
+/// // returns all tags whose name doesn't contain `nightly-` which are protected from deletion
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProtectPatternPolicy {
+    pattern: Option<Regex>
+}
+
+impl ProtectPatternPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.trim() == "" {
+            return Self { pattern: None }
+        }
+        match Regex::new(value) {
+            Ok(regex) => Self { pattern: Some(regex) },
+            Err(err) => {
+                info!("Received invalid pattern '{value}'. Reason: {err}");
+                Self { pattern: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for ProtectPatternPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if let Some(pattern) = &self.pattern {
+            elements.into_iter().filter(|tag| !pattern.is_match(&tag.name)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        PROTECT_PATTERN_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.pattern.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::protect_pattern::ProtectPatternPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_protect_not_matching() {
+        let tags = get_tags_by_name(vec!["nightly-1", "latest", "v1", "nightly-2"], Duration::seconds(-1), 1);
+        let policy = ProtectPatternPolicy::new("nightly-.*");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_invalid_pattern() {
+        let tags = get_tags_by_name(vec!["nightly-1", "latest"], Duration::seconds(-1), 1);
+        for value in ["", "([a-z]+"] {
+            let policy = ProtectPatternPolicy::new(value);
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![]);
+        }
+    }
+}
@@ -30,7 +30,7 @@ impl RevisionPolicy {
 
 impl Policy<Tag> for RevisionPolicy {
     fn affects(&self, mut elements: Vec<Tag>) -> Vec<Tag> {
-        elements.sort_by(|t1, t2| t1.created.cmp(&t2.created));
+        elements.sort_by_key(|tag| tag.created);
         if let Some(revisions) = self.revisions {
             if elements.len() > revisions {
                 let length = elements.len();
@@ -55,6 +55,10 @@ impl Policy<Tag> for RevisionPolicy {
     fn enabled(&self) -> bool {
         self.revisions.is_some()
     }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        format!("{REVISION_LABEL}: older than the newest {}", self.revisions.unwrap_or_default())
+    }
 }
 
 impl Default for RevisionPolicy {
@@ -113,4 +117,11 @@ mod test {
         assert!(policy.revisions.is_none());
         assert_eq!(policy.affects(tags), vec![])
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_reason() {
+        let tags = get_current_tags();
+        let policy = RevisionPolicy { revisions: Some(3) };
+        assert_eq!(policy.reason(&tags[0]), "revisions: older than the newest 3");
+    }
+}
@@ -0,0 +1,136 @@
+use log::{debug, info};
+use semver::Version;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer, without_kept_digests};
+
+pub const SEMVER_LABEL: &str = "keep.semver";
+
+/// Policy to match all tags except the ones with the highest semantic versions. Versions are
+/// compared by semver precedence which keeps e.g. `1.10.0` above `1.9.0` and `1.0.0` above
+/// `1.0.0-rc.1`. An optional leading `v` is ignored. Tags which aren't a semantic version are
+/// never matched
+/// # Example
+/// ```
+/// let policy = SemverPolicy::new(String::from("3"));
+///
+/// // returns all semantic version tags except the 3 highest versions
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SemverPolicy {
+    keep: Option<usize>
+}
+
+impl SemverPolicy {
+    pub fn new(value: String) -> Self {
+        match parse_integer(value.clone()) {
+            Some(keep) if keep > 0 => Self { keep: Some(keep as usize) },
+            _ => {
+                info!("Received invalid semver keep value '{value}'. Expected non-zero positive integer");
+                Self { keep: None }
+            }
+        }
+    }
+}
+
+/// Parse the name of a tag as semantic version. An optional leading `v` is ignored
+pub fn parse_version(tag: &str) -> Option<Version> {
+    let name = tag.strip_prefix('v').unwrap_or(tag);
+    match Version::parse(name) {
+        Ok(version) => Some(version),
+        Err(err) => {
+            debug!("Ignoring tag '{tag}' which isn't a semantic version. Reason: {err}");
+            None
+        }
+    }
+}
+
+impl Policy<Tag> for SemverPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let Some(keep) = self.keep else {
+            return vec![]
+        };
+        let mut versioned = elements.into_iter()
+            .filter_map(|tag| parse_version(&tag.name).map(|version| (version, tag)))
+            .collect::<Vec<_>>();
+        versioned.sort_by(|(v1, t1), (v2, t2)| v1.cmp(v2).then(t1.created.cmp(&t2.created)));
+        let kept = versioned.split_off(versioned.len().saturating_sub(keep)).into_iter().map(|(_, tag)| tag).collect::<Vec<_>>();
+        without_kept_digests(versioned.into_iter().map(|(_, tag)| tag).collect(), &kept)
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        SEMVER_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.keep.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        format!("{SEMVER_LABEL}: version {} isn't within the highest {}", tag.name, self.keep.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::semver::SemverPolicy;
+    use crate::test::{get_tags, get_tags_by_name};
+
+    #[test]
+    pub fn test_keeping_highest() {
+        let tags = get_tags_by_name(vec!["1.9.0", "v1.10.0", "1.4.2", "1.10.0-rc.1", "2.0.0"], Duration::seconds(-1), 1);
+        let policy = SemverPolicy::new(String::from("2"));
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[2].clone(), tags[0].clone(), tags[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_regardless_of_age() {
+        let tags = get_tags(vec![
+            ("1.0.0", Duration::minutes(-1), 1),
+            ("3.0.0", Duration::days(-300), 1),
+            ("2.0.0", Duration::days(-10), 1)
+        ]);
+        let policy = SemverPolicy::new(String::from("1"));
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_ignoring_non_semver() {
+        let tags = get_tags_by_name(vec!["latest", "1.0.0", "main", "1.0", "2.0.0"], Duration::seconds(-1), 1);
+        let policy = SemverPolicy::new(String::from("1"));
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone()]);
+    }
+
+    #[test]
+    pub fn test_aliases_of_kept_versions() {
+        let mut tags = get_tags_by_name(vec!["1.0.0", "2.0.0", "1.9.9"], Duration::seconds(-1), 1);
+        // `1.9.9` was re-tagged as `2.0.0` and deleting it would delete `2.0.0` as well
+        tags[2].digest = tags[1].digest.clone();
+        let policy = SemverPolicy::new(String::from("1"));
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    pub fn test_keeping_more() {
+        let tags = get_tags_by_name(vec!["1.0.0", "2.0.0"], Duration::seconds(-1), 1);
+        let policy = SemverPolicy::new(String::from("5"));
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_invalid_integer() {
+        let tags = get_tags_by_name(vec!["1.0.0", "2.0.0"], Duration::seconds(-1), 1);
+        for value in ["0", "asdf", "-1"] {
+            let policy = SemverPolicy::new(String::from(value));
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![]);
+        }
+    }
+}
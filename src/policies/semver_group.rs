@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy, parse_integer};
+use crate::policies::semver::parse_version;
+
+pub const SEMVER_GROUP_LABEL: &str = "keep.semver.minor";
+
+/// Policy to match all tags except the ones with the highest patch versions of every minor
+/// version. Tags which aren't a semantic version are never matched
+/// # Example
+/// ```
+/// let policy = SemverGroupPolicy::new(String::from("2"));
+///
+/// // returns all semantic version tags except the 2 highest patch
+/// // versions of every `major.minor` release line
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SemverGroupPolicy {
+    keep: Option<usize>
+}
+
+impl SemverGroupPolicy {
+    pub fn new(value: String) -> Self {
+        match parse_integer(value.clone()) {
+            Some(keep) if keep > 0 => Self { keep: Some(keep as usize) },
+            _ => {
+                info!("Received invalid semver minor keep value '{value}'. Expected non-zero positive integer");
+                Self { keep: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for SemverGroupPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        let Some(keep) = self.keep else {
+            return vec![]
+        };
+        let mut groups = HashMap::new();
+        for tag in elements {
+            if let Some(version) = parse_version(&tag.name) {
+                groups.entry((version.major, version.minor)).or_insert_with(Vec::new).push((version, tag))
+            }
+        }
+        let mut affected = groups.into_values().flat_map(|mut versioned| {
+            versioned.sort_by(|(v1, t1), (v2, t2)| v1.cmp(v2).then(t1.created.cmp(&t2.created)));
+            let length = versioned.len();
+            versioned.into_iter().take(length.saturating_sub(keep))
+        }).collect::<Vec<_>>();
+        affected.sort_by(|(v1, _), (v2, _)| v1.cmp(v2));
+        affected.into_iter().map(|(_, tag)| tag).collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        SEMVER_GROUP_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.keep.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        format!("{SEMVER_GROUP_LABEL}: version {} isn't within the highest {} of its minor version", tag.name, self.keep.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::semver_group::SemverGroupPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_keeping_per_minor() {
+        let tags = get_tags_by_name(vec!["1.4.0", "1.4.1", "v1.4.2", "1.5.0", "1.5.1", "2.0.0", "latest"], Duration::seconds(-1), 1);
+        let policy = SemverGroupPolicy::new(String::from("1"));
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[1].clone(), tags[3].clone()]);
+    }
+
+    #[test]
+    pub fn test_keeping_more() {
+        let tags = get_tags_by_name(vec!["1.4.0", "1.4.1", "1.5.0"], Duration::seconds(-1), 1);
+        let policy = SemverGroupPolicy::new(String::from("2"));
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_invalid_integer() {
+        let tags = get_tags_by_name(vec!["1.4.0", "1.4.1"], Duration::seconds(-1), 1);
+        for value in ["0", "asdf"] {
+            let policy = SemverGroupPolicy::new(String::from(value));
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![]);
+        }
+    }
+}
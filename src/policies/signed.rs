@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const SIGNED_LABEL: &str = "keep.signed";
+/// Suffix of the tags under which cosign stores the signatures of an image
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Get the name of the cosign signature tag of the digest. Cosign replaces the colon between
+/// the algorithm and the hash with a hyphen (`sha256:abc` -> `sha256-abc.sig`)
+pub fn signature_tag(digest: &str) -> String {
+    format!("{}{SIGNATURE_SUFFIX}", digest.replacen(':', "-", 1))
+}
+
+/// Policy to protect all images which are signed with cosign. An image is signed when the repository
+/// contains the signature tag (`sha256-<digest>.sig`) of its digest. The signature tags of the signed
+/// images are protected as well
+/// # Example
+/// ```
+/// let policy = SignedPolicy::new("true");
+///
+/// // returns all tags of images with a signature tag and the signature tags themselves
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SignedPolicy {
+    enabled: bool
+}
+
+impl SignedPolicy {
+    pub fn new(value: &str) -> Self {
+        let enabled = value.trim().parse::<bool>().unwrap_or_else(|_| {
+            info!("Received invalid value '{value}' for policy '{SIGNED_LABEL}'. Expected 'true' or 'false'");
+            false
+        });
+        Self { enabled }
+    }
+}
+
+impl Policy<Tag> for SignedPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if !self.enabled {
+            return vec![]
+        }
+        let names = elements.iter().map(|tag| tag.name.as_str()).collect::<HashSet<_>>();
+        let signed = elements.iter()
+            .map(|tag| tag.digest.as_str())
+            .filter(|digest| names.contains(signature_tag(digest).as_str()))
+            .collect::<HashSet<_>>();
+        let signatures = signed.iter().map(|digest| signature_tag(digest)).collect::<HashSet<_>>();
+
+        elements.iter()
+            .filter(|tag| signed.contains(tag.digest.as_str()) || signatures.contains(&tag.name))
+            .cloned()
+            .collect()
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Requirement
+    }
+
+    fn id(&self) -> &'static str {
+        SIGNED_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policies::signed::{signature_tag, SignedPolicy};
+    use crate::policies::Policy;
+    use crate::test::get_tags_with_digest;
+
+    #[test]
+    pub fn test_signature_tag() {
+        assert_eq!(signature_tag("sha256:abc"), "sha256-abc.sig");
+    }
+
+    #[test]
+    pub fn test_signed() {
+        let policy = SignedPolicy::new("true");
+        assert!(policy.enabled());
+
+        let tags = get_tags_with_digest(vec![
+            ("v1", "sha256:1"), ("latest", "sha256:1"), ("sha256-1.sig", "sha256:s1"),
+            ("v2", "sha256:2"),
+            ("sha256-3.sig", "sha256:s3")
+        ]);
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[1].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_disabled() {
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("sha256-1.sig", "sha256:s1")]);
+        for value in ["false", "yes"] {
+            let policy = SignedPolicy::new(value);
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![]);
+        }
+    }
+}
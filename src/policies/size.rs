@@ -51,6 +51,10 @@ impl Policy<Tag> for SizePolicy {
     fn enabled(&self) -> bool {
         self.size.is_some()
     }
+
+    fn reason(&self, tag: &Tag) -> String {
+        format!("{SIZE_LABEL}: {} >= {} bytes", tag.size, self.size.unwrap_or_default())
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,133 @@
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, parse_size, Policy};
+
+pub const SIZE_MIN_LABEL: &str = "size.min";
+
+/// Policy to match all tags which are smaller than a given blob size
+/// # Example
+/// ```
+/// let policy = SizeMinPolicy::new("1 MiB");
+///
+/// // returns all tags which are smaller than 1 MiB
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SizeMinPolicy {
+    size: Option<u64>
+}
+
+impl SizeMinPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.is_empty() {
+            Self { size: None }
+        } else {
+            let size = parse_size(value);
+            if size.is_none() {
+                info!("Received invalid min size '{value}'")
+            }
+            Self { size }
+        }
+    }
+}
+
+impl Policy<Tag> for SizeMinPolicy {
+    fn affects(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        if let Some(size) = self.size {
+            tags.into_iter().filter(|tag| tag.size < size).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        SIZE_MIN_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.size.is_some()
+    }
+
+    fn reason(&self, tag: &Tag) -> String {
+        format!("{SIZE_MIN_LABEL}: {} < {} bytes", tag.size, self.size.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::api::tag::Tag;
+    use crate::policies::Policy;
+    use crate::policies::size_min::SizeMinPolicy;
+    use crate::test::get_tags;
+
+    fn get_current_tags() -> Vec<Tag> {
+        get_tags(vec![
+            ("first", Duration::hours(-5), 1_200_000),
+            ("second", Duration::minutes(-5), 1_000),
+            ("third", Duration::minutes(-30), 100_000_000),
+            ("fourth", Duration::minutes(-10), 100_000),
+            ("fifth", Duration::seconds(-15), 1_048_576),
+            ("sixth", Duration::minutes(-50), 0)
+        ])
+    }
+
+    #[test]
+    pub fn test_matching() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("1 MiB");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[3].clone(), tags[5].clone()])
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![])
+    }
+
+    #[test]
+    pub fn test_default() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::default();
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![])
+    }
+
+    #[test]
+    pub fn test_invalid_size() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("120 asdf");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![])
+    }
+
+    #[test]
+    pub fn test_negative_size() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("-1 MiB");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![])
+    }
+
+    #[test]
+    pub fn test_without_unit() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("1_048_576");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[3].clone(), tags[5].clone()])
+    }
+
+    #[test]
+    pub fn test_reason() {
+        let tags = get_current_tags();
+        let policy = SizeMinPolicy::new("1 MiB");
+        assert_eq!(policy.reason(&tags[1]), "size.min: 1000 < 1048576 bytes");
+    }
+}
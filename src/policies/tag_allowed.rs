@@ -0,0 +1,94 @@
+use log::info;
+use regex::Regex;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const TAG_ALLOWED_LABEL: &str = "tag.allowed";
+
+/// Policy to match all tags whose name doesn't match the provided
+/// regex pattern. This is the inverse of the tag pattern policy and
+/// allows enforcing a naming convention by pruning all violating tags
+/// # Example
+/// ```
+/// let policy = TagAllowedPolicy::new("^(v\\d+\\.\\d+\\.\\d+|latest)$");
+///
+/// // returns all tags which are neither `latest` nor a semantic version
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TagAllowedPolicy {
+    pattern: Option<Regex>
+}
+
+impl TagAllowedPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.trim() == "" {
+            return Self { pattern: None }
+        }
+        match Regex::new(value) {
+            Ok(regex) => Self { pattern: Some(regex) },
+            Err(err) => {
+                info!("Received invalid pattern '{value}'. Reason: {err}");
+                Self { pattern: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for TagAllowedPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if let Some(pattern) = &self.pattern {
+            elements.into_iter().filter(|tag| !pattern.is_match(&tag.name)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        TAG_ALLOWED_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.pattern.is_some()
+    }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        format!("{TAG_ALLOWED_LABEL}: doesn't match '{}'", self.pattern.as_ref().map(|pattern| pattern.as_str()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::tag_allowed::TagAllowedPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_not_matching() {
+        let tags = get_tags_by_name(vec!["v1.2.3", "latest", "main", "v1.2", "tset", "manual-push"], Duration::seconds(1), 1);
+        let policy = TagAllowedPolicy::new(r"^(v\d+\.\d+\.\d+|latest|main)$");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), tags[3..].to_vec());
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let tags = get_tags_by_name(vec!["v1.2.3", "manual-push"], Duration::seconds(1), 1);
+        let policy = TagAllowedPolicy::new("");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_invalid_regex() {
+        let tags = get_tags_by_name(vec!["v1.2.3", "manual-push"], Duration::seconds(1), 1);
+        let policy = TagAllowedPolicy::new("([a-z]+");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+}
@@ -0,0 +1,109 @@
+use globset::{Glob, GlobMatcher};
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const TAG_GLOB_LABEL: &str = "tag.glob";
+
+/// Policy to match all tags whose whole name matches the provided
+/// shell-style glob pattern
+/// # Example
+/// ```
+/// let policy = TagGlobPolicy::new("v*-rc?");
+///
+/// // returns all tags whose name starts with `v` and ends in `-rc`
+/// // followed by exactly one character (e.g. `v1.2-rc1`)
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TagGlobPolicy {
+    glob: Option<GlobMatcher>
+}
+
+impl TagGlobPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.trim() == "" {
+            return Self { glob: None }
+        }
+        match Glob::new(value) {
+            Ok(glob) => Self { glob: Some(glob.compile_matcher()) },
+            Err(err) => {
+                info!("Received invalid glob '{value}'. Reason: {err}");
+                Self { glob: None }
+            }
+        }
+    }
+}
+
+impl Policy<Tag> for TagGlobPolicy {
+    fn affects(&self, elements: Vec<Tag>) -> Vec<Tag> {
+        if let Some(glob) = &self.glob {
+            elements.into_iter().filter(|tag| glob.is_match(&tag.name)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        TAG_GLOB_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.glob.is_some()
+    }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        format!("{TAG_GLOB_LABEL}: matches '{}'", self.glob.as_ref().map(|glob| glob.glob().glob()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::policies::Policy;
+    use crate::policies::tag_glob::TagGlobPolicy;
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    pub fn test_prefix() {
+        let tags = get_tags_by_name(vec!["v1.0", "v2-rc1", "latest", "dev-v1"], Duration::seconds(1), 1);
+        let policy = TagGlobPolicy::new("v*");
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone(), tags[1].clone()]);
+        assert_eq!(policy.reason(&tags[0]), "tag.glob: matches 'v*'");
+    }
+
+    #[test]
+    pub fn test_infix() {
+        let tags = get_tags_by_name(vec!["v1.0", "v2-rc1", "1.4-rc", "rc-1"], Duration::seconds(1), 1);
+        let policy = TagGlobPolicy::new("*-rc*");
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[2].clone()]);
+    }
+
+    #[test]
+    pub fn test_single_character() {
+        let tags = get_tags_by_name(vec!["v1", "v12", "v", "w1"], Duration::seconds(1), 1);
+        let policy = TagGlobPolicy::new("v?");
+        assert_eq!(policy.affects(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    pub fn test_empty() {
+        let tags = get_tags_by_name(vec!["v1", "latest"], Duration::seconds(1), 1);
+        let policy = TagGlobPolicy::new(" ");
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_invalid_glob() {
+        let tags = get_tags_by_name(vec!["v1", "latest"], Duration::seconds(1), 1);
+        let policy = TagGlobPolicy::new("v[1"); // the glob is invalid
+        assert!(!policy.enabled());
+        assert_eq!(policy.affects(tags), vec![]);
+    }
+}
@@ -4,6 +4,8 @@ use crate::api::tag::Tag;
 use crate::policies::{AffectionType, Policy};
 
 pub const TAG_PATTERN_LABEL: &str = "tag.pattern";
+/// Whether the tag pattern has to match the whole name of a tag
+pub const TAG_PATTERN_ANCHORED_LABEL: &str = "tag.pattern.anchored";
 
 /// Policy to match all tags whose name matches the provided
 /// regex pattern
@@ -61,6 +63,10 @@ impl Policy<Tag> for TagPatternPolicy {
     fn enabled(&self) -> bool {
         self.pattern.is_some()
     }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        format!("{TAG_PATTERN_LABEL}: matches '{}'", self.pattern.as_ref().map(|pattern| pattern.as_str()).unwrap_or_default())
+    }
 }
 
 
@@ -0,0 +1,134 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::info;
+use crate::api::tag::Tag;
+use crate::policies::{AffectionType, Policy};
+
+pub const WINDOW_LABEL: &str = "window";
+
+/// Policy to match all tags created within a time window. The window is given as `<start>..<end>` where
+/// both bounds are either a date (`2024-01-01`) at midnight UTC or a RFC 3339 timestamp. The start is
+/// inclusive whereas the end is exclusive
+/// # Example
+/// ```
+/// let policy = WindowPolicy::new("2024-01-01..2024-02-01");
+///
+/// // returns all tags created in January 2024
+/// let affected = policy.affects(&tags);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WindowPolicy {
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>
+}
+
+impl WindowPolicy {
+    pub fn new(value: &str) -> Self {
+        if value.is_empty() {
+            return Self { window: None }
+        }
+        let window = value.split_once("..").and_then(|(start, end)| Some((parse_bound(start)?, parse_bound(end)?)));
+        match window {
+            Some((start, end)) if start < end => Self { window: Some((start, end)) },
+            Some(_) => {
+                info!("Received reversed window '{value}'. Expected the start to be before the end");
+                Self { window: None }
+            },
+            None => {
+                info!("Received invalid window '{value}'. Expected '<start>..<end>'");
+                Self { window: None }
+            }
+        }
+    }
+}
+
+fn parse_bound(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|date| date.and_utc())
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|date| date.with_timezone(&Utc))
+}
+
+impl Policy<Tag> for WindowPolicy {
+    fn affects(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        if let Some((start, end)) = self.window {
+            tags.into_iter().filter(|tag| tag.created >= start && tag.created < end).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn affection_type(&self) -> AffectionType {
+        AffectionType::Target
+    }
+
+    fn id(&self) -> &'static str {
+        WINDOW_LABEL
+    }
+
+    fn enabled(&self) -> bool {
+        self.window.is_some()
+    }
+
+    fn reason(&self, _tag: &Tag) -> String {
+        let (start, end) = self.window.unwrap_or_default();
+        format!("{WINDOW_LABEL}: created between {start} and {end}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, SecondsFormat, Utc};
+    use crate::api::tag::Tag;
+    use crate::policies::Policy;
+    use crate::policies::window::WindowPolicy;
+    use crate::test::get_tags;
+
+    fn get_current_tags() -> Vec<Tag> {
+        get_tags(vec![
+            ("first", Duration::days(-10), 1),
+            ("second", Duration::days(-6), 1),
+            ("third", Duration::days(-4), 1),
+            ("fourth", Duration::days(-1), 1)
+        ])
+    }
+
+    fn window(start: Duration, end: Duration) -> String {
+        let now = Utc::now();
+        format!("{}..{}", (now + start).to_rfc3339_opts(SecondsFormat::Secs, true), (now + end).to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    #[test]
+    pub fn test_within_window() {
+        let tags = get_current_tags();
+        let policy = WindowPolicy::new(&window(Duration::days(-7), Duration::days(-2)));
+        assert!(policy.enabled());
+        assert_eq!(policy.affects(tags.clone()), vec![tags[1].clone(), tags[2].clone()])
+    }
+
+    #[test]
+    pub fn test_half_open() {
+        let tags = get_tags(vec![("start", Duration::zero(), 1)]);
+        let created = tags[0].created.to_rfc3339();
+        let end = (tags[0].created + Duration::days(1)).to_rfc3339();
+        assert_eq!(WindowPolicy::new(&format!("{created}..{end}")).affects(tags.clone()), tags);
+        let start = (tags[0].created - Duration::days(1)).to_rfc3339();
+        assert_eq!(WindowPolicy::new(&format!("{start}..{created}")).affects(tags), vec![]);
+    }
+
+    #[test]
+    pub fn test_dates() {
+        let policy = WindowPolicy::new("2024-01-01..2024-02-01");
+        assert!(policy.enabled());
+        assert_eq!(policy.reason(&get_current_tags()[0]), "window: created between 2024-01-01 00:00:00 UTC and 2024-02-01 00:00:00 UTC");
+    }
+
+    #[test]
+    pub fn test_invalid_window() {
+        let tags = get_current_tags();
+        for value in ["", "2024-02-01..2024-01-01", "2024-01-01..2024-01-01", "2024-01-01", "yesterday..today"] {
+            let policy = WindowPolicy::new(value);
+            assert!(!policy.enabled());
+            assert_eq!(policy.affects(tags.clone()), vec![])
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use chrono::{DateTime, Utc};
+use log::warn;
+use crate::api::tag::Tag;
+use crate::error::Error;
+use crate::scan::strip_host;
+
+/// Source of the times at which the tags of a registry were last pulled
+pub trait PullSource: Debug + Send + Sync {
+    /// Time at which the tag of the repository was last pulled. `None` when the source has no data about the tag
+    fn last_pulled(&self, repository: &str, tag: &str) -> Option<DateTime<Utc>>;
+
+    /// Add the pull times of the source to the tags of a repository
+    fn annotate(&self, repository: &str, tags: Vec<Tag>) -> Vec<Tag> {
+        tags.into_iter().map(|tag| {
+            let last_pulled = self.last_pulled(repository, &tag.name);
+            tag.with_last_pulled(last_pulled)
+        }).collect()
+    }
+}
+
+/// Pull times read from a json object whose keys are `repository:tag` references and whose values are
+/// RFC 3339 timestamps (e.g. `{"app:v1": "2024-01-01T00:00:00Z"}`). The registry host in front of the
+/// repository is removed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PullLog {
+    entries: HashMap<(String, String), DateTime<Utc>>
+}
+
+impl PullLog {
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let raw = serde_json::from_str::<HashMap<String, String>>(content).map_err(|err| Error::InvalidPullLog(err.to_string()))?;
+        let mut entries = HashMap::new();
+        for (reference, timestamp) in raw {
+            let parsed = strip_host(reference.trim()).rsplit_once(':')
+                .filter(|(repository, tag)| !repository.is_empty() && !tag.is_empty() && !tag.contains('/'))
+                .zip(DateTime::parse_from_rfc3339(timestamp.trim()).ok());
+            match parsed {
+                Some(((repository, tag), pulled)) => {
+                    entries.insert((repository.to_string(), tag.to_string()), pulled.with_timezone(&Utc));
+                },
+                None => warn!("Skipping pull log entry '{reference}' which isn't a 'repository:tag' reference with a valid timestamp")
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn read(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|err| Error::InvalidPullLog(err.to_string()))?;
+        Self::parse(&content)
+    }
+}
+
+impl PullSource for PullLog {
+    fn last_pulled(&self, repository: &str, tag: &str) -> Option<DateTime<Utc>> {
+        self.entries.get(&(repository.to_string(), tag.to_string())).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::pulls::{PullLog, PullSource};
+    use crate::test::get_tags_by_name;
+
+    #[test]
+    fn test_parse_pull_log() {
+        let log = PullLog::parse(r#"{
+            "app:v1": "2024-01-01T00:00:00Z",
+            "localhost:5000/team/api:latest": "2024-02-01T12:00:00+01:00",
+            "app": "2024-01-01T00:00:00Z",
+            "app:v2": "yesterday"
+        }"#).unwrap();
+        assert_eq!(log.last_pulled("app", "v1").map(|pulled| pulled.to_rfc3339()), Some(String::from("2024-01-01T00:00:00+00:00")));
+        assert_eq!(log.last_pulled("team/api", "latest").map(|pulled| pulled.to_rfc3339()), Some(String::from("2024-02-01T11:00:00+00:00")));
+        assert_eq!(log.last_pulled("app", "v2"), None);
+        assert_eq!(log.entries.len(), 2);
+        assert!(PullLog::parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let log = PullLog::parse(r#"{"app:v1": "2024-01-01T00:00:00Z"}"#).unwrap();
+        let tags = log.annotate("app", get_tags_by_name(vec!["v1", "v2"], Duration::zero(), 1));
+        assert!(tags[0].last_pulled.is_some());
+        assert_eq!(tags[1].last_pulled, None);
+    }
+}
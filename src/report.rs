@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use serde::Serialize;
+use crate::plan::PlannedTag;
+use crate::policies::semver::parse_version;
+
+/// Outcome of applying rules to a registry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunReport {
+    pub registry: String,
+    /// Names of the rules which were applied
+    pub rules: Vec<String>,
+    /// Amount of repositories whose tags were read
+    pub scanned: usize,
+    pub deleted: Vec<DeletedTag>,
+    /// Errors which didn't abort the run
+    pub errors: Vec<String>,
+    /// Names of the repositories in which at least one error occurred in the order of their first error
+    pub failed_repositories: Vec<String>
+}
+
+/// Which tag name represents the tags sharing a digest in logs and reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Representative {
+    /// The tag with the highest semantic version. Falls back to the lexically first tag when no tag is a semantic version
+    #[default]
+    Semver,
+    /// The lexically first tag
+    Lexical
+}
+
+impl Representative {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "semver" => Some(Self::Semver),
+            "lexical" => Some(Self::Lexical),
+            _ => None
+        }
+    }
+
+    /// Select the representative of tag names which share their digest
+    pub fn select<'a>(&self, names: &[&'a str]) -> Option<&'a str> {
+        let lexical = names.iter().min().copied();
+        match self {
+            Self::Semver => names.iter()
+                .filter_map(|name| parse_version(name).map(|version| (version, *name)))
+                .max_by(|(v1, n1), (v2, n2)| v1.cmp(v2).then_with(|| n2.cmp(n1)))
+                .map(|(_, name)| name)
+                .or(lexical),
+            Self::Lexical => lexical
+        }
+    }
+}
+
+/// Tags of a repository which share their digest
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasGroup {
+    pub repository: String,
+    pub digest: String,
+    /// Name of the tag representing the group
+    pub name: String,
+    /// Names of all other tags in the group
+    pub aliases: Vec<String>
+}
+
+/// Group `(repository, digest, name)` entries by their repository and digest. The groups are sorted by their
+/// repository and digest to get a stable output
+pub fn group_aliases<'a>(tags: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>, representative: Representative) -> Vec<AliasGroup> {
+    let mut groups: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+    for (repository, digest, name) in tags {
+        groups.entry((repository, digest)).or_default().push(name)
+    }
+    groups.into_iter().filter_map(|((repository, digest), names)| {
+        let name = representative.select(&names)?;
+        let mut aliases = names.into_iter().filter(|alias| *alias != name).map(String::from).collect::<Vec<_>>();
+        aliases.sort();
+        aliases.dedup();
+        Some(AliasGroup { repository: repository.to_string(), digest: digest.to_string(), name: name.to_string(), aliases })
+    }).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeletedTag {
+    pub repository: String,
+    pub name: String,
+    pub digest: String,
+    pub size: u64,
+    pub reason: String
+}
+
+impl RunReport {
+    pub fn new(registry: String) -> Self {
+        Self { registry, rules: vec![], scanned: 0, deleted: vec![], errors: vec![], failed_repositories: vec![] }
+    }
+
+    pub fn record_deletion(&mut self, repository: &str, tag: &PlannedTag) {
+        self.deleted.push(DeletedTag {
+            repository: repository.to_string(),
+            name: tag.name.clone(),
+            digest: tag.digest.clone(),
+            size: tag.size,
+            reason: tag.reason.clone()
+        })
+    }
+
+    pub fn record_error(&mut self, error: impl Display) {
+        self.errors.push(error.to_string())
+    }
+
+    /// Record an error which occurred in the given repository
+    pub fn record_repository_error(&mut self, repository: &str, error: impl Display) {
+        self.record_error(error);
+        if !self.failed_repositories.iter().any(|failed| failed == repository) {
+            self.failed_repositories.push(repository.to_string())
+        }
+    }
+
+    /// Amount of repositories in which at least one tag was deleted
+    pub fn affected_repositories(&self) -> usize {
+        let mut repositories = self.deleted.iter().map(|tag| tag.repository.as_str()).collect::<Vec<_>>();
+        repositories.sort();
+        repositories.dedup();
+        repositories.len()
+    }
+
+    /// Approximate amount of bytes freed by the deletions. Tags sharing their digest within a repository are only
+    /// counted once whereas layers shared between different images are counted for every image
+    pub fn freed_bytes(&self) -> u64 {
+        let mut images = self.deleted.iter().map(|tag| ((tag.repository.as_str(), tag.digest.as_str()), tag.size)).collect::<Vec<_>>();
+        images.sort();
+        images.dedup_by_key(|(image, _)| *image);
+        images.into_iter().map(|(_, size)| size).sum()
+    }
+
+    /// Deleted tags grouped by their repository and digest
+    pub fn deleted_images(&self, representative: Representative) -> Vec<AliasGroup> {
+        group_aliases(self.deleted.iter().map(|tag| (tag.repository.as_str(), tag.digest.as_str(), tag.name.as_str())), representative)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::plan::PlannedTag;
+    use crate::policies::format_size;
+    use crate::report::{Representative, RunReport};
+
+    fn planned(name: &str, digest: &str) -> PlannedTag {
+        PlannedTag { name: name.to_string(), digest: digest.to_string(), size: 10, created: None, rule: None, reason: String::new() }
+    }
+
+    #[test]
+    fn test_representative() {
+        let aliases = vec!["latest", "v1.2.0", "main", "v1.10.0", "v1.10"];
+        assert_eq!(Representative::Semver.select(&aliases), Some("v1.10.0"));
+        assert_eq!(Representative::Lexical.select(&aliases), Some("latest"));
+        assert_eq!(Representative::Semver.select(&["main", "latest"]), Some("latest"));
+        assert_eq!(Representative::Semver.select(&[]), None);
+    }
+
+    #[test]
+    fn test_deleted_images() {
+        let mut report = RunReport::new(String::from("registry"));
+        report.record_deletion("app", &planned("latest", "sha256:abc"));
+        report.record_deletion("app", &planned("v1.2.0", "sha256:abc"));
+        report.record_deletion("app", &planned("v1.1.0", "sha256:def"));
+        report.record_deletion("other", &planned("latest", "sha256:abc"));
+
+        let images = report.deleted_images(Representative::Semver);
+        assert_eq!(images.iter().map(|image| (image.repository.as_str(), image.name.as_str(), image.aliases.clone())).collect::<Vec<_>>(), vec![
+            ("app", "v1.2.0", vec![String::from("latest")]),
+            ("app", "v1.1.0", vec![]),
+            ("other", "latest", vec![])
+        ]);
+        assert_eq!(report.deleted_images(Representative::Lexical)[0].name, "latest");
+    }
+
+    #[test]
+    fn test_freed_bytes() {
+        let mut report = RunReport::new(String::from("registry"));
+        assert_eq!(report.freed_bytes(), 0);
+        report.record_deletion("app", &planned("latest", "sha256:abc"));
+        report.record_deletion("app", &planned("v1.2.0", "sha256:abc"));
+        report.record_deletion("app", &planned("v1.1.0", "sha256:def"));
+        report.record_deletion("other", &planned("latest", "sha256:abc"));
+        assert_eq!(report.freed_bytes(), 30);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(20 * 1024 * 1024 * 1024), "20.0 GiB");
+    }
+}
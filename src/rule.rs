@@ -1,16 +1,48 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use cron::Schedule;
 use log::{debug, info, warn};
 use crate::api::repository::Repository;
 use crate::api::tag::Tag;
-use crate::policies::{AffectionType, PolicyMap};
+use crate::policies::{AffectionType, anchor_pattern, Policy, PolicyMap};
 use crate::policies::age_min::{AGE_MIN_LABEL, AgeMinPolicy};
 use crate::policies::age_max::{AGE_MAX_LABEL, AgeMaxPolicy};
-use crate::policies::image_pattern::{IMAGE_PATTERN_LABEL, ImagePatternPolicy};
+use crate::policies::arch::{ARCH_LABEL, ArchPolicy};
+use crate::policies::base_image::{BASE_IMAGE_LABEL, BaseImagePolicy};
+use crate::policies::build_number::{BUILD_NUMBER_LABEL, BUILD_NUMBER_PATTERN_LABEL, BuildNumberPolicy};
+use crate::policies::cap_size::{CAP_SIZE_LABEL, CapSizePolicy};
+use crate::policies::deployed::{DEPLOYED_LABEL, DeployedPolicy};
+use crate::policies::group::{GROUP_LABEL, GroupPolicy};
+use crate::policies::image_glob::{IMAGE_GLOB_LABEL, ImageGlobPolicy};
+use crate::policies::image_pattern::{IMAGE_PATTERN_ANCHORED_LABEL, IMAGE_PATTERN_LABEL, ImagePatternPolicy};
+use crate::policies::keep_min::{KEEP_MIN_LABEL, KeepMinPolicy};
+use crate::policies::last_pulled::{LAST_PULLED_MAX_LABEL, LastPulledMaxPolicy};
+use crate::policies::min_tags::{MIN_TAGS_LABEL, MinTagsPolicy};
+use crate::policies::not::{NOT_SUFFIX, NotPolicy};
+use crate::policies::prefix::{PREFIX_LABEL, PrefixPolicy};
+use crate::policies::protect::{PROTECT_LABEL, ProtectPolicy};
+use crate::policies::protect_pattern::{PROTECT_PATTERN_ANCHORED_LABEL, PROTECT_PATTERN_LABEL, ProtectPatternPolicy};
 use crate::policies::revision::{REVISION_LABEL, RevisionPolicy};
+use crate::policies::semver::{SEMVER_LABEL, SemverPolicy};
+use crate::policies::semver_group::{SEMVER_GROUP_LABEL, SemverGroupPolicy};
+use crate::policies::signed::{SIGNED_LABEL, SignedPolicy};
 use crate::policies::size::{SIZE_LABEL, SizePolicy};
-use crate::policies::tag_pattern::{TAG_PATTERN_LABEL, TagPatternPolicy};
+use crate::policies::size_min::{SIZE_MIN_LABEL, SizeMinPolicy};
+use crate::policies::tag_glob::{TAG_GLOB_LABEL, TagGlobPolicy};
+use crate::policies::tag_pattern::{TAG_PATTERN_ANCHORED_LABEL, TAG_PATTERN_LABEL, TagPatternPolicy};
+use crate::policies::tag_allowed::{TAG_ALLOWED_LABEL, TagAllowedPolicy};
+use crate::policies::window::{WINDOW_LABEL, WindowPolicy};
+
+/// How the matches of the target policies of a rule are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MatchMode {
+    /// Elements matched by any target policy are affected
+    #[default]
+    Any,
+    /// Only elements matched by all enabled target policies are affected
+    All
+}
 
 #[derive(Debug)]
 pub struct Rule {
@@ -18,60 +50,149 @@ pub struct Rule {
     pub repository_policies: PolicyMap<Repository>,
     pub tag_policies: PolicyMap<Tag>,
     pub schedule: String,
-    pub tidy: Option<bool>
+    pub tidy: Option<bool>,
+    pub enabled: bool,
+    pub match_mode: MatchMode
 }
 
 impl Rule{
     pub fn new(name: String) -> Self {
-        Self { name, repository_policies: HashMap::new(), tag_policies: HashMap::new(), schedule: String::new(), tidy: None }
+        Self { name, repository_policies: HashMap::new(), tag_policies: HashMap::new(), schedule: String::new(), tidy: None, enabled: true, match_mode: MatchMode::Any }
+    }
+
+    /// Whether the repositories need to have their tag count loaded before calling
+    /// [`Rule::affected_repositories`]
+    pub fn requires_tag_count(&self) -> bool {
+        self.repository_policies.get(MIN_TAGS_LABEL).is_some_and(|policy| policy.enabled())
     }
 
     /// Get all repositories which are affected by the current rule
     pub fn affected_repositories(&self, repositories: Vec<Repository>) -> Vec<Repository> {
         let mut requirements = Vec::new();
-        let mut affected = HashSet::new();
+        let mut affected: Option<HashSet<Repository>> = None;
         for policy in self.repository_policies.values() {
             if policy.affection_type() == AffectionType::Requirement {
-                requirements.push(policy);
+                // disabled requirements don't protect anything, negating them would protect everything instead
+                if policy.enabled() {
+                    requirements.push(policy);
+                }
+                continue
+            }
+            if self.match_mode == MatchMode::All && !policy.enabled() {
                 continue
             }
             let affects = policy.affects(repositories.clone());
             debug!("Policy '{}' affected {} repositories", policy.id(), affects.len());
-            affected.extend(affects)
+            affected = Some(match (affected, self.match_mode) {
+                (Some(affected), MatchMode::All) => affected.into_iter().filter(|repo| affects.contains(repo)).collect(),
+                (Some(mut affected), MatchMode::Any) => {
+                    affected.extend(affects);
+                    affected
+                },
+                (None, _) => affects.into_iter().collect()
+            })
         }
 
-        let mut affected = affected.into_iter().collect::<Vec<_>>();
+        let mut affected = affected.unwrap_or_default().into_iter().collect::<Vec<_>>();
 
         for requirement in requirements {
-            let not_matching = requirement.affects(affected.clone());
+            let not_matching = requirement.affects(repositories.clone());
             affected.retain(|repo| !not_matching.contains(repo))
         }
 
         affected
     }
 
+    /// Get the digests of all tags which are protected by the enabled requirement policies of the rule
+    pub fn protected_digests(&self, tags: &[Tag]) -> HashSet<String> {
+        self.tag_policies.values()
+            .filter(|policy| policy.affection_type() == AffectionType::Requirement && policy.enabled())
+            .flat_map(|policy| policy.affects(tags.to_vec()))
+            .map(|tag| tag.digest)
+            .collect()
+    }
+
     /// Get all tags which are affected by the current rule
+    #[cfg(test)]
     pub fn affected_tags(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        self.affected_tags_with_reasons(tags).into_iter().map(|(tag, _)| tag).collect()
+    }
+
+    /// Get all tags which are affected by the current rule together with the reasons of all
+    /// target policies which affected the tag
+    #[cfg(test)]
+    pub fn affected_tags_with_reasons(&self, tags: Vec<Tag>) -> Vec<(Tag, String)> {
+        self.affected_tags_explained(tags).into_iter()
+            .map(|(tag, explanation)| (tag, explanation.reasons.join(", ")))
+            .collect()
+    }
+
+    /// Get all tags which are affected by the current rule together with the target policies which
+    /// matched them and the enabled requirement policies which didn't protect them
+    pub fn affected_tags_explained(&self, tags: Vec<Tag>) -> Vec<(Tag, Explanation)> {
         let mut requirements = Vec::new();
-        let mut affected = HashSet::new();
+        let mut targets = 0;
+        let mut affected: HashMap<Tag, Vec<(String, &'static str)>> = HashMap::new();
         for policy in self.tag_policies.values() {
             if policy.affection_type() == AffectionType::Requirement {
-                requirements.push(policy);
+                // disabled requirements don't protect anything, negating them would protect everything instead
+                if policy.enabled() {
+                    requirements.push(policy);
+                }
+                continue
+            }
+            if self.match_mode == MatchMode::All && !policy.enabled() {
                 continue
             }
+            targets += 1;
             let affects = policy.affects(tags.clone());
             debug!("Policy '{}' affected {} tags", policy.id(), affects.len());
-            affected.extend(affects)
+            for tag in affects {
+                let reason = policy.reason(&tag);
+                affected.entry(tag).or_default().push((reason, policy.id()));
+            }
         }
 
-        let mut affected = affected.into_iter().collect::<Vec<_>>();
+        if self.match_mode == MatchMode::All {
+            // every target policy adds exactly one reason to the tags it affects
+            affected.retain(|_, reasons| reasons.len() == targets)
+        }
 
+        let mut not_saved_by = Vec::new();
         for requirement in requirements {
-            let not_matching = requirement.affects(affected.clone());
-            affected.retain(|tag| !not_matching.contains(tag))
+            // tags are deleted by their digest, therefore all tags sharing a digest with a protected tag are kept as well
+            let protected = requirement.affects(tags.clone()).into_iter().map(|tag| tag.digest).collect::<HashSet<_>>();
+            affected.retain(|tag, _| !protected.contains(&tag.digest));
+            not_saved_by.push(requirement.id())
         }
+        not_saved_by.sort();
 
-        affected
+        affected.into_iter().map(|(tag, mut reasons)| {
+            reasons.sort();
+            let (reasons, matched) = reasons.into_iter().unzip();
+            (tag, Explanation { reasons, matched, not_saved_by: not_saved_by.clone() })
+        }).collect()
+    }
+}
+
+/// Why a tag is affected by a rule
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Explanation {
+    /// Reasons of the target policies which matched the tag
+    pub reasons: Vec<String>,
+    /// Ids of the target policies which matched the tag in the same order as the reasons
+    pub matched: Vec<&'static str>,
+    /// Ids of the enabled requirement policies of the rule which didn't protect the tag
+    pub not_saved_by: Vec<&'static str>
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reasons.join(", "))?;
+        if !self.not_saved_by.is_empty() {
+            write!(f, "; not saved by: {}", self.not_saved_by.join(", "))?;
+        }
+        Ok(())
     }
 }
 
@@ -79,39 +200,28 @@ impl Rule{
 /// any tag policies nor any repository policies
 pub fn parse_rule(name: String, policies: Vec<(String, &str)>) -> Option<Rule> {
     let mut rule = Rule::new(name.clone());
+    // the build number pattern only configures the build number policy
+    let build_number_pattern = policies.iter().find(|(policy_name, _)| policy_name == BUILD_NUMBER_PATTERN_LABEL).map(|(_, value)| value.to_string());
+    // anchored patterns (including negated ones) are wrapped before the policies are created
+    let anchored = [
+        (TAG_PATTERN_LABEL, TAG_PATTERN_ANCHORED_LABEL),
+        (IMAGE_PATTERN_LABEL, IMAGE_PATTERN_ANCHORED_LABEL),
+        (PROTECT_PATTERN_LABEL, PROTECT_PATTERN_ANCHORED_LABEL)
+    ].into_iter()
+        .filter(|(_, anchored_label)| is_anchored(&policies, anchored_label))
+        .map(|(label, _)| label)
+        .collect::<Vec<_>>();
     policies.into_iter().for_each(|(policy_name, value)| {
-        match policy_name.as_str() {
-            "schedule" => {
-                rule.schedule = parse_schedule(value).unwrap_or_default()
-            },
-            "tidy" => {
-                rule.tidy = value.parse::<bool>().map(Some).unwrap_or_else(|_| {
-                    info!("Received invalid value for field 'tidy'. Ignoring policy");
-                    None
-                })
-            },
-            AGE_MAX_LABEL => {
-                rule.tag_policies.insert(AGE_MAX_LABEL, Box::new(AgeMaxPolicy::new(value.to_string())));
-            },
-            AGE_MIN_LABEL => {
-                rule.tag_policies.insert(AGE_MIN_LABEL, Box::new(AgeMinPolicy::new(value.to_string())));
-            },
-            IMAGE_PATTERN_LABEL => {
-                rule.repository_policies.insert(IMAGE_PATTERN_LABEL, Box::new(ImagePatternPolicy::new(value)));
-            },
-            TAG_PATTERN_LABEL => {
-                rule.tag_policies.insert(TAG_PATTERN_LABEL, Box::new(TagPatternPolicy::new(value)));
-            }
-            REVISION_LABEL => {
-                rule.tag_policies.insert(REVISION_LABEL, Box::new(RevisionPolicy::new(value.to_string())));
-            },
-            SIZE_LABEL => {
-                rule.tag_policies.insert(SIZE_LABEL, Box::new(SizePolicy::new(value)));
-            }
-            other => {
-                warn!("Found unknown policy '{other}' for rule '{name}'. Ignoring policy")
-            }
+        let inner_name = policy_name.strip_suffix(NOT_SUFFIX);
+        let value = if anchored.contains(&inner_name.unwrap_or(&policy_name)) {
+            anchor_pattern(value)
+        } else {
+            value.to_string()
         };
+        match inner_name {
+            Some(inner_name) => insert_negated_policy(&mut rule, inner_name, &value, &build_number_pattern),
+            None => insert_policy(&mut rule, &policy_name, &value, &build_number_pattern)
+        }
     });
 
     if rule.tag_policies.is_empty() && rule.repository_policies.is_empty() && rule.schedule.is_empty() {
@@ -122,6 +232,143 @@ pub fn parse_rule(name: String, policies: Vec<(String, &str)>) -> Option<Rule> {
     }
 }
 
+/// Whether the anchoring option with the given label is enabled
+fn is_anchored(policies: &[(String, &str)], anchored_label: &str) -> bool {
+    policies.iter()
+        .find(|(policy_name, _)| policy_name == anchored_label)
+        .is_some_and(|(_, value)| value.trim().parse::<bool>().unwrap_or_else(|_| {
+            info!("Received invalid value '{value}' for field '{anchored_label}'. Expected 'true' or 'false'. Ignoring field");
+            false
+        }))
+}
+
+/// Negate the policy with the given name and insert it into the rule
+fn insert_negated_policy(rule: &mut Rule, policy_name: &str, value: &str, build_number_pattern: &Option<String>) {
+    let mut inner = Rule::new(rule.name.clone());
+    insert_policy(&mut inner, policy_name, value, build_number_pattern);
+    if let Some((_, policy)) = inner.tag_policies.into_iter().next() {
+        let policy = NotPolicy::new(policy);
+        rule.tag_policies.insert(policy.id(), Box::new(policy));
+    } else if let Some((_, policy)) = inner.repository_policies.into_iter().next() {
+        let policy = NotPolicy::new(policy);
+        rule.repository_policies.insert(policy.id(), Box::new(policy));
+    } else {
+        warn!("Found policy '{policy_name}{NOT_SUFFIX}' which can't be negated for rule '{}'. Ignoring policy", rule.name)
+    }
+}
+
+fn insert_policy(rule: &mut Rule, policy_name: &str, value: &str, build_number_pattern: &Option<String>) {
+    match policy_name {
+        "schedule" => {
+            rule.schedule = parse_schedule(value).unwrap_or_default()
+        },
+        "tidy" => {
+            rule.tidy = value.parse::<bool>().map(Some).unwrap_or_else(|_| {
+                info!("Received invalid value for field 'tidy'. Ignoring policy");
+                None
+            })
+        },
+        "match" => {
+            rule.match_mode = match value {
+                "any" => MatchMode::Any,
+                "all" => MatchMode::All,
+                other => {
+                    info!("Received invalid value '{other}' for field 'match'. Expected 'any' or 'all'. Ignoring policy");
+                    MatchMode::Any
+                }
+            }
+        },
+        "enabled" => {
+            rule.enabled = value.parse::<bool>().unwrap_or_else(|_| {
+                info!("Received invalid value for field 'enabled'. Ignoring policy");
+                true
+            })
+        },
+        AGE_MAX_LABEL => {
+            rule.tag_policies.insert(AGE_MAX_LABEL, Box::new(AgeMaxPolicy::new(value.to_string())));
+        },
+        AGE_MIN_LABEL => {
+            rule.tag_policies.insert(AGE_MIN_LABEL, Box::new(AgeMinPolicy::new(value.to_string())));
+        },
+        IMAGE_PATTERN_LABEL => {
+            rule.repository_policies.insert(IMAGE_PATTERN_LABEL, Box::new(ImagePatternPolicy::new(value)));
+        },
+        IMAGE_GLOB_LABEL => {
+            rule.repository_policies.insert(IMAGE_GLOB_LABEL, Box::new(ImageGlobPolicy::new(value)));
+        },
+        MIN_TAGS_LABEL => {
+            rule.repository_policies.insert(MIN_TAGS_LABEL, Box::new(MinTagsPolicy::new(value.to_string())));
+        },
+        TAG_PATTERN_LABEL => {
+            rule.tag_policies.insert(TAG_PATTERN_LABEL, Box::new(TagPatternPolicy::new(value)));
+        }
+        TAG_GLOB_LABEL => {
+            rule.tag_policies.insert(TAG_GLOB_LABEL, Box::new(TagGlobPolicy::new(value)));
+        },
+        TAG_ALLOWED_LABEL => {
+            rule.tag_policies.insert(TAG_ALLOWED_LABEL, Box::new(TagAllowedPolicy::new(value)));
+        },
+        REVISION_LABEL => {
+            rule.tag_policies.insert(REVISION_LABEL, Box::new(RevisionPolicy::new(value.to_string())));
+        },
+        SEMVER_LABEL => {
+            rule.tag_policies.insert(SEMVER_LABEL, Box::new(SemverPolicy::new(value.to_string())));
+        },
+        GROUP_LABEL => {
+            rule.tag_policies.insert(GROUP_LABEL, Box::new(GroupPolicy::new(value)));
+        },
+        SEMVER_GROUP_LABEL => {
+            rule.tag_policies.insert(SEMVER_GROUP_LABEL, Box::new(SemverGroupPolicy::new(value.to_string())));
+        },
+        ARCH_LABEL => {
+            rule.tag_policies.insert(ARCH_LABEL, Box::new(ArchPolicy::new(value)));
+        },
+        SIZE_LABEL => {
+            rule.tag_policies.insert(SIZE_LABEL, Box::new(SizePolicy::new(value)));
+        },
+        CAP_SIZE_LABEL => {
+            rule.tag_policies.insert(CAP_SIZE_LABEL, Box::new(CapSizePolicy::new(value)));
+        },
+        WINDOW_LABEL => {
+            rule.tag_policies.insert(WINDOW_LABEL, Box::new(WindowPolicy::new(value)));
+        },
+        SIZE_MIN_LABEL => {
+            rule.tag_policies.insert(SIZE_MIN_LABEL, Box::new(SizeMinPolicy::new(value)));
+        },
+        DEPLOYED_LABEL => {
+            rule.tag_policies.insert(DEPLOYED_LABEL, Box::new(DeployedPolicy::new(value)));
+        },
+        PROTECT_LABEL => {
+            rule.tag_policies.insert(PROTECT_LABEL, Box::new(ProtectPolicy::new(value)));
+        },
+        PROTECT_PATTERN_LABEL => {
+            rule.tag_policies.insert(PROTECT_PATTERN_LABEL, Box::new(ProtectPatternPolicy::new(value)));
+        },
+        PREFIX_LABEL => {
+            rule.tag_policies.insert(PREFIX_LABEL, Box::new(PrefixPolicy::new(value)));
+        },
+        BASE_IMAGE_LABEL => {
+            rule.tag_policies.insert(BASE_IMAGE_LABEL, Box::new(BaseImagePolicy::new(value.to_string())));
+        },
+        BUILD_NUMBER_LABEL => {
+            rule.tag_policies.insert(BUILD_NUMBER_LABEL, Box::new(BuildNumberPolicy::new(value.to_string(), build_number_pattern.clone())));
+        },
+        LAST_PULLED_MAX_LABEL => {
+            rule.tag_policies.insert(LAST_PULLED_MAX_LABEL, Box::new(LastPulledMaxPolicy::new(value.to_string())));
+        },
+        KEEP_MIN_LABEL => {
+            rule.tag_policies.insert(KEEP_MIN_LABEL, Box::new(KeepMinPolicy::new(value.to_string())));
+        },
+        SIGNED_LABEL => {
+            rule.tag_policies.insert(SIGNED_LABEL, Box::new(SignedPolicy::new(value)));
+        },
+        BUILD_NUMBER_PATTERN_LABEL | TAG_PATTERN_ANCHORED_LABEL | IMAGE_PATTERN_ANCHORED_LABEL | PROTECT_PATTERN_ANCHORED_LABEL => {}
+        other => {
+            warn!("Found unknown policy '{other}' for rule '{}'. Ignoring policy", rule.name)
+        }
+    };
+}
+
 /// Parse a cron schedule string
 /// # Example
 /// ```
@@ -147,8 +394,8 @@ mod test {
     use crate::policies::revision::REVISION_LABEL;
     use crate::policies::size::SIZE_LABEL;
     use crate::policies::tag_pattern::TAG_PATTERN_LABEL;
-    use crate::rule::{parse_rule, parse_schedule};
-    use crate::test::{get_repositories, get_tags, get_tags_by_name};
+    use crate::rule::{MatchMode, parse_rule, parse_schedule};
+    use crate::test::{get_repositories, get_repositories_with_tag_count, get_tags, get_tags_by_name, get_tags_with_digest};
 
     fn get_labels<'a>(raw: Vec<(&'a str, &'a str)>) -> Vec<(String, &'a str)> {
         let mut labels: Vec<(String, &'a str)> = Vec::new();
@@ -212,8 +459,25 @@ mod test {
         assert_eq!(parsed.tag_policies.len(), 2);
         assert_eq!(parsed.repository_policies.len(), 0);
         assert_eq!(parsed.tidy, Some(false));
-        assert!(parsed.tag_policies.get(AGE_MAX_LABEL).is_some());
-        assert!(parsed.tag_policies.get(AGE_MIN_LABEL).is_some());
+        assert!(parsed.tag_policies.contains_key(AGE_MAX_LABEL));
+        assert!(parsed.tag_policies.contains_key(AGE_MIN_LABEL));
+    }
+
+    #[test]
+    fn test_disabled_rule() {
+        let labels = get_labels(vec![
+            ("revisions", "10"),
+            ("enabled", "false")
+        ]);
+        let parsed = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(!parsed.enabled);
+
+        let labels = get_labels(vec![
+            ("revisions", "10"),
+            ("enabled", "asdf")
+        ]);
+        let parsed = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(parsed.enabled);
     }
 
     #[test]
@@ -233,8 +497,8 @@ mod test {
         assert_eq!(parsed.tag_policies.len(), 2);
         assert_eq!(parsed.repository_policies.len(), 0);
         assert_eq!(parsed.tidy, None);
-        assert!(parsed.tag_policies.get(AGE_MAX_LABEL).is_some());
-        assert!(parsed.tag_policies.get(AGE_MIN_LABEL).is_some());
+        assert!(parsed.tag_policies.contains_key(AGE_MAX_LABEL));
+        assert!(parsed.tag_policies.contains_key(AGE_MIN_LABEL));
     }
 
     #[test]
@@ -258,12 +522,12 @@ mod test {
         assert_eq!(parsed.tag_policies.len(), 5);
         assert_eq!(parsed.repository_policies.len(), 1);
         assert_eq!(parsed.tidy, Some(true));
-        assert!(parsed.tag_policies.get(AGE_MAX_LABEL).is_some());
-        assert!(parsed.tag_policies.get(AGE_MIN_LABEL).is_some());
-        assert!(parsed.tag_policies.get(REVISION_LABEL).is_some());
-        assert!(parsed.tag_policies.get(TAG_PATTERN_LABEL).is_some());
-        assert!(parsed.tag_policies.get(SIZE_LABEL).is_some());
-        assert!(parsed.repository_policies.get(IMAGE_PATTERN_LABEL).is_some())
+        assert!(parsed.tag_policies.contains_key(AGE_MAX_LABEL));
+        assert!(parsed.tag_policies.contains_key(AGE_MIN_LABEL));
+        assert!(parsed.tag_policies.contains_key(REVISION_LABEL));
+        assert!(parsed.tag_policies.contains_key(TAG_PATTERN_LABEL));
+        assert!(parsed.tag_policies.contains_key(SIZE_LABEL));
+        assert!(parsed.repository_policies.contains_key(IMAGE_PATTERN_LABEL))
     }
 
     #[test]
@@ -322,6 +586,33 @@ mod test {
         assert_eq!(parsed.affected_tags(tags.clone()), vec![]);
     }
 
+    #[test]
+    fn test_only_disabled_requirement_tag_policies() {
+        let tags = get_tags(vec![
+            ("test", Duration::seconds(-10), 10),
+            ("another", Duration::hours(-10), 10)
+        ]);
+        let disabled = parse_rule(String::from("test-rule"), get_labels(vec![("age.min", "invalid")])).unwrap();
+        assert!(!disabled.tag_policies["age.min"].enabled());
+        assert_eq!(disabled.affected_tags(tags.clone()), vec![]);
+        let negated = parse_rule(String::from("test-rule"), get_labels(vec![("age.min.not", "invalid")])).unwrap();
+        assert_eq!(negated.affected_tags(tags.clone()), vec![]);
+
+        let enabled = parse_rule(String::from("test-rule"), get_labels(vec![("age.min", "1h")])).unwrap();
+        assert!(enabled.tag_policies["age.min"].enabled());
+        assert_eq!(enabled.affected_tags(tags.clone()), vec![]);
+    }
+
+    #[test]
+    fn test_only_disabled_requirement_repository_policies() {
+        let repositories = get_repositories_with_tag_count(vec![("small", 2), ("big", 20)]);
+        let disabled = parse_rule(String::from("test-rule"), get_labels(vec![("repository.min-tags", "invalid")])).unwrap();
+        assert!(!disabled.requires_tag_count());
+        assert_eq!(disabled.affected_repositories(repositories.clone()), vec![]);
+        let negated = parse_rule(String::from("test-rule"), get_labels(vec![("repository.min-tags.not", "invalid")])).unwrap();
+        assert_eq!(negated.affected_repositories(repositories.clone()), vec![]);
+    }
+
     #[test]
     fn test_without_repository_policies() {
         let labels = get_labels(vec![
@@ -354,7 +645,37 @@ mod test {
 
     #[test]
     fn test_only_requirement_repository_policies() {
-        // TODO: Implement this test case as soon as there is a requirement repository policy
+        let labels = get_labels(vec![
+            ("repository.min-tags", "10")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels);
+        assert!(rule.is_some());
+        let parsed = rule.unwrap();
+        assert!(parsed.requires_tag_count());
+
+        let repositories = get_repositories_with_tag_count(vec![("small", 2), ("big", 20)]);
+        // requirement policies are only used for filtering, not for matching
+        assert_eq!(parsed.affected_repositories(repositories), vec![]);
+    }
+
+    #[test]
+    fn test_min_tags_repository_policy() {
+        let labels = get_labels(vec![
+            ("image.pattern", "test-.+"),
+            ("repository.min-tags", "100")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(rule.requires_tag_count());
+
+        let repositories = get_repositories_with_tag_count(vec![
+            ("test-small", 5),
+            ("test-bloated", 150),
+            ("other-bloated", 300),
+            ("test-limit", 100)
+        ]);
+        let mut affected = rule.affected_repositories(repositories.clone());
+        affected.sort_by(|r1, r2| r1.name.cmp(&r2.name));
+        assert_eq!(affected, vec![repositories[1].clone(), repositories[3].clone()]);
     }
 
     #[test]
@@ -446,4 +767,317 @@ mod test {
         affected.sort_by(|t1, t2| t1.created.cmp(&t2.created).reverse());
         assert_eq!(affected, vec![tags[1].clone(), tags[3].clone(), tags[2].clone(), tags[5].clone(), tags[0].clone()]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_match_all() {
+        let tags = get_tags(vec![
+            ("first", Duration::hours(-5), 1_000),
+            ("second", Duration::minutes(-5), 1_200_000),
+            ("third", Duration::minutes(-30), 1_400_000),
+            ("fourth", Duration::minutes(-10), 100_000_000),
+            ("fifth", Duration::seconds(-15), 1_000_000),
+            ("sixth", Duration::minutes(-50), 1_000)
+        ]);
+        let labels = vec![
+            ("age.min", "5m"),
+            ("age.max", "30m"),
+            ("tag.pattern", "th$"),
+            ("revisions", ""),
+            ("image.pattern", "test-.+"),
+            ("image.pattern.not", "test-test")
+        ];
+        let rule = parse_rule(String::from("test-rule"), get_labels(labels.clone())).unwrap();
+        assert_eq!(rule.match_mode, MatchMode::Any);
+        let mut affected = rule.affected_tags(tags.clone());
+        affected.sort_by(|t1, t2| t1.created.cmp(&t2.created).reverse());
+        assert_eq!(affected, vec![tags[3].clone(), tags[2].clone(), tags[5].clone(), tags[0].clone()]);
+
+        let repositories = get_repositories(vec!["test-asdf", "test-", "test-test"]);
+        let mut affected = rule.affected_repositories(repositories.clone());
+        affected.sort_by(|r1, r2| r1.name.cmp(&r2.name));
+        assert_eq!(affected, vec![repositories[1].clone(), repositories[0].clone(), repositories[2].clone()]);
+
+        let rule = parse_rule(String::from("test-rule"), get_labels([labels, vec![("match", "all")]].concat())).unwrap();
+        assert_eq!(rule.match_mode, MatchMode::All);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[5].clone()]);
+        assert_eq!(rule.affected_repositories(repositories.clone()), vec![repositories[0].clone()]);
+    }
+
+    #[test]
+    fn test_invalid_match_mode() {
+        let rule = parse_rule(String::from("test-rule"), get_labels(vec![("match", "some"), ("age.max", "30m")])).unwrap();
+        assert_eq!(rule.match_mode, MatchMode::Any);
+    }
+
+    #[test]
+    fn test_protect_deployed() {
+        let labels = get_labels(vec![
+            ("tag.pattern", ".+"),
+            ("protect-deployed", "prod")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("v2", "sha256:2"), ("deployed-prod", "sha256:1")]);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[1].clone()]);
+
+        let tags = get_tags_with_digest(vec![("v1", "sha256:1"), ("v2", "sha256:2"), ("deployed-prod", "sha256:2")]);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    fn test_tag_allowed() {
+        let labels = get_labels(vec![
+            ("tag.allowed", r"^(v\d+\.\d+\.\d+|latest|main)$"),
+            ("protect-deployed", "prod")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags_with_digest(vec![("v1.0.0", "sha256:1"), ("latest", "sha256:2"), ("tset", "sha256:3"), ("manual", "sha256:4"), ("deployed-prod", "sha256:4")]);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[2].clone()]);
+    }
+
+    #[test]
+    fn test_negated_policy() {
+        let tags = get_tags_by_name(vec!["v1", "v2", "latest", "main"], Duration::seconds(-1), 1);
+        let rule = parse_rule(String::from("test-rule"), get_labels(vec![("tag.pattern", r"^v\d+")])).unwrap();
+        let negated = parse_rule(String::from("test-rule"), get_labels(vec![("tag.pattern.not", r"^v\d+")])).unwrap();
+        assert!(negated.tag_policies.contains_key("tag.pattern.not"));
+
+        let mut affected = rule.affected_tags(tags.clone());
+        affected.extend(negated.affected_tags(tags.clone()));
+        affected.sort_by(|t1, t2| t1.name.cmp(&t2.name));
+        let mut expected = tags.clone();
+        expected.sort_by(|t1, t2| t1.name.cmp(&t2.name));
+        assert_eq!(affected, expected);
+        assert!(parse_rule(String::from("test-rule"), get_labels(vec![("schedule.not", "* * * * * *")])).is_none());
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let tags = get_tags_by_name(vec!["test", "mytest-thing", "latest"], Duration::seconds(-1), 1);
+        let substring = parse_rule(String::from("test-rule"), get_labels(vec![("tag.pattern", "test")])).unwrap();
+        let mut affected = substring.affected_tags(tags.clone());
+        affected.sort_by(|t1, t2| t1.name.cmp(&t2.name));
+        assert_eq!(affected, vec![tags[2].clone(), tags[1].clone(), tags[0].clone()]);
+
+        let anchored = parse_rule(String::from("test-rule"), get_labels(vec![("tag.pattern", "test"), ("tag.pattern.anchored", "true")])).unwrap();
+        assert_eq!(anchored.affected_tags(tags.clone()), vec![tags[0].clone()]);
+        assert!(!anchored.tag_policies.contains_key("tag.pattern.anchored"));
+
+        let negated = parse_rule(String::from("test-rule"), get_labels(vec![("tag.pattern.not", "test|latest"), ("tag.pattern.anchored", "true")])).unwrap();
+        assert_eq!(negated.affected_tags(tags.clone()), vec![tags[1].clone()]);
+
+        let repositories = get_repositories(vec!["test", "mytest-thing"]);
+        let substring = parse_rule(String::from("test-rule"), get_labels(vec![("image.pattern", "test")])).unwrap();
+        assert_eq!(substring.affected_repositories(repositories.clone()).len(), 2);
+        let anchored = parse_rule(String::from("test-rule"), get_labels(vec![("image.pattern", "test"), ("image.pattern.anchored", "true")])).unwrap();
+        assert_eq!(anchored.affected_repositories(repositories.clone()), vec![repositories[0].clone()]);
+        let invalid = parse_rule(String::from("test-rule"), get_labels(vec![("image.pattern", "test"), ("image.pattern.anchored", "yes")])).unwrap();
+        assert_eq!(invalid.affected_repositories(repositories.clone()).len(), 2);
+    }
+
+    #[test]
+    fn test_glob_policies() {
+        let labels = get_labels(vec![
+            ("tag.glob", "v*"),
+            ("image.glob", "team/*")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(rule.tag_policies.contains_key("tag.glob"));
+        assert!(rule.repository_policies.contains_key("image.glob"));
+
+        let tags = get_tags_by_name(vec!["v1", "latest"], Duration::seconds(-1), 1);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[0].clone()]);
+        let repositories = get_repositories(vec!["team/api", "app"]);
+        assert_eq!(rule.affected_repositories(repositories.clone()), vec![repositories[0].clone()]);
+    }
+
+    #[test]
+    fn test_disabled_requirements() {
+        let labels = get_labels(vec![
+            ("age.max", "1d"),
+            ("tag.pattern", "^v"),
+            ("age.min", "invalid"),
+            ("keep.tags.not", "")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(!rule.tag_policies["age.min"].enabled());
+        assert!(!rule.tag_policies["keep.tags.not"].enabled());
+
+        let tags = get_tags(vec![("v1", Duration::days(-10), 1), ("latest", Duration::days(-10), 1), ("new", Duration::seconds(-1), 1)]);
+        let mut affected = rule.affected_tags_explained(tags.clone());
+        affected.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        assert_eq!(affected.iter().map(|(tag, _)| tag.clone()).collect::<Vec<_>>(), vec![tags[1].clone(), tags[0].clone()]);
+        assert!(affected.iter().all(|(_, explanation)| explanation.not_saved_by.is_empty()));
+
+        let labels = get_labels(vec![
+            ("image.pattern", "^test"),
+            ("repository.min-tags.not", "invalid")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        let repositories = get_repositories(vec!["test", "other"]);
+        assert_eq!(rule.affected_repositories(repositories.clone()), vec![repositories[0].clone()]);
+    }
+
+    #[test]
+    fn test_keep_tags() {
+        let labels = get_labels(vec![
+            ("age.max", "1d"),
+            ("keep.tags", "latest,stable")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags_by_name(vec!["latest", "stable", "v1"], Duration::days(-10), 1);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[2].clone()]);
+    }
+
+    #[test]
+    fn test_keep_tags_aliases() {
+        let labels = get_labels(vec![
+            ("age.max", "1d"),
+            ("keep.tags", "latest")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let mut tags = get_tags_by_name(vec!["latest", "v1", "v2"], Duration::days(-10), 1);
+        // deleting `v1` would delete the manifest of `latest` as well
+        tags[1].digest = tags[0].digest.clone();
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[2].clone()]);
+    }
+
+    #[test]
+    fn test_keep_min() {
+        let labels = get_labels(vec![
+            ("age.max", "1s"),
+            ("size", "1 B"),
+            ("keep.min", "3")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags(vec![
+            ("v1", Duration::days(-5), 10),
+            ("v2", Duration::days(-4), 10),
+            ("v3", Duration::days(-3), 10),
+            ("v4", Duration::days(-2), 10),
+            ("v5", Duration::days(-1), 10)
+        ]);
+        let mut affected = rule.affected_tags(tags.clone());
+        affected.sort_by_key(|tag| tag.created);
+        assert_eq!(affected, vec![tags[0].clone(), tags[1].clone()]);
+        assert_eq!(rule.affected_tags(tags[3..].to_vec()), vec![]);
+    }
+
+    #[test]
+    fn test_protect_pattern() {
+        let labels = get_labels(vec![
+            ("age.max", "2d"),
+            ("protect.pattern", "nightly-.*")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags(vec![
+            ("nightly-1", Duration::days(-5), 1),
+            ("nightly-2", Duration::days(-1), 1),
+            ("v1", Duration::days(-5), 1),
+            ("latest", Duration::days(-5), 1)
+        ]);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[0].clone()]);
+
+        // `nightly-1` can't be deleted without deleting the protected `latest` tag
+        let mut aliased = tags.clone();
+        aliased[3].digest = aliased[0].digest.clone();
+        assert_eq!(rule.affected_tags(aliased), vec![]);
+    }
+
+    #[test]
+    fn test_anchored_protect_pattern() {
+        let tags = get_tags_by_name(vec!["nightly", "nightly-1", "latest"], Duration::days(-5), 1);
+        let labels = get_labels(vec![
+            ("age.max", "2d"),
+            ("protect.pattern", "nightly"),
+            ("protect.pattern.anchored", "true")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert!(!rule.tag_policies.contains_key("protect.pattern.anchored"));
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    fn test_protect_newest_per_prefix() {
+        let labels = get_labels(vec![
+            ("age.max", "1d"),
+            ("protect-newest-per-prefix", "prod-,staging-,dev-")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags(vec![
+            ("prod-1", Duration::days(-10), 1),
+            ("prod-2", Duration::days(-9), 1),
+            ("staging-1", Duration::days(-8), 1),
+            ("staging-2", Duration::days(-7), 1),
+            ("dev-1", Duration::days(-6), 1),
+            ("dev-2", Duration::days(-5), 1),
+            ("other", Duration::days(-4), 1)
+        ]);
+        let mut affected = rule.affected_tags(tags.clone());
+        affected.sort_by_key(|tag| tag.created);
+        assert_eq!(affected, vec![tags[0].clone(), tags[2].clone(), tags[4].clone(), tags[6].clone()]);
+
+        // deleting `other` would delete the newest `prod-` tag which shares its digest
+        let mut aliased = tags.clone();
+        aliased[6].digest = aliased[1].digest.clone();
+        let mut affected = rule.affected_tags(aliased.clone());
+        affected.sort_by_key(|tag| tag.created);
+        assert_eq!(affected, vec![aliased[0].clone(), aliased[2].clone(), aliased[4].clone()]);
+    }
+
+    #[test]
+    fn test_build_number_pattern() {
+        let labels = get_labels(vec![
+            ("build-number.pattern", "^release-(\\d+)$"),
+            ("build-number.keep", "1")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+        assert_eq!(rule.tag_policies.len(), 1);
+
+        let tags = get_tags_by_name(vec!["release-999", "release-1000", "nightly-1"], Duration::seconds(1), 1);
+        assert_eq!(rule.affected_tags(tags.clone()), vec![tags[0].clone()]);
+    }
+
+    #[test]
+    fn test_deletion_reasons() {
+        let labels = get_labels(vec![
+            ("age.max", "30d"),
+            ("revisions", "1")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags(vec![("old", Duration::days(-45), 1), ("older", Duration::days(-60), 1), ("new", Duration::days(-1), 1)]);
+        let mut affected = rule.affected_tags_with_reasons(tags);
+        affected.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        assert_eq!(affected.len(), 2);
+        assert_eq!(affected[0].0.name, "old");
+        assert_eq!(affected[0].1, "age.max: 45d > 30d, revisions: older than the newest 1");
+        assert_eq!(affected[1].1, "age.max: 60d > 30d, revisions: older than the newest 1");
+    }
+
+    #[test]
+    fn test_explained_deletions() {
+        let labels = get_labels(vec![
+            ("age.max", "30d"),
+            ("tag.pattern", "^old"),
+            ("age.min", "10d"),
+            ("keep.tags", "latest")
+        ]);
+        let rule = parse_rule(String::from("test-rule"), labels).unwrap();
+
+        let tags = get_tags(vec![("old", Duration::days(-45), 1), ("latest", Duration::days(-45), 1), ("new", Duration::days(-1), 1)]);
+        let affected = rule.affected_tags_explained(tags);
+        assert_eq!(affected.len(), 1);
+        let (tag, explanation) = &affected[0];
+        assert_eq!(tag.name, "old");
+        assert_eq!(explanation.matched, vec!["age.max", "tag.pattern"]);
+        assert_eq!(explanation.not_saved_by, vec!["age.min", "keep.tags"]);
+        assert_eq!(explanation.to_string(), "age.max: 45d > 30d, tag.pattern: matches '^old'; not saved by: age.min, keep.tags");
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::{Display, Formatter};
+use log::warn;
+use crate::error::Error;
+
+/// Image flagged by a vulnerability scan report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanEntry {
+    pub repository: String,
+    pub reference: ScanReference
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanReference {
+    Tag(String),
+    Digest(String)
+}
+
+impl Display for ScanEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.reference {
+            ScanReference::Tag(tag) => write!(f, "{}:{tag}", self.repository),
+            ScanReference::Digest(digest) => write!(f, "{}@{digest}", self.repository)
+        }
+    }
+}
+
+/// Parse a scan report which is a json array of `repository:tag` or `repository@digest` entries. The registry
+/// host in front of the repository (e.g. `localhost:5000/app:v1`) is removed. Entries without a tag or digest
+/// are skipped since they don't identify a single image
+pub fn parse_scan_report(content: &str) -> Result<Vec<ScanEntry>, Error> {
+    let raw = serde_json::from_str::<Vec<String>>(content).map_err(|err| Error::InvalidScanReport(err.to_string()))?;
+    Ok(raw.iter().filter_map(|entry| {
+        let parsed = parse_entry(entry);
+        if parsed.is_none() {
+            warn!("Skipping scan report entry '{entry}' which neither contains a tag nor a digest")
+        }
+        parsed
+    }).collect())
+}
+
+fn parse_entry(entry: &str) -> Option<ScanEntry> {
+    let entry = strip_host(entry.trim());
+    let (repository, reference) = if let Some((repository, digest)) = entry.split_once('@') {
+        (repository, ScanReference::Digest(digest.to_string()))
+    } else {
+        match entry.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, ScanReference::Tag(tag.to_string())),
+            _ => return None
+        }
+    };
+    match &reference {
+        ScanReference::Tag(value) | ScanReference::Digest(value) if repository.is_empty() || value.is_empty() => None,
+        _ => Some(ScanEntry { repository: repository.to_string(), reference })
+    }
+}
+
+/// Remove the registry host from an image reference. Like docker the first path segment is considered a host
+/// when it contains a `.` or `:` or is `localhost`
+pub(crate) fn strip_host(entry: &str) -> &str {
+    match entry.split_once('/') {
+        Some((host, rest)) if host.contains('.') || host.contains(':') || host == "localhost" => rest,
+        _ => entry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scan::{parse_scan_report, ScanEntry, ScanReference};
+
+    fn entry(repository: &str, reference: ScanReference) -> ScanEntry {
+        ScanEntry { repository: repository.to_string(), reference }
+    }
+
+    #[test]
+    fn test_parse_scan_report() {
+        let report = r#"[
+            "localhost:5000/app:v1",
+            "registry.example.com/team/api@sha256:abc",
+            "team/web:latest",
+            "app",
+            "localhost:5000/app"
+        ]"#;
+        assert_eq!(parse_scan_report(report).unwrap(), vec![
+            entry("app", ScanReference::Tag(String::from("v1"))),
+            entry("team/api", ScanReference::Digest(String::from("sha256:abc"))),
+            entry("team/web", ScanReference::Tag(String::from("latest")))
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_scan_report() {
+        assert!(parse_scan_report(r#"{"Results":[]}"#).is_err());
+        assert!(parse_scan_report("invalid").is_err());
+    }
+}
@@ -1,8 +1,15 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use log::{error, info, warn};
+use std::time::Duration;
+use log::{debug, error, info, warn};
 use crate::instance::Instance;
-use crate::task::Task;
+use crate::listener::{Debouncer, PushEvent};
+use crate::order::{DEFAULT_ORDER_WINDOW, RunOrder};
+use crate::state::StateStore;
+use crate::task::{apply_rules, InFlight, Task};
+
+pub const RUN_ON_START_ENV: &str = "ABWART_RUN_ON_START";
+pub const PROBE_ENV: &str = "ABWART_PROBE";
 
 #[derive(Debug)]
 pub enum ScheduleReason {
@@ -14,37 +21,71 @@ pub enum ScheduleReason {
 #[derive(Debug)]
 pub enum DescheduleReason {
     RegistryStop,
-    ConfigUpdate
+    ConfigUpdate,
+    Shutdown
 }
 
 pub struct Scheduler {
     tasks: HashMap<String, Task>,
-    names: HashMap<String, String>
+    names: HashMap<String, String>,
+    debouncer: Debouncer,
+    state: Arc<StateStore>,
+    order: Arc<RunOrder>,
+    /// Runs of all instances which are currently in progress
+    in_flight: Arc<InFlight>,
+    /// Whether all scheduled instances only log their deletions
+    dry_run: bool,
+    /// Whether all scheduled instances apply their rules once when they're scheduled
+    run_on_start: bool,
+    /// Whether the connectivity of instances is checked when they're scheduled
+    probe: bool
 }
 
 impl Scheduler {
-    pub fn new() -> Self {
-        Self { tasks: HashMap::new(), names: HashMap::new() }
+    pub fn new(state: Arc<StateStore>) -> Self {
+        Self { tasks: HashMap::new(), names: HashMap::new(), debouncer: Debouncer::default(), state, order: Arc::new(RunOrder::new(DEFAULT_ORDER_WINDOW)), in_flight: Arc::new(InFlight::default()), dry_run: false, run_on_start: false, probe: false }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_run_on_start(mut self, run_on_start: bool) -> Self {
+        self.run_on_start = run_on_start;
+        self
+    }
+
+    pub fn with_probe(mut self, probe: bool) -> Self {
+        self.probe = probe;
+        self
     }
 
     /// Start scheduling a given instance
-    pub async fn schedule_instance(&mut self, instance: Instance, reason: ScheduleReason) {
+    pub async fn schedule_instance(&mut self, mut instance: Instance, reason: ScheduleReason) {
         if self.tasks.contains_key(instance.id.as_str()) {
             warn!("Received duplicate schedule request for registry '{}' ({reason:?}). Ignoring request", instance.name);
             return
         }
+        if self.probe {
+            instance.probe().await;
+        }
+        instance.load_config_artifact().await;
+        instance.dry_run |= self.dry_run;
+        instance.run_on_start |= self.run_on_start;
 
         let id = instance.id.clone();
         let name = instance.name.clone();
-        let mut task = Task::new(instance);
+        self.order.register(&name, &instance.after);
+        let mut task = Task::new(instance, self.state.clone(), self.order.clone(), self.in_flight.clone());
         self.names.insert(name.clone(), id.clone());
         match task.start().await {
             Ok(_) => {
-                info!("Added registry '{name}' to scheduler ({reason:?})");
+                info!(registry = name.as_str(); "Added registry '{name}' to scheduler ({reason:?})");
                 self.tasks.insert(id, task);
             },
             Err(err) => {
-                error!("Unable add registry '{name}' to scheduler ({reason:?}). Reason: {err}")
+                error!(registry = name.as_str(); "Unable add registry '{name}' to scheduler ({reason:?}). Reason: {err}")
             }
         }
     }
@@ -57,13 +98,14 @@ impl Scheduler {
             let name = instance.name.clone();
             match task.stop().await {
                 Ok(_) => {
-                    info!("Removed registry '{name}' from scheduler ({reason:?})");
+                    info!(registry = name.as_str(); "Removed registry '{name}' from scheduler ({reason:?})");
                     self.tasks.remove(id.as_str());
                     self.names.remove(&name);
+                    self.order.unregister(&name);
                     Some(instance)
                 },
                 Err(err) => {
-                    error!("Unable remove registry '{name}' from scheduler ({reason:?}). Reason: {err}");
+                    error!(registry = name.as_str(); "Unable remove registry '{name}' from scheduler ({reason:?}). Reason: {err}");
                     None
                 }
             }
@@ -73,6 +115,66 @@ impl Scheduler {
         }
     }
 
+    /// Schedule a cleanup of the pushed repository after the notification delay of its registry. Further
+    /// pushes to the same repository within the delay postpone the cleanup
+    pub fn schedule_push(&mut self, event: PushEvent) {
+        let Some(task) = self.names.get(&event.registry).and_then(|id| self.tasks.get(id)) else {
+            warn!("Received push event for unscheduled registry '{}'. Ignoring event", event.registry);
+            return
+        };
+        let instance = task.instance.clone();
+        let state = self.state.clone();
+        let in_flight = self.in_flight.clone();
+        let delay = instance.notify_delay;
+        let repository = event.repository.clone();
+        debug!("Scheduling cleanup of repository '{repository}' in registry '{}' in {delay:?}", instance.name);
+        self.debouncer.trigger(event, delay, async move {
+            let rules = instance.rule_names();
+            let (registry, repository) = (instance.name.as_str(), repository.as_str());
+            info!(registry = registry, repository = repository; "Applying rules to pushed repository '{repository}' in registry '{registry}'");
+            match in_flight.track(instance.run_rules(rules, Some(repository), &state)).await {
+                Ok(report) if report.errors.is_empty() => info!(registry = registry, repository = repository; "Successfully applied rules to repository '{repository}' in registry '{registry}'"),
+                Ok(report) => warn!(registry = registry, repository = repository, errors = report.errors.len(); "Applied rules to repository '{repository}' in registry '{registry}' with {} errors", report.errors.len()),
+                Err(err) => error!(registry = registry, repository = repository; "Unable to apply rules to repository '{repository}' in registry '{registry}'. Reason: {err}")
+            }
+        })
+    }
+
+    /// Apply all rules of the registry with the given name once in the background. <br>
+    /// Returns whether the registry is scheduled
+    pub fn trigger_run(&self, registry: &str) -> bool {
+        let Some(task) = self.names.get(registry).and_then(|id| self.tasks.get(id)) else {
+            warn!("Received run request for unscheduled registry '{registry}'. Ignoring request");
+            return false
+        };
+        let instance = task.instance.clone();
+        let state = self.state.clone();
+        let order = self.order.clone();
+        let in_flight = self.in_flight.clone();
+        let running = task.running.clone();
+        tokio::spawn(async move {
+            let rules = instance.rule_names();
+            info!("Applying rules '{}' to registry '{}' on request", rules.join(", "), instance.name);
+            apply_rules(&instance, &state, &order, &in_flight, &running, rules).await
+        });
+        true
+    }
+
+    /// Remove all instances from the scheduler and wait up to the timeout for the runs in progress to finish
+    pub async fn shutdown(&mut self, timeout: Duration) {
+        let ids = self.tasks.keys().cloned().collect::<Vec<String>>();
+        for id in ids {
+            self.deschedule_instance(id, DescheduleReason::Shutdown).await;
+        }
+        let runs = self.in_flight.count();
+        if runs > 0 {
+            info!("Waiting up to {timeout:?} for {runs} runs in progress to finish");
+            if !self.in_flight.wait(timeout).await {
+                warn!("{} runs were still in progress after {timeout:?}", self.in_flight.count())
+            }
+        }
+    }
+
     pub fn get_instance(&self, name: &str) -> Option<String> {
         self.names.get(name).cloned()
     }
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+use crate::policies::{format_duration, parse_duration};
+
+pub const FIRST_RUN_DRY_ENV: &str = "ABWART_FIRST_RUN_DRY";
+pub const SAFETY_PERIOD_ENV: &str = "ABWART_SAFETY_PERIOD";
+pub const REQUIRE_LOCK_ENV: &str = "ABWART_REQUIRE_LOCK";
+/// Locks which weren't released within 6 hours are considered abandoned by a crashed process
+const LOCK_TTL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+/// Held locks are refreshed every 30 minutes which keeps long runs from being taken over
+const LOCK_REFRESH: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+/// Persistent state which survives restarts of abwart
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    #[serde(default)]
+    pub registries: HashMap<String, RegistryState>,
+    /// End of the fleet-wide safety period which started on the first-ever start of abwart
+    #[serde(default)]
+    pub safety_period_end: Option<DateTime<Utc>>
+}
+
+/// Persistent state of a single registry identified by its name
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RegistryState {
+    /// Whether the rules of the registry were applied at least once
+    #[serde(default)]
+    pub has_run: bool,
+    /// Start of the last run of every rule which was applied to the whole registry
+    #[serde(default)]
+    pub last_runs: HashMap<String, DateTime<Utc>>
+}
+
+impl State {
+    pub fn path() -> String {
+        std::env::var("STATE_PATH").unwrap_or(String::from("state.json"))
+    }
+
+    /// Read the state from the given path. A missing state file results in an empty state
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, content).map_err(|err| err.to_string())
+    }
+}
+
+/// Lock of a registry which is released once dropped. Runs of the same process are serialized in memory whereas
+/// other processes are excluded by a lock file next to the state file. The lock file only protects against processes
+/// sharing the same state directory. While held the modification time of the file is refreshed in the background so
+/// the lock isn't considered abandoned during long runs
+#[derive(Debug)]
+pub struct RunLock {
+    /// The lock file or `None` when it couldn't be created and the lock isn't required
+    file: Option<(PathBuf, Sender<()>, JoinHandle<()>)>,
+    /// Released after the lock file was removed
+    _run: OwnedMutexGuard<()>
+}
+
+impl RunLock {
+    fn new(path: Option<PathBuf>, run: OwnedMutexGuard<()>) -> Self {
+        let file = path.map(|path| {
+            let (stop, stopped) = channel::<()>();
+            let refreshed = path.clone();
+            let handle = std::thread::spawn(move || {
+                // the sender is only dropped once the lock is released
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(LOCK_REFRESH) {
+                    if let Err(err) = refresh_lock(&refreshed) {
+                        warn!("Unable to refresh lock at '{}'. Reason: {err}", refreshed.display())
+                    }
+                }
+            });
+            (path, stop, handle)
+        });
+        Self { file, _run: run }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        if let Some((path, stop, handle)) = self.file.take() {
+            drop(stop);
+            let _ = handle.join();
+            if let Err(err) = std::fs::remove_file(&path) {
+                error!("Unable to release lock at '{}'. Reason: {err}", path.display())
+            }
+        }
+    }
+}
+
+/// Reason why the lock of a registry couldn't be acquired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    /// Another process holds the lock. Contains the holder as described in the lock file
+    Held(String),
+    /// The lock file couldn't be created (e.g. in a read-only directory) and the lock is required
+    Unavailable(String)
+}
+
+/// Set the modification time of a lock file to now
+fn refresh_lock(path: &Path) -> std::io::Result<()> {
+    OpenOptions::new().write(true).open(path)?.set_modified(SystemTime::now())
+}
+
+/// Shared access to the [`State`] which writes every change back to the state file
+#[derive(Debug)]
+pub struct StateStore {
+    path: String,
+    state: Mutex<State>,
+    /// Whether the first run of a newly discovered registry only previews its deletions
+    first_run_dry: bool,
+    /// Whether runs fail when the lock file of their registry can't be created
+    require_lock: bool,
+    /// Serializes the runs of every registry within this process
+    runs: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>
+}
+
+impl StateStore {
+    /// Create the store from the state file at the given path. Should the state file be absent (first-ever start) and
+    /// a safety period be provided the safety period starts now
+    pub fn new(path: String, first_run_dry: bool, safety_period: Option<Duration>) -> Self {
+        let first_start = !Path::new(&path).exists();
+        let state = State::load(&path).unwrap_or_else(|err| {
+            warn!("Unable to read state file at '{path}'. Using empty state instead. Reason: {err}");
+            State::default()
+        });
+        let store = Self { path, state: Mutex::new(state), first_run_dry, require_lock: false, runs: Mutex::default() };
+        if let Some(period) = safety_period.filter(|_| first_start) {
+            let end = Utc::now() + period;
+            info!("First start of abwart. Only previewing deletions during the safety period until {end}");
+            store.update(|state| state.safety_period_end = Some(end));
+        }
+        store
+    }
+
+    pub fn with_required_lock(mut self, require_lock: bool) -> Self {
+        self.require_lock = require_lock;
+        self
+    }
+
+    /// Create the store from the `STATE_PATH`, `ABWART_FIRST_RUN_DRY`, `ABWART_SAFETY_PERIOD` and `ABWART_REQUIRE_LOCK`
+    /// environment variables
+    pub fn from_env() -> Self {
+        let first_run_dry = std::env::var(FIRST_RUN_DRY_ENV).is_ok_and(|value| value.parse::<bool>().unwrap_or_else(|_| {
+            warn!("Received invalid value '{value}' for '{FIRST_RUN_DRY_ENV}'. Expected boolean. Using default (false) instead");
+            false
+        }));
+        let safety_period = std::env::var(SAFETY_PERIOD_ENV).ok().and_then(|value| {
+            let period = parse_duration(value.clone());
+            if period.is_none() {
+                warn!("Received invalid value '{value}' for '{SAFETY_PERIOD_ENV}'. Expected duration. Disabled safety period")
+            }
+            period
+        });
+        let require_lock = std::env::var(REQUIRE_LOCK_ENV).is_ok_and(|value| value.parse::<bool>().unwrap_or_else(|_| {
+            warn!("Received invalid value '{value}' for '{REQUIRE_LOCK_ENV}'. Expected boolean. Using default (false) instead");
+            false
+        }));
+        Self::new(State::path(), first_run_dry, safety_period).with_required_lock(require_lock)
+    }
+
+    pub fn has_run(&self, registry: &str) -> bool {
+        self.state.lock().map(|state| state.registries.get(registry).is_some_and(|registry| registry.has_run)).unwrap_or(false)
+    }
+
+    /// Why the next run of the registry should only preview its deletions. Returns `None` when the
+    /// run should delete the tags
+    pub fn dry_run_reason(&self, registry: &str) -> Option<String> {
+        let safety_period_end = self.state.lock().ok().and_then(|state| state.safety_period_end);
+        if let Some(end) = safety_period_end.filter(|end| *end > Utc::now()) {
+            Some(format!("Fleet-wide safety period is active for another {} (until {end})", format_duration(end - Utc::now())))
+        } else if self.first_run_dry && !self.has_run(registry) {
+            Some(format!("First run of registry '{registry}'"))
+        } else {
+            None
+        }
+    }
+
+    /// Remember that the rules of the registry were applied
+    pub fn mark_run(&self, registry: &str) {
+        self.update(|state| state.registries.entry(registry.to_string()).or_default().has_run = true)
+    }
+
+    /// Remember the start of a run of the given rules of the registry
+    pub fn mark_rules_run(&self, registry: &str, rules: &[String], start: DateTime<Utc>) {
+        self.update(|state| {
+            let last_runs = &mut state.registries.entry(registry.to_string()).or_default().last_runs;
+            rules.iter().for_each(|rule| { last_runs.insert(rule.clone(), start); })
+        })
+    }
+
+    /// Start of the last run of a rule of the registry
+    pub fn last_run(&self, registry: &str, rule: &str) -> Option<DateTime<Utc>> {
+        self.state.lock().ok().and_then(|state| state.registries.get(registry).and_then(|registry| registry.last_runs.get(rule).copied()))
+    }
+
+    /// Acquire the lock of the registry. Runs of this process wait for the run in progress to finish. Returns the holder
+    /// of the lock when another process holds it. Locks which weren't refreshed within the lock ttl are taken over
+    pub async fn lock(&self, registry: &str) -> Result<RunLock, LockError> {
+        let run = self.runs.lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(registry.to_string())
+            .or_default()
+            .clone();
+        let run = match run.clone().try_lock_owned() {
+            Ok(run) => run,
+            Err(_) => {
+                info!("Waiting for the run in progress in registry '{registry}' to finish");
+                run.lock_owned().await
+            }
+        };
+        let path = self.lock_file(registry)?;
+        Ok(RunLock::new(path, run))
+    }
+
+    /// Create the lock file of the registry. Returns `None` when the lock file can't be created and the lock isn't required
+    fn lock_file(&self, registry: &str) -> Result<Option<PathBuf>, LockError> {
+        let path = PathBuf::from(format!("{}.{registry}.lock", self.path));
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let _ = write!(file, "pid {} since {}", std::process::id(), Utc::now());
+                    return Ok(Some(path))
+                },
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    let age = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok().and_then(|modified| modified.elapsed().ok());
+                    if age.is_some_and(|age| age > LOCK_TTL) {
+                        warn!("Taking over abandoned lock at '{}'", path.display());
+                        let _ = std::fs::remove_file(&path);
+                        continue
+                    }
+                    return Err(LockError::Held(std::fs::read_to_string(&path).unwrap_or(String::from("unknown process"))))
+                },
+                Err(err) if self.require_lock => return Err(LockError::Unavailable(format!("Unable to create lock at '{}'. Reason: {err}", path.display()))),
+                Err(err) => {
+                    warn!("Unable to create lock at '{}'. Continuing without excluding other processes. Reason: {err}", path.display());
+                    return Ok(None)
+                }
+            }
+        }
+        Err(LockError::Held(String::from("unknown process")))
+    }
+
+    fn update(&self, update: impl FnOnce(&mut State)) {
+        match self.state.lock() {
+            Ok(mut state) => {
+                update(&mut state);
+                if let Err(err) = state.save(&self.path) {
+                    error!("Unable to write state file at '{}'. Reason: {err}", self.path)
+                }
+            },
+            Err(err) => error!("Unable to lock state. Reason: {err}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+    use chrono::{Duration, Utc};
+    use crate::state::{LOCK_TTL, LockError, refresh_lock, State, StateStore};
+    use crate::test::get_state_path;
+
+    #[test]
+    fn test_missing_state_file() {
+        let path = get_state_path("missing");
+        assert_eq!(State::load(&path).unwrap(), State::default());
+    }
+
+    #[test]
+    fn test_persisted_run() {
+        let path = get_state_path("persisted");
+        let store = StateStore::new(path.clone(), true, None);
+        assert!(store.dry_run_reason("registry").is_some());
+        store.mark_run("registry");
+        assert!(store.dry_run_reason("registry").is_none());
+        assert!(StateStore::new(path.clone(), true, None).has_run("registry"));
+        assert!(!StateStore::new(path, true, None).has_run("other"));
+    }
+
+    #[tokio::test]
+    async fn test_lock() {
+        let path = get_state_path("lock");
+        let store = StateStore::new(path.clone(), false, None);
+        let lock = store.lock("registry").await.unwrap();
+        // another process sharing the state file
+        let other = StateStore::new(path, false, None);
+        assert!(other.lock("registry").await.is_err_and(|err| matches!(err, LockError::Held(holder) if holder.starts_with(&format!("pid {}", std::process::id())))));
+        assert!(store.lock("other").await.is_ok());
+        drop(lock);
+        assert!(other.lock("registry").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lock_waits_within_process() {
+        let store = StateStore::new(get_state_path("lock-wait"), false, None);
+        let lock = store.lock("registry").await.unwrap();
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(100), store.lock("registry")).await.is_err());
+        let (waiting, _) = tokio::join!(store.lock("registry"), async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            drop(lock)
+        });
+        assert!(waiting.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_lock() {
+        let path = get_state_path("refresh-lock");
+        let store = StateStore::new(path.clone(), false, None);
+        let _lock = store.lock("registry").await.unwrap();
+        let file = PathBuf::from(format!("{path}.registry.lock"));
+        OpenOptions::new().write(true).open(&file).unwrap().set_modified(SystemTime::now() - LOCK_TTL * 2).unwrap();
+        refresh_lock(&file).unwrap();
+        assert!(matches!(StateStore::new(path, false, None).lock("registry").await, Err(LockError::Held(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_lock() {
+        let store = StateStore::new(String::from("/nonexistent/state.json"), false, None);
+        assert!(store.lock("registry").await.is_ok());
+        let store = StateStore::new(String::from("/nonexistent/state.json"), false, None).with_required_lock(true);
+        assert!(matches!(store.lock("registry").await, Err(LockError::Unavailable(_))));
+    }
+
+    #[test]
+    fn test_first_run_dry_disabled() {
+        let store = StateStore::new(get_state_path("disabled"), false, None);
+        assert!(store.dry_run_reason("registry").is_none());
+    }
+
+    #[test]
+    fn test_safety_period_only_on_first_start() {
+        let path = get_state_path("safety-period");
+        let store = StateStore::new(path.clone(), false, Some(Duration::hours(24)));
+        assert!(store.dry_run_reason("registry").is_some_and(|reason| reason.contains("safety period")));
+        assert!(State::load(&path).unwrap().safety_period_end.is_some());
+
+        let path = get_state_path("safety-period-existing");
+        State::default().save(&path).unwrap();
+        let store = StateStore::new(path, false, Some(Duration::hours(24)));
+        assert!(store.dry_run_reason("registry").is_none());
+    }
+
+    #[test]
+    fn test_last_runs() {
+        let path = get_state_path("last-runs");
+        let store = StateStore::new(path.clone(), false, None);
+        let start = Utc::now() - Duration::hours(1);
+        assert_eq!(store.last_run("registry", "dev"), None);
+        store.mark_rules_run("registry", &[String::from("dev"), String::from("nightly")], start);
+        assert_eq!(store.last_run("registry", "dev"), Some(start));
+
+        let store = StateStore::new(path, false, None);
+        assert_eq!(store.last_run("registry", "nightly"), Some(start));
+        assert_eq!(store.last_run("registry", "other"), None);
+        assert_eq!(store.last_run("other", "dev"), None);
+    }
+}
@@ -1,17 +1,33 @@
-use std::sync::Arc;
-use log::{debug, error, info};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use log::{debug, error, info, warn};
+use tokio::sync::Notify;
+use tokio::time::sleep;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use crate::error::Error;
 use crate::instance::Instance;
+use crate::order::RunOrder;
+use crate::state::StateStore;
 
 pub struct Task {
     pub instance: Arc<Instance>,
+    state: Arc<StateStore>,
+    order: Arc<RunOrder>,
+    in_flight: Arc<InFlight>,
+    /// Rules of the instance which are currently applied by any kind of run
+    pub running: Arc<RunningRules>,
     tx: Option<tokio::sync::mpsc::Sender<()>>
 }
 
 impl Task {
-    pub fn new(instance: Instance) -> Self {
-        Self { instance: Arc::new(instance), tx: None }
+    pub fn new(instance: Instance, state: Arc<StateStore>, order: Arc<RunOrder>, in_flight: Arc<InFlight>) -> Self {
+        Self { instance: Arc::new(instance), state, order, in_flight, running: Arc::new(RunningRules::default()), tx: None }
     }
 
     /// Start the scheduling process for all unique cron times of an instance
@@ -23,25 +39,66 @@ impl Task {
         let copy_name = name.clone();
         let instance = self.instance.clone();
 
+        let missed = if instance.catch_up && !instance.run_on_start {
+            missed_rules(&name, &bundles, &self.state, Utc::now())
+        } else {
+            vec![]
+        };
+        if !missed.is_empty() {
+            let instance = instance.clone();
+            let state = self.state.clone();
+            let order = self.order.clone();
+            let in_flight = self.in_flight.clone();
+            let running = self.running.clone();
+            tokio::spawn(async move {
+                info!("Catching up on rules '{}' of registry '{}' which missed their schedule", missed.join(", "), instance.name);
+                apply_rules(&instance, &state, &order, &in_flight, &running, missed).await
+            });
+        }
+
+        if instance.run_on_start {
+            let instance = instance.clone();
+            let state = self.state.clone();
+            let order = self.order.clone();
+            let in_flight = self.in_flight.clone();
+            let running = self.running.clone();
+            tokio::spawn(async move {
+                let rules = instance.rule_names();
+                info!("Applying rules '{}' to registry '{}' as initial run", rules.join(", "), instance.name);
+                apply_rules(&instance, &state, &order, &in_flight, &running, rules).await
+            });
+        }
+
+        let offset = instance.jitter_offset();
+        if !offset.is_zero() {
+            info!("Delaying scheduled runs of registry '{name}' by {offset:?}");
+        }
+
         let mut sched = JobScheduler::new().await.map_err(|err| Error::TaskCreationFailed(name.clone(), err.to_string()))?;
 
         for (cron, rules) in bundles {
             debug!("Cron '{cron}' with rules '{}'", rules.join(", "));
             let instance = instance.clone();
+            let state = self.state.clone();
+            let order = self.order.clone();
+            let in_flight = self.in_flight.clone();
             let copy_name = copy_name.clone();
+            let running = self.running.clone();
             let job = Job::new_async(cron.as_str(), move |_uuid, mut _l| {
                 let instance = instance.clone();
+                let state = state.clone();
+                let order = order.clone();
+                let in_flight = in_flight.clone();
                 let rules = rules.clone();
                 let name = copy_name.clone();
+                let running = running.clone();
 
                 Box::pin(async move {
                     let next_tick = _l.next_tick_for_job(_uuid).await;
                     debug!("Next tick for registry '{name}' is {:?}", next_tick.unwrap_or_default().unwrap_or_default());
+                    sleep(offset).await;
                     info!("Applying rules '{}' to registry '{name}'", rules.join(", "));
-                    match instance.apply_rules(rules.clone()).await {
-                        Ok(_) => info!("Successfully applied rules '{}' to registry '{name}'", rules.join(", ")),
-                        Err(err) => error!("Unable to apply rules '{}' to registry '{name}'. Reason: {err}", rules.join(", "))
-                    }
+                    apply_rules(&instance, &state, &order, &in_flight, &running, rules).await
                 })
             }).map_err(|err| Error::TaskCreationFailed(name.clone(), err.to_string()))?;
             sched.add(job).await.map_err(|err| Error::TaskCreationFailed(name.clone(), err.to_string()))?;
@@ -58,6 +115,7 @@ impl Task {
                 Box::pin(async move {
                     let next_tick = _l.next_tick_for_job(_uuid).await;
                     debug!("Next automated cleanup for registry '{name}' is {:?}", next_tick.unwrap_or_default().unwrap_or_default());
+                    sleep(offset).await;
                     info!("Running automated cleanup in registry '{name}'");
                     instance.run_garbage_collector().await;
                 })
@@ -93,3 +151,279 @@ impl Task {
         }
     }
 }
+
+/// Rules of the bundles whose schedule ticked since their last recorded run. Rules which never ran aren't considered missed
+fn missed_rules(registry: &str, bundles: &HashMap<String, Vec<String>>, state: &StateStore, now: DateTime<Utc>) -> Vec<String> {
+    let mut missed = bundles.iter()
+        .filter_map(|(cron, rules)| Schedule::from_str(cron).ok().map(|schedule| (schedule, rules)))
+        .flat_map(|(schedule, rules)| rules.iter().filter(move |rule| {
+            state.last_run(registry, rule).and_then(|last| schedule.after(&last).next()).is_some_and(|next| next <= now)
+        }))
+        .cloned()
+        .collect::<Vec<String>>();
+    missed.sort();
+    missed
+}
+
+/// Counts the runs which are currently in progress to allow waiting for them on shutdown
+#[derive(Default)]
+pub struct InFlight {
+    count: AtomicUsize,
+    notify: Notify
+}
+
+/// Marks a tracked run as finished once dropped
+struct TrackedRun<'a>(&'a InFlight);
+
+impl Drop for TrackedRun<'_> {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::AcqRel);
+        self.0.notify.notify_waiters()
+    }
+}
+
+impl InFlight {
+    /// Count the future as in progress until it completes
+    pub async fn track<F: Future>(&self, run: F) -> F::Output {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        let _run = TrackedRun(self);
+        run.await
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Wait until no run is in progress anymore. Returns `false` when runs are still in progress after the timeout
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.notify.notified();
+                if self.count() == 0 {
+                    break
+                }
+                notified.await
+            }
+        }).await.is_ok()
+    }
+}
+
+/// Rules of an instance which are currently applied. Scheduled, initial, catch-up and requested runs all mark
+/// their rules as running which keeps a rule from overlapping with another run of itself
+#[derive(Default)]
+pub struct RunningRules(Mutex<HashSet<String>>);
+
+/// Marks rules as running until the guard is dropped. This releases the rules even when applying them fails or panics
+struct RunGuard<'a> {
+    running: &'a RunningRules,
+    rules: Vec<String>
+}
+
+impl RunningRules {
+    /// Mark the given rules which aren't running yet as running. Returns `None` when all rules are already running
+    fn acquire(&self, rules: &[String]) -> Option<RunGuard<'_>> {
+        let mut running = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        let rules = rules.iter().filter(|rule| running.insert(rule.to_string())).cloned().collect::<Vec<String>>();
+        if rules.is_empty() {
+            return None
+        }
+        Some(RunGuard { running: self, rules })
+    }
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        let mut running = self.running.0.lock().unwrap_or_else(|err| err.into_inner());
+        for rule in &self.rules {
+            running.remove(rule);
+        }
+    }
+}
+
+/// Apply the given rules to the instance once its co-scheduled dependencies finished and log the outcome. Rules
+/// whose previous run is still in progress are skipped
+pub async fn apply_rules(instance: &Instance, state: &StateStore, order: &RunOrder, in_flight: &InFlight, running: &RunningRules, rules: Vec<String>) {
+    let name = &instance.name;
+    let Some(guard) = running.acquire(&rules) else {
+        warn!("Skipping rules '{}' in registry '{name}' since their previous run is still in progress", rules.join(", "));
+        return
+    };
+    if guard.rules.len() < rules.len() {
+        let skipped = rules.iter().filter(|rule| !guard.rules.contains(rule)).cloned().collect::<Vec<String>>();
+        warn!("Skipping rules '{}' in registry '{name}' since their previous run is still in progress", skipped.join(", "));
+    }
+    let rules = guard.rules.clone();
+    let result = in_flight.track(order.run(name, instance.run_rules(rules.clone(), None, state))).await;
+    let rules = rules.join(", ");
+    match result {
+        Ok(report) if report.errors.is_empty() => info!(registry = name.as_str(), rules = rules.as_str(); "Successfully applied rules '{rules}' to registry '{name}'"),
+        Ok(report) => warn!(registry = name.as_str(), rules = rules.as_str(), errors = report.errors.len(); "Applied rules '{rules}' to registry '{name}' with {} errors", report.errors.len()),
+        Err(err) => error!(registry = name.as_str(), rules = rules.as_str(), error = err.to_string().as_str(); "Unable to apply rules '{rules}' to registry '{name}'. Reason: {err}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use chrono::{Duration, Utc};
+    use crate::order::RunOrder;
+    use crate::state::StateStore;
+    use std::collections::HashMap;
+    use crate::task::{apply_rules, InFlight, missed_rules, RunningRules, Task};
+    use crate::test::{get_mock_instance, get_state_path, mock_catalog, mock_delete, mock_manifest, mock_tag_list};
+
+    #[tokio::test]
+    async fn test_run_on_start() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let state = Arc::new(StateStore::new(get_state_path("run-on-start"), false, None));
+        let order = Arc::new(RunOrder::new(std::time::Duration::ZERO));
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("rule.dev.schedule", "0 0 0 1 1 * *"), ("run_on_start", "true")]);
+        assert!(instance.run_on_start);
+        let mut task = Task::new(instance, state, order, Arc::new(InFlight::default()));
+        assert!(task.start().await.is_ok());
+        for _ in 0..50 {
+            if delete_dev.matched_async().await {
+                break
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        delete_dev.assert_async().await;
+        assert!(task.stop().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_no_run_on_start() {
+        let mut server = mockito::Server::new_async().await;
+        let catalog = mock_catalog(&mut server, vec!["app"]).await.expect(0);
+        let state = Arc::new(StateStore::new(get_state_path("no-run-on-start"), false, None));
+        let order = Arc::new(RunOrder::new(std::time::Duration::ZERO));
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("rule.dev.schedule", "0 0 0 1 1 * *")]);
+        assert!(!instance.run_on_start);
+        let mut task = Task::new(instance, state, order, Arc::new(InFlight::default()));
+        assert!(task.start().await.is_ok());
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        catalog.assert_async().await;
+        assert!(task.stop().await.is_ok());
+    }
+
+    #[test]
+    fn test_run_guard() {
+        let running = RunningRules::default();
+        let rules = vec![String::from("dev"), String::from("old")];
+        let guard = running.acquire(&rules[..1]);
+        assert!(guard.is_some());
+        assert!(running.acquire(&rules[..1]).is_none());
+        assert_eq!(running.acquire(&rules).map(|guard| guard.rules.clone()), Some(vec![String::from("old")]));
+        drop(guard);
+        assert!(running.acquire(&rules).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_guard_released_on_error() {
+        let running = Arc::new(RunningRules::default());
+        let run = {
+            let running = running.clone();
+            tokio::spawn(async move {
+                let _guard = running.acquire(&[String::from("dev")]).unwrap();
+                panic!("Run failed")
+            })
+        };
+        assert!(run.await.is_err());
+        assert!(running.acquire(&[String::from("dev")]).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_runs() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev", "old"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let _old = mock_manifest(&mut server, "app", "old", "sha256:old", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 0).await;
+        let delete_old = mock_delete(&mut server, "app", "sha256:old", 202, "", 1).await;
+        let state = Arc::new(StateStore::new(get_state_path("overlapping-runs"), false, None));
+        let order = Arc::new(RunOrder::new(std::time::Duration::ZERO));
+        let in_flight = Arc::new(InFlight::default());
+
+        let instance = get_mock_instance(&server, vec![
+            ("rule.dev.tag.pattern", "^dev$"), ("rule.dev.schedule", "0 0 0 1 1 * *"),
+            ("rule.old.tag.pattern", "^old$"), ("rule.old.schedule", "0 0 0 1 1 * *")
+        ]);
+        let task = Task::new(instance, state.clone(), order.clone(), in_flight.clone());
+        // The scheduled run of the dev rule is still in progress while all rules are triggered
+        let scheduled = task.running.acquire(&[String::from("dev")]).unwrap();
+        apply_rules(&task.instance, &state, &order, &in_flight, &task.running, task.instance.rule_names()).await;
+        delete_dev.assert_async().await;
+        delete_old.assert_async().await;
+        drop(scheduled);
+        assert!(task.running.acquire(&task.instance.rule_names()).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_wait_in_flight() {
+        let in_flight = Arc::new(InFlight::default());
+        assert!(in_flight.wait(std::time::Duration::ZERO).await);
+        let run = {
+            let in_flight = in_flight.clone();
+            tokio::spawn(async move {
+                in_flight.track(tokio::time::sleep(std::time::Duration::from_millis(100))).await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert_eq!(in_flight.count(), 1);
+        assert!(!in_flight.wait(std::time::Duration::from_millis(10)).await);
+        assert!(in_flight.wait(std::time::Duration::from_secs(1)).await);
+        assert_eq!(in_flight.count(), 0);
+        assert!(run.await.is_ok());
+    }
+
+    #[test]
+    fn test_missed_rules() {
+        let state = StateStore::new(get_state_path("missed-rules"), false, None);
+        let now = Utc::now();
+        let mut bundles = HashMap::new();
+        bundles.insert(String::from("0 0 * * * * *"), vec![String::from("hourly"), String::from("new")]);
+        bundles.insert(String::from("0 0 0 1 1 * *"), vec![String::from("yearly")]);
+        bundles.insert(String::from("invalid"), vec![String::from("invalid")]);
+        state.mark_rules_run("registry", &[String::from("hourly"), String::from("yearly"), String::from("invalid")], now - Duration::hours(2));
+        assert_eq!(missed_rules("registry", &bundles, &state, now), vec![String::from("hourly")]);
+
+        state.mark_rules_run("registry", &[String::from("hourly")], now);
+        assert!(missed_rules("registry", &bundles, &state, now).is_empty());
+        assert!(missed_rules("other", &bundles, &state, now).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_catch_up() {
+        let mut server = mockito::Server::new_async().await;
+        let created = Utc::now() - Duration::days(1);
+        let _catalog = mock_catalog(&mut server, vec!["app"]).await;
+        let _tags = mock_tag_list(&mut server, "app", vec!["dev"]).await;
+        let _dev = mock_manifest(&mut server, "app", "dev", "sha256:dev", created, 10).await;
+        let delete_dev = mock_delete(&mut server, "app", "sha256:dev", 202, "", 1).await;
+        let state = Arc::new(StateStore::new(get_state_path("catch-up"), false, None));
+        state.mark_rules_run("test-registry", &[String::from("dev")], Utc::now() - Duration::days(2));
+        let order = Arc::new(RunOrder::new(std::time::Duration::ZERO));
+
+        let instance = get_mock_instance(&server, vec![("rule.dev.tag.pattern", "dev"), ("rule.dev.schedule", "0 0 0 * * * *"), ("catch_up", "true")]);
+        assert!(instance.catch_up);
+        let mut task = Task::new(instance, state.clone(), order, Arc::new(InFlight::default()));
+        assert!(task.start().await.is_ok());
+        for _ in 0..50 {
+            if delete_dev.matched_async().await {
+                break
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        delete_dev.assert_async().await;
+        assert!(task.stop().await.is_ok());
+    }
+}
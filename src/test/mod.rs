@@ -1,14 +1,25 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use chrono::Duration;
+use bollard::{API_DEFAULT_VERSION, Docker};
+use bollard::secret::EndpointSettings;
+use chrono::{DateTime, Duration, Utc};
+use mockito::{Matcher, Mock, ServerGuard};
+use serde_json::json;
 use crate::api::DistributionConfig;
 use crate::api::repository::Repository;
 use crate::api::tag::Tag;
+use crate::instance::Instance;
 
 pub fn get_distribution_config() -> Arc<DistributionConfig> {
     let config = DistributionConfig::new(String::new(), None, None, true);
     Arc::new(config)
 }
 
+pub fn get_mock_distribution_config(server: &mockito::ServerGuard) -> Arc<DistributionConfig> {
+    let config = DistributionConfig::new(server.host_with_port(), None, None, true);
+    Arc::new(config)
+}
+
 pub fn get_repositories(names: Vec<impl Into<String>>) -> Vec<Repository> {
     let mut repositories = vec![];
     let config = get_distribution_config();
@@ -18,15 +29,119 @@ pub fn get_repositories(names: Vec<impl Into<String>>) -> Vec<Repository> {
     repositories
 }
 
+pub fn get_repositories_with_tag_count(raw: Vec<(impl Into<String>, usize)>) -> Vec<Repository> {
+    let mut repositories = vec![];
+    let config = get_distribution_config();
+    for (name, count) in raw {
+        let mut repository = Repository::new(name.into(), config.clone());
+        repository.tag_count = Some(count);
+        repositories.push(repository)
+    }
+    repositories
+}
+
 pub fn get_tags(raw: Vec<(impl Into<String>, Duration, u64)>) -> Vec<Tag> {
     let mut tags = vec![];
     let now = chrono::offset::Utc::now();
     for (name, offset, size) in raw {
-        tags.push(Tag::new(name.into(), String::new(), now + offset, size))
+        let name = name.into();
+        // every tag gets its own digest since tags sharing a digest are deleted together
+        tags.push(Tag::new(name.clone(), format!("sha256:{name}"), now + offset, size))
     }
     tags
 }
 
+pub fn get_tags_with_digest(raw: Vec<(impl Into<String>, impl Into<String>)>) -> Vec<Tag> {
+    let now = chrono::offset::Utc::now();
+    raw.into_iter().map(|(name, digest)| Tag::new(name.into(), digest.into(), now, 1)).collect()
+}
+
 pub fn get_tags_by_name(raw: Vec<impl Into<String>>, duration: Duration, size: u64) -> Vec<Tag> {
     get_tags(raw.into_iter().map(|x| (x, duration, size)).collect())
-}
\ No newline at end of file
+}
+
+/// Get a docker client which isn't connected to any daemon. Any request made with it fails
+pub fn get_docker_client() -> Arc<Docker> {
+    Arc::new(Docker::connect_with_http("http://127.0.0.1:1", 1, API_DEFAULT_VERSION).expect("Docker client should be valid"))
+}
+
+/// Get an instance which uses the mocked registry running on `server`. The labels are expected without
+/// the `abwart.` prefix
+pub fn get_mock_instance(server: &ServerGuard, labels: Vec<(&str, &str)>) -> Instance {
+    build_mock_instance(server, labels, get_docker_client())
+}
+
+/// Get an instance which uses the mocked registry running on `registry` and the mocked docker daemon
+/// running on `docker`. The labels are expected without the `abwart.` prefix
+pub fn get_mock_docker_instance(registry: &ServerGuard, docker: &ServerGuard, labels: Vec<(&str, &str)>) -> Instance {
+    let client = Docker::connect_with_http(&format!("http://{}", docker.host_with_port()), 5, API_DEFAULT_VERSION).expect("Docker client should be valid");
+    build_mock_instance(registry, labels, Arc::new(client))
+}
+
+fn build_mock_instance(server: &ServerGuard, labels: Vec<(&str, &str)>, client: Arc<Docker>) -> Instance {
+    let mut labels = labels.into_iter()
+        .map(|(key, value)| (crate::label(key), value.to_string()))
+        .collect::<HashMap<_, _>>();
+    labels.insert(crate::label("port"), server.socket_address().port().to_string());
+    let networks = HashMap::from([(String::from("bridge"), EndpointSettings { ip_address: Some(String::from("127.0.0.1")), ..EndpointSettings::default() })]);
+    Instance::new(String::from("test-id"), String::from("test-registry"), labels, networks, client).expect("Instance should be valid")
+}
+
+/// Mock the catalog of a registry containing the given repositories
+pub async fn mock_catalog(server: &mut ServerGuard, repositories: Vec<&str>) -> Mock {
+    server.mock("GET", "/v2/_catalog")
+        .match_query(Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "repositories": repositories }).to_string())
+        .create_async()
+        .await
+}
+
+/// Mock the tag list of a repository
+pub async fn mock_tag_list(server: &mut ServerGuard, repository: &str, tags: Vec<&str>) -> Mock {
+    server.mock("GET", format!("/v2/{repository}/tags/list").as_str())
+        .match_query(Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "name": repository, "tags": tags }).to_string())
+        .create_async()
+        .await
+}
+
+/// Mock a single-arch manifest referenced by `reference` together with its config blob
+pub async fn mock_manifest(server: &mut ServerGuard, repository: &str, reference: &str, digest: &str, created: DateTime<Utc>, size: u64) -> (Mock, Mock) {
+    let config_digest = format!("{digest}-config");
+    let manifest = server.mock("GET", format!("/v2/{repository}/manifests/{reference}").as_str())
+        .with_header("content-type", "application/vnd.docker.distribution.manifest.v2+json")
+        .with_header("Docker-Content-Digest", digest)
+        .with_body(json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": { "mediaType": "application/vnd.docker.container.image.v1+json", "digest": config_digest, "size": 1 },
+            "layers": [{ "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip", "digest": format!("{digest}-layer"), "size": size }]
+        }).to_string())
+        .create_async()
+        .await;
+    let config = server.mock("GET", format!("/v2/{repository}/blobs/{config_digest}").as_str())
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "created": created }).to_string())
+        .create_async()
+        .await;
+    (manifest, config)
+}
+
+/// Mock the deletion of a manifest by its digest. The mock expects to be called `hits` times
+pub async fn mock_delete(server: &mut ServerGuard, repository: &str, digest: &str, status: usize, body: &str, hits: usize) -> Mock {
+    server.mock("DELETE", format!("/v2/{repository}/manifests/{digest}").as_str())
+        .with_status(status)
+        .with_body(body)
+        .expect(hits)
+        .create_async()
+        .await
+}
+
+/// Get the path of a non-existent state file in the temp directory which is unique per name
+pub fn get_state_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("abwart-state-{}-{name}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}